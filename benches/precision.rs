@@ -0,0 +1,43 @@
+// Training time at the network's float precision. Run it once per precision
+// and compare the two:
+//
+//     cargo bench --bench precision
+//     cargo bench --bench precision --features f32
+
+use std::time::Instant;
+
+use ndarray::Array2;
+use ndarray_rand::RandomExt;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
+use water_potability_nn::data_loader::Dataset;
+use water_potability_nn::neural_network::{train_network, Float, NetworkBuilder, TrainingParams};
+
+const SAMPLES: usize = 4096;
+const FEATURES: usize = 9;
+const EPOCHS: usize = 20;
+const RUNS: usize = 3;
+
+fn main() {
+    // Labels from a fixed linear rule, so the final loss is comparable across precisions
+    let mut rng = StdRng::seed_from_u64(7);
+    let features: Array2<f64> = Array2::random_using((SAMPLES, FEATURES), StandardNormal, &mut rng);
+    let labels = features.map_axis(ndarray::Axis(1), |row| if row[0] - 0.5 * row[3] + row[6] > 0.0 { 1.0 } else { 0.0 });
+    let dataset = Dataset { features, labels: labels.insert_axis(ndarray::Axis(1)), weights: None };
+    let params = TrainingParams { epochs: EPOCHS, batch_size: 64, learning_rate: 0.05, ..TrainingParams::default() };
+
+    println!("{}-bit floats, {} samples, {} epochs per run", 8 * std::mem::size_of::<Float>(), SAMPLES, EPOCHS);
+    for hidden in [[32, 32], [128, 128], [512, 512]] {
+        let mut best = f64::INFINITY;
+        let mut loss = f64::NAN;
+        for _ in 0..RUNS {
+            let mut network = NetworkBuilder::new(FEATURES).hidden_layers(&hidden).seed(1).build().expect("valid network");
+            let started = Instant::now();
+            let outcome = train_network(&mut network, &dataset, &params, |_, _, _| {}).expect("training succeeds");
+            best = best.min(started.elapsed().as_secs_f64());
+            loss = outcome.final_metrics.map_or(f64::NAN, |state| state.loss);
+        }
+        println!("{:>3}x{:<3} hidden: {:8.1} ms/epoch, final loss {:.6}", hidden[0], hidden[1], best * 1000.0 / EPOCHS as f64, loss);
+    }
+}
@@ -0,0 +1,68 @@
+// Cost of scoring a large validation set after every epoch with the default
+// settings. The validation rows are already scaled and go through the network
+// once per epoch without keeping any layer's activations, so the added time
+// is one forward pass:
+//
+//     cargo bench --bench validation
+
+use std::time::Instant;
+
+use ndarray::{Array2, Axis};
+use ndarray_rand::RandomExt;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
+use water_potability_nn::data_loader::Dataset;
+use water_potability_nn::neural_network::{create_network, train_network, train_network_validated, TrainingParams};
+
+const TRAINING_SAMPLES: usize = 2_000;
+const VALIDATION_SAMPLES: usize = 100_000;
+const FEATURES: usize = 9;
+const EPOCHS: usize = 10;
+
+fn dataset(samples: usize, rng: &mut StdRng) -> Dataset {
+    let features: Array2<f64> = Array2::random_using((samples, FEATURES), StandardNormal, rng);
+    let labels = features.map_axis(Axis(1), |row| if row[0] - 0.5 * row[3] + row[6] > 0.0 { 1.0 } else { 0.0 });
+    Dataset { features, labels: labels.insert_axis(Axis(1)), weights: None }
+}
+
+// Milliseconds per epoch, the best of three runs
+fn time_per_epoch(params: &TrainingParams, data: &Dataset, validation: Option<&Dataset>) -> f64 {
+    (0..3)
+        .map(|_| {
+            let mut network = create_network(params).expect("valid network");
+            let started = Instant::now();
+            match validation {
+                Some(validation) => train_network_validated(&mut network, data, validation, params, |_, _, _| {}, |_| {}),
+                None => train_network(&mut network, data, params, |_, _, _| {}),
+            }
+            .expect("training succeeds");
+            started.elapsed().as_secs_f64() * 1000.0 / EPOCHS as f64
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let data = dataset(TRAINING_SAMPLES, &mut rng);
+    let validation = dataset(VALIDATION_SAMPLES, &mut rng);
+    let params = TrainingParams {
+        input_dim: FEATURES,
+        epochs: EPOCHS,
+        hidden_layers: 2,
+        batch_size: 64,
+        seed: Some(1),
+        ..TrainingParams::default()
+    };
+
+    println!("{} training rows, {} validation rows scored after each of {} epochs", TRAINING_SAMPLES, VALIDATION_SAMPLES, EPOCHS);
+    for (name, neurons_per_layer) in [("64 neurons", 64), ("256 neurons", 256)] {
+        let params = TrainingParams { neurons_per_layer, ..params.clone() };
+        let plain = time_per_epoch(&params, &data, None);
+        let validated = time_per_epoch(&params, &data, Some(&validation));
+        println!(
+            "{:<11}: {:7.1} ms/epoch, {:7.1} ms/epoch with validation (+{:.1} ms)",
+            name, plain, validated, validated - plain
+        );
+    }
+}
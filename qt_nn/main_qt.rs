@@ -24,29 +24,46 @@ fn main() {
     thread::spawn(move || {
         println!("Starting neural network training thread");
         
-        // Initialize neural network
-        let mut network = neural_network::create_network();
-        let data = data_loader::load_water_data("data/water_potability.csv");
+        let data = match data_loader::load_water_data("data/water_potability.csv", data_loader::DEFAULT_LABEL_COLUMN) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("Failed to load training data: {}", e);
+                return;
+            }
+        };
         
-        // Listen for parameter updates
-        while let Ok(params) = params_receiver.recv() {
+        // Listen for parameter updates until the UI drops its sender
+        loop {
+            let params = match params_receiver.recv() {
+                Ok(params) => params,
+                Err(_) => {
+                    println!("Parameter channel closed, training thread exiting");
+                    break;
+                }
+            };
             let params = params.lock().unwrap().clone();
             println!("Received new training parameters: {:?}", params);
             
             if params.restart_training {
-                // Train network with new parameters
-                train_network(&mut network, &data, params.epochs, params.hidden_layers, 
-                              params.neurons_per_layer, params.learning_rate, 
-                              |epoch, accuracy, loss| {
-                                  // Only send update every few epochs to avoid overwhelming UI
-                                  if epoch % 10 == 0 || epoch == 1 {
-                                      sender.send((accuracy, loss)).unwrap_or_else(|_| {
-                                          println!("Failed to send training update");
-                                      });
-                                  }
-                              });
+                // Build a fresh network for the requested architecture and train it
+                let result = neural_network::create_network(&params).and_then(|mut network| {
+                    train_network(&mut network, &data, &params, |epoch, accuracy, loss| {
+                        // Only send update every few epochs to avoid overwhelming UI
+                        if epoch % 10 == 0 || epoch == 1 {
+                            sender.send((accuracy, loss)).unwrap_or_else(|_| {
+                                println!("Failed to send training update");
+                            });
+                        }
+                    })
+                });
+                if let Err(e) = result {
+                    println!("Training failed: {}", e);
+                }
             }
         }
+        
+        // Release the dataset before the thread ends
+        drop(data);
     });
     
     // Start prediction handling thread
@@ -54,10 +71,18 @@ fn main() {
         println!("Starting prediction handling thread");
         
         // Initialize neural network for predictions
-        let network = neural_network::create_network();
+        let network = neural_network::create_network(&TrainingParams::default())
+            .expect("default network parameters are valid");
         
-        // Listen for prediction requests
-        while let Ok(water_params) = water_params_receiver.recv() {
+        // Listen for prediction requests until the UI drops its sender
+        loop {
+            let water_params = match water_params_receiver.recv() {
+                Ok(water_params) => water_params,
+                Err(_) => {
+                    println!("Prediction channel closed, prediction thread exiting");
+                    break;
+                }
+            };
             println!("Received prediction request: {:?}", water_params);
             
             // Make prediction
@@ -74,6 +99,8 @@ fn main() {
                 }
             }
         }
+        
+        drop(network);
     });
     
     // Launch Qt application
@@ -6,6 +6,10 @@ use std::sync::{Arc, Mutex};
 
 use crate::TrainingParams;
 use crate::PredictionResult;
+use crate::data_loader::{parse_feature, NUM_FEATURES};
+
+// Style of a line edit holding a value that cannot be used
+const INVALID_INPUT_STYLE: &str = "border: 2px solid #dc3232;";
 
 // Qt wrapper for the neural network backend
 pub struct NeuralNetworkQt {
@@ -236,19 +240,41 @@ impl NeuralNetworkQt {
         
         // Connect predict button
         self.predict_button.connect_clicked(move || {
-            // Gather water parameters from inputs
-            let water_params = [
-                self.ph_input.text().parse().unwrap_or(7.5),
-                self.hardness_input.text().parse().unwrap_or(150.0),
-                self.solids_input.text().parse().unwrap_or(500.0),
-                self.chloramines_input.text().parse().unwrap_or(5.0),
-                self.sulfate_input.text().parse().unwrap_or(250.0),
-                self.conductivity_input.text().parse().unwrap_or(350.0),
-                self.organic_carbon_input.text().parse().unwrap_or(5.0),
-                self.trihalomethanes_input.text().parse().unwrap_or(30.0),
-                self.turbidity_input.text().parse().unwrap_or(2.0),
+            // Gather water parameters from inputs, in FEATURE_NAMES order
+            let inputs = [
+                &self.ph_input,
+                &self.hardness_input,
+                &self.solids_input,
+                &self.chloramines_input,
+                &self.sulfate_input,
+                &self.conductivity_input,
+                &self.organic_carbon_input,
+                &self.trihalomethanes_input,
+                &self.turbidity_input,
             ];
             
+            // Same checks as the egui frontend: a typo or impossible value
+            // highlights its field and blocks the prediction
+            let mut water_params = [0.0; NUM_FEATURES];
+            let mut first_error = None;
+            for (i, input) in inputs.iter().enumerate() {
+                match parse_feature(i, &input.text()) {
+                    Ok(value) => {
+                        water_params[i] = value;
+                        input.set_style_sheet("");
+                    }
+                    Err(e) => {
+                        input.set_style_sheet(INVALID_INPUT_STYLE);
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            if let Some(e) = first_error {
+                self.result_label.set_style_sheet("");
+                self.result_label.set_text(&format!("Fix the highlighted input: {}", e));
+                return;
+            }
+            
             // Request prediction (in real implementation)
             // Display results in result_label
         });
@@ -268,13 +294,10 @@ impl NeuralNetworkQt {
     
     // Display prediction result
     pub fn display_prediction(&self, result: PredictionResult) {
-        let result_text = if result.is_potable {
-            format!("POTABLE - Confidence: {:.2}%", result.probability * 100.0)
-        } else {
-            format!("NOT POTABLE - Confidence: {:.2}%", result.probability * 100.0)
-        };
-        
-        self.result_label.set_text(&result_text);
+        // Same wording as the egui prediction panel, green for potable and red otherwise
+        let color = if result.is_potable { "#32cd32" } else { "#dc3232" };
+        self.result_label.set_style_sheet(&format!("color: {}; font-weight: bold;", color));
+        self.result_label.set_text(&result.to_string());
     }
     
     // Run the application
@@ -0,0 +1,451 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use water_potability_nn::cross_validation;
+use water_potability_nn::data_loader::{self, ColumnSpec, CsvFormat, Dataset, Scaler};
+use water_potability_nn::jsonl::JsonlMetrics;
+use water_potability_nn::metrics;
+use water_potability_nn::neural_network::{self, Network, TrainingCallback, TrainingParams};
+use water_potability_nn::NnError;
+
+// Epoch budget of each learning-rate sweep run unless --epochs is given
+const LR_SWEEP_EPOCHS: usize = 200;
+// Epoch budget of each cross-validation fold unless --epochs is given
+const CV_EPOCHS: usize = 500;
+// Seed shared by every headless run unless --seed is given
+const DEFAULT_SEED: u64 = 42;
+// Fraction of the shuffled data held out to score the sweep runs
+const VALIDATION_FRACTION: f64 = 0.2;
+// Replay speed unless --replay-speed is given
+const REPLAY_EPOCHS_PER_SECOND: f64 = 100.0;
+// Model served unless --model is given, where the GUI saves by default
+const SERVE_MODEL: &str = "model.bin";
+
+const USAGE: &str = "Usage: water_potability_nn [--lr-sweep LR,LR,... | --cv K] [--epochs N] [--seed N] [--metrics-jsonl PATH|-] [--record PATH] [--workers N] [--csv-format FORMAT]\n       \
+                     water_potability_nn --replay PATH [--replay-speed EPOCHS_PER_SECOND]\n       \
+                     water_potability_nn --serve ADDR [--model MODEL]\n       \
+                     water_potability_nn --compare MODEL_A MODEL_B [--data CSV] [--csv-format FORMAT]\n       \
+                     water_potability_nn --check [--csv-format FORMAT]\n       \
+                     water_potability_nn --stats CSV [--csv-format FORMAT]\n\
+                     FORMAT is the CSV delimiter then the decimal separator, e.g. ';,' or 'tab.'; detected when not given";
+
+// What the binary was asked to do
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Gui,
+    LrSweep { rates: Vec<f64>, epochs: usize, seed: u64 },
+    CrossValidate { folds: usize, epochs: usize, seed: u64 },
+    Compare { model_a: String, model_b: String, data: Option<String> },
+    Check,
+    Stats { path: String },  // Dataset summary, nothing is trained
+    Replay { path: String, epochs_per_second: f64 },  // GUI fed from a recording instead of training
+    Serve { addr: String, model: String },
+}
+
+// Parsed command line: the mode plus options shared by the training modes
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub metrics_jsonl: Option<String>,  // Per-epoch JSON lines file, "-" for stdout
+    pub record: Option<String>,  // Recording of each GUI run, replaced by the next
+    pub workers: Option<usize>,  // Cross-validation folds or variance seeds trained at once, overriding the setting
+    pub csv_format: Option<CsvFormat>,  // Overrides the detected format of the data files read
+}
+
+// Parses the arguments after the program name
+pub fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut metrics_jsonl = None;
+    let mut record = None;
+    let mut workers = None;
+    let mut csv_format = None;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--metrics-jsonl" {
+            metrics_jsonl = Some(args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))?.clone());
+        } else if arg == "--record" {
+            record = Some(args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))?.clone());
+        } else if arg == "--workers" {
+            let text = args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))?;
+            workers = Some(text.parse::<usize>().ok().filter(|&n| n > 0)
+                .ok_or_else(|| format!("Invalid worker count '{}'", text))?);
+        } else if arg == "--csv-format" {
+            csv_format = Some(args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))?.parse::<CsvFormat>()?);
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+
+    let command = parse_command(&rest)?;
+    if metrics_jsonl.is_some() && !matches!(command, Command::Gui | Command::LrSweep { .. }) {
+        return Err(format!("--metrics-jsonl applies to GUI training and --lr-sweep\n{}", USAGE));
+    }
+    if record.is_some() && command != Command::Gui {
+        return Err(format!("--record applies to GUI training\n{}", USAGE));
+    }
+    if workers.is_some() && !matches!(command, Command::Gui | Command::CrossValidate { .. }) {
+        return Err(format!("--workers applies to the GUI's variance analysis and --cv\n{}", USAGE));
+    }
+    if csv_format.is_some() && matches!(command, Command::Replay { .. } | Command::Serve { .. }) {
+        return Err(format!("--csv-format applies to the modes that read a data file\n{}", USAGE));
+    }
+    Ok(Args { command, metrics_jsonl, record, workers, csv_format })
+}
+
+// Opens the --metrics-jsonl target: stdout for "-", otherwise the file, appended to
+pub fn open_metrics_jsonl(path: &str) -> io::Result<JsonlMetrics<Box<dyn Write + Send>>> {
+    let writer: Box<dyn Write + Send> = if path == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(OpenOptions::new().create(true).append(true).open(path)?)
+    };
+    Ok(JsonlMetrics::new(writer))
+}
+
+fn parse_command(args: &[String]) -> Result<Command, String> {
+    let mut rates = None;
+    let mut folds = None;
+    let mut epochs = None;
+    let mut seed = DEFAULT_SEED;
+    let mut models = None;
+    let mut data = None;
+    let mut check = false;
+    let mut stats = None;
+    let mut replay = None;
+    let mut replay_speed = None;
+    let mut serve = None;
+    let mut model = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--lr-sweep" => {
+                let list = value()?;
+                let parsed = list
+                    .split(',')
+                    .map(|lr| match lr.trim().parse::<f64>() {
+                        Ok(lr) if lr > 0.0 => Ok(lr),
+                        _ => Err(format!("Invalid learning rate '{}' in --lr-sweep", lr)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                rates = Some(parsed);
+            }
+            "--cv" => {
+                let text = value()?;
+                folds = Some(text.parse::<usize>().ok().filter(|&k| k >= 2)
+                    .ok_or_else(|| format!("Invalid fold count '{}', need at least 2", text))?);
+            }
+            "--epochs" => {
+                let text = value()?;
+                epochs = Some(text.parse::<usize>().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid epoch count '{}'", text))?);
+            }
+            "--compare" => {
+                let model_a = value()?.clone();
+                let model_b = value()?.clone();
+                models = Some((model_a, model_b));
+            }
+            "--data" => data = Some(value()?.clone()),
+            "--check" => check = true,
+            "--stats" => stats = Some(value()?.clone()),
+            "--replay" => replay = Some(value()?.clone()),
+            "--serve" => serve = Some(value()?.clone()),
+            "--model" => model = Some(value()?.clone()),
+            "--replay-speed" => {
+                let text = value()?;
+                replay_speed = Some(text.parse::<f64>().ok().filter(|&speed| speed > 0.0 && speed.is_finite())
+                    .ok_or_else(|| format!("Invalid replay speed '{}'", text))?);
+            }
+            "--seed" => {
+                let text = value()?;
+                seed = text.parse().map_err(|_| format!("Invalid seed '{}'", text))?;
+            }
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    if let Some(path) = stats {
+        let other_mode = serve.is_some() || check || replay.is_some() || rates.is_some() || folds.is_some() || models.is_some();
+        if other_mode || data.is_some() || model.is_some() {
+            return Err(format!("--stats cannot be combined with another mode\n{}", USAGE));
+        }
+        return Ok(Command::Stats { path });
+    }
+    if let Some(addr) = serve {
+        if check || replay.is_some() || rates.is_some() || folds.is_some() || models.is_some() {
+            return Err(format!("--serve cannot be combined with another mode\n{}", USAGE));
+        }
+        return Ok(Command::Serve { addr, model: model.unwrap_or_else(|| SERVE_MODEL.to_string()) });
+    }
+    if model.is_some() {
+        return Err(format!("--model only applies to --serve\n{}", USAGE));
+    }
+    if let Some(path) = replay {
+        if check || rates.is_some() || folds.is_some() || models.is_some() {
+            return Err(format!("--replay cannot be combined with another mode\n{}", USAGE));
+        }
+        return Ok(Command::Replay { path, epochs_per_second: replay_speed.unwrap_or(REPLAY_EPOCHS_PER_SECOND) });
+    }
+    if replay_speed.is_some() {
+        return Err(format!("--replay-speed only applies to --replay\n{}", USAGE));
+    }
+    if check {
+        if rates.is_some() || folds.is_some() || models.is_some() {
+            return Err(format!("--check cannot be combined with another mode\n{}", USAGE));
+        }
+        return Ok(Command::Check);
+    }
+    if let Some((model_a, model_b)) = models {
+        if rates.is_some() || folds.is_some() {
+            return Err(format!("--compare cannot be combined with --lr-sweep or --cv\n{}", USAGE));
+        }
+        return Ok(Command::Compare { model_a, model_b, data });
+    }
+    if data.is_some() {
+        return Err(format!("--data only applies to --compare\n{}", USAGE));
+    }
+
+    match (rates, folds) {
+        (Some(_), Some(_)) => Err(format!("--lr-sweep and --cv cannot be combined\n{}", USAGE)),
+        (Some(rates), None) => Ok(Command::LrSweep { rates, epochs: epochs.unwrap_or(LR_SWEEP_EPOCHS), seed }),
+        (None, Some(folds)) => Ok(Command::CrossValidate { folds, epochs: epochs.unwrap_or(CV_EPOCHS), seed }),
+        (None, None) => Ok(Command::Gui),
+    }
+}
+
+// Trains a short run per learning rate on the same split and seed and prints
+// the validation loss and accuracy of each, marking the lowest loss. The seed
+// also fixes the split, so repeated sweeps are comparable.
+pub fn run_lr_sweep(
+    data_path: &str,
+    rates: &[f64],
+    epochs: usize,
+    seed: u64,
+    metrics_jsonl: Option<&str>,
+    csv_format: Option<CsvFormat>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let raw = data_loader::shuffle_data_with(
+        &data_loader::load_potability_data(data_path, csv_format)?,
+        &mut StdRng::seed_from_u64(seed),
+    );
+    data_loader::check_split(&raw, VALIDATION_FRACTION)?;
+    let (train, validation) = data_loader::train_validation_split(&raw, VALIDATION_FRACTION);
+
+    let base = TrainingParams { epochs, seed: Some(seed), ..TrainingParams::default() };
+    let scaler = Scaler::fit(&train.features, base.scale_mode);
+    let scaled = Dataset { features: scaler.transform(&train.features), labels: train.labels.clone(), weights: train.weights.clone() };
+
+    println!(
+        "Learning rate sweep: {} epochs, seed {}, {} training / {} validation samples",
+        epochs, seed, train.n_samples(), validation.n_samples()
+    );
+
+    let mut results = Vec::with_capacity(rates.len());
+    for &learning_rate in rates {
+        let params = TrainingParams { learning_rate, ..base.clone() };
+        let mut network = neural_network::create_network(&params)?;
+        network.set_scaler(scaler.clone());
+        let mut stream = metrics_jsonl.map(open_metrics_jsonl).transpose()?.map(|stream| stream.with_run(format!("lr={}", learning_rate)));
+        // A diverging rate is a result of the sweep, not a reason to stop it
+        let outcome = match neural_network::train_network(&mut network, &scaled, &params, &mut stream as &mut dyn TrainingCallback) {
+            Ok(_) => Ok(metrics::evaluate(&network, &validation)),
+            Err(NnError::Diverged { epoch, .. }) => Err(epoch),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(stream) = stream {
+            stream.finish()?;
+        }
+        results.push((learning_rate, outcome));
+    }
+
+    let best = results
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (_, outcome))| outcome.as_ref().ok().filter(|eval| eval.loss.is_finite()).map(|eval| (i, eval.loss)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i);
+
+    println!("{:>12} {:>10} {:>9}", "lr", "val loss", "val acc");
+    for (i, (learning_rate, outcome)) in results.iter().enumerate() {
+        match outcome {
+            Ok(eval) => {
+                let marker = if Some(i) == best { "  <- best" } else { "" };
+                println!("{:>12} {:>10.4} {:>8.2}%{}", learning_rate, eval.loss, eval.accuracy, marker);
+            }
+            Err(epoch) => println!("{:>12}   diverged at epoch {}", learning_rate, epoch),
+        }
+    }
+    Ok(())
+}
+
+// Runs k-fold cross-validation, training up to `workers` folds at once on
+// their own threads, and prints per-fold and mean validation metrics plus
+// the time saved
+pub fn run_cross_validation(
+    data_path: &str,
+    folds: usize,
+    epochs: usize,
+    seed: u64,
+    workers: usize,
+    csv_format: Option<CsvFormat>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let raw = data_loader::shuffle_data_with(
+        &data_loader::load_potability_data(data_path, csv_format)?,
+        &mut StdRng::seed_from_u64(seed),
+    );
+    let params = TrainingParams { epochs, seed: Some(seed), ..TrainingParams::default() };
+
+    println!(
+        "{}-fold cross-validation: {} epochs, seed {}, {} samples, up to {} folds at once",
+        folds, epochs, seed, raw.n_samples(), workers.min(folds)
+    );
+    let cv = cross_validation::cross_validate(&raw, &params, folds, workers)?;
+
+    println!("{:>6} {:>10} {:>9} {:>9}", "fold", "val loss", "val acc", "time");
+    for fold in &cv.folds {
+        println!(
+            "{:>6} {:>10.4} {:>8.2}% {:>8.1}s",
+            fold.fold + 1, fold.evaluation.loss, fold.evaluation.accuracy, fold.elapsed.as_secs_f64()
+        );
+    }
+    println!("{:>6} {:>10.4} {:>8.2}%", "mean", cv.mean_loss(), cv.mean_accuracy());
+
+    let sequential = cv.sequential_time().as_secs_f64();
+    let wall = cv.wall_time.as_secs_f64();
+    println!(
+        "Wall clock {:.1}s versus {:.1}s sequentially (saved {:.1}s, {:.1}x)",
+        wall, sequential, (sequential - wall).max(0.0), sequential / wall.max(f64::EPSILON)
+    );
+    Ok(())
+}
+
+// Scores two saved models on the same labeled CSV and prints their metrics
+// side by side with the B - A difference. Both models must take the file's
+// feature count and agree on whether they carry a scaler, i.e. both expect
+// raw measurements or both expect pre-scaled input; otherwise one of them
+// would be scored on inputs it was not trained for.
+pub fn run_compare(model_a: &str, model_b: &str, data_path: &str, csv_format: Option<CsvFormat>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let dataset = data_loader::load_potability_data(data_path, csv_format)?;
+    let n_features = dataset.features.ncols();
+    let a = neural_network::load_model_for(model_a, n_features)?;
+    let b = neural_network::load_model_for(model_b, n_features)?;
+
+    let describe = |network: &Network| network.scaler().map_or("no scaler".to_string(), |s| format!("{:?} scaler", s.mode()));
+    match (a.scaler(), b.scaler()) {
+        (Some(_), None) | (None, Some(_)) => {
+            return Err(format!(
+                "Cannot compare fairly: {} has {} but {} has {}, so they expect differently scaled inputs",
+                model_a, describe(&a), model_b, describe(&b)
+            ).into());
+        }
+        (None, None) => println!("Neither model carries a scaler, both are fed the features unscaled"),
+        (Some(_), Some(_)) => {}
+    }
+
+    println!("A: {} ({}, {})", model_a, a.summary(), describe(&a));
+    println!("B: {} ({}, {})", model_b, b.summary(), describe(&b));
+    println!("Evaluated on {} samples from {}", dataset.n_samples(), data_path);
+
+    let (eval_a, eval_b) = (metrics::evaluate(&a, &dataset), metrics::evaluate(&b, &dataset));
+    println!("{:<10} {:>10} {:>10} {:>10}", "metric", "A", "B", "B - A");
+    for (name, value_a, value_b) in [
+        ("accuracy", eval_a.accuracy, eval_b.accuracy),
+        ("loss", eval_a.loss, eval_b.loss),
+        ("precision", eval_a.precision, eval_b.precision),
+        ("recall", eval_a.recall, eval_b.recall),
+        ("f1", eval_a.f1, eval_b.f1),
+    ] {
+        println!("{:<10} {:>10.4} {:>10.4} {:>+10.4}", name, value_a, value_b, value_b - value_a);
+    }
+    Ok(())
+}
+
+// Loads and scales the data and builds the default network like a GUI run
+// would, runs one forward pass and reports what training would need, without
+// training. Any data or configuration error surfaces as the returned error.
+pub fn run_check(data_path: &str, csv_format: Option<CsvFormat>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let raw = data_loader::load_potability_data(data_path, csv_format)?;
+    println!("Data: {} samples with {} features from {}", raw.n_samples(), raw.features.ncols(), data_path);
+
+    let params = TrainingParams::default();
+    let scaler = Scaler::fit(&raw.features, params.scale_mode);
+    if !scaler.constant_columns().is_empty() {
+        println!("Warning: constant feature columns {:?} carry no information", scaler.constant_columns());
+    }
+    let scaled = Dataset { features: scaler.transform(&raw.features), labels: raw.labels, weights: raw.weights };
+
+    let check = neural_network::dry_run(&scaled, &params)?;
+    println!("Network: {}", check.summary);
+    println!("Forward pass on a batch of {} samples: ok", check.batch_size);
+    println!("Estimated training memory: {:.2} MiB", check.estimated_bytes as f64 / (1024.0 * 1024.0));
+    println!("Configuration is valid, no training was run");
+    Ok(())
+}
+
+// Prints the row count, class balance and per-column statistics and missing
+// values of a labeled CSV, to settle on imputation and scaling before training
+pub fn run_stats(data_path: &str, csv_format: Option<CsvFormat>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut spec = ColumnSpec::default().label_header(data_loader::LABEL_HEADER);
+    if let Some(format) = csv_format {
+        spec = spec.format(format);
+    }
+    let loaded = data_loader::load_data_with_spec(data_path, data_loader::DEFAULT_LABEL_COLUMN, &spec)?;
+    let dataset = &loaded.dataset;
+    let n = dataset.n_samples();
+    println!(
+        "{}: {} complete rows with {} features, {} rows skipped for missing values",
+        data_path, n, dataset.features.ncols(), loaded.skipped_rows
+    );
+
+    let labels = dataset.labels.column(0);
+    if labels.iter().all(|&y| y == 0.0 || y == 1.0) {
+        let positives = labels.iter().filter(|&&y| y == 1.0).count();
+        let share = |count: usize| 100.0 * count as f64 / n.max(1) as f64;
+        println!(
+            "Class balance: {} potable ({:.1}%), {} not potable ({:.1}%)",
+            positives, share(positives), n - positives, share(n - positives)
+        );
+    } else {
+        let (min, max) = labels.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &y| (min.min(y), max.max(y)));
+        println!("Labels are continuous: min {:.4}, max {:.4}, mean {:.4}", min, max, labels.mean().unwrap_or(0.0));
+    }
+
+    // Missing counts are per CSV column, so the label's is listed on its own and the weights' not at all
+    let (label_name, label_missing) = &loaded.missing_values[loaded.label_column];
+    let feature_missing = loaded
+        .missing_values
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| j != loaded.label_column && Some(j) != loaded.weight_column);
+    println!("{:<16} {:>12} {:>12} {:>12} {:>12} {:>8}", "column", "min", "max", "mean", "std", "missing");
+    for (stats, (_, (name, missing))) in data_loader::feature_stats(dataset).iter().zip(feature_missing) {
+        if n == 0 {
+            println!("{:<16} {:>12} {:>12} {:>12} {:>12} {:>8}", name, "-", "-", "-", "-", missing);
+        } else {
+            println!("{:<16} {:>12.4} {:>12.4} {:>12.4} {:>12.4} {:>8}", name, stats.min, stats.max, stats.mean, stats.std, missing);
+        }
+    }
+    println!("{:<16} {:>12} {:>12} {:>12} {:>12} {:>8}", label_name, "", "", "", "", label_missing);
+    Ok(())
+}
+
+// Answers POST /predict on `addr` with the saved model until the process is stopped
+#[cfg(feature = "serve")]
+pub fn run_serve(addr: &str, model: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let network = neural_network::load_model_for(model, data_loader::NUM_FEATURES)?;
+    if network.scaler().is_none() {
+        println!("Warning: {} carries no scaler, requests must send already scaled features", model);
+    }
+    let listener = std::net::TcpListener::bind(addr)?;
+    println!("Serving {} ({}) at http://{}/predict", model, network.summary(), listener.local_addr()?);
+    water_potability_nn::serve::serve(&network, listener)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "serve"))]
+pub fn run_serve(_addr: &str, _model: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    Err("--serve needs a build with the serve feature: cargo run --release --features serve -- --serve ADDR".into())
+}
@@ -0,0 +1,138 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::data_loader::{balance_classes, Balance, Dataset, Scaler};
+use crate::error::NnError;
+use crate::metrics::{evaluate, Evaluation};
+use crate::neural_network::{create_network, train_network, TrainingParams};
+
+/// Validation result of one fold.
+#[derive(Clone, Debug)]
+pub struct FoldResult {
+    pub fold: usize,
+    /// Seed the fold's network was initialised with.
+    pub seed: u64,
+    pub evaluation: Evaluation,
+    /// Time spent training and evaluating this fold.
+    pub elapsed: Duration,
+}
+
+/// Results of [`cross_validate`], ordered by fold.
+#[derive(Clone, Debug)]
+pub struct CrossValidation {
+    pub folds: Vec<FoldResult>,
+    /// Wall-clock time of the whole run.
+    pub wall_time: Duration,
+}
+
+impl CrossValidation {
+    pub fn mean_accuracy(&self) -> f64 {
+        self.folds.iter().map(|f| f.evaluation.accuracy).sum::<f64>() / self.folds.len() as f64
+    }
+
+    pub fn mean_loss(&self) -> f64 {
+        self.folds.iter().map(|f| f.evaluation.loss).sum::<f64>() / self.folds.len() as f64
+    }
+
+    /// Sum of the per-fold times, i.e. roughly what running the folds one
+    /// after the other would have taken.
+    pub fn sequential_time(&self) -> Duration {
+        self.folds.iter().map(|f| f.elapsed).sum()
+    }
+}
+
+/// Seed for `fold`, derived from the run's base seed so every fold gets the
+/// same initialisation however the folds are scheduled.
+pub fn fold_seed(base_seed: u64, fold: usize) -> u64 {
+    base_seed.wrapping_add((fold as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+/// Splits `dataset` into `k` contiguous folds and returns `(train, validation)`
+/// for `fold`. Shuffle first if the rows are ordered.
+pub fn fold_split(dataset: &Dataset, k: usize, fold: usize) -> (Dataset, Dataset) {
+    let n = dataset.n_samples();
+    let (start, end) = (n * fold / k, n * (fold + 1) / k);
+    let train_rows: Vec<usize> = (0..start).chain(end..n).collect();
+    let validation_rows: Vec<usize> = (start..end).collect();
+    (dataset.select_rows(&train_rows), dataset.select_rows(&validation_rows))
+}
+
+/// k-fold cross-validation of `params` on `dataset` (raw features).
+///
+/// Folds are trained concurrently on at most `max_threads` threads, each with
+/// its own network and scaler fitted on its training part only. Fold seeds come
+/// from [`fold_seed`] with `params.seed` (or a random base seed) so results do
+/// not depend on the order in which folds finish.
+pub fn cross_validate(
+    dataset: &Dataset,
+    params: &TrainingParams,
+    k: usize,
+    max_threads: usize,
+) -> Result<CrossValidation, NnError> {
+    if k < 2 || k > dataset.n_samples() {
+        return Err(NnError::Config(format!("Cannot split {} samples into {} folds", dataset.n_samples(), k)));
+    }
+    params.check_data(dataset)?;
+    let base_seed = params.seed.unwrap_or_else(rand::random);
+    let started = Instant::now();
+
+    let folds = run_parallel(k, max_threads, |fold| run_fold(dataset, params, k, fold, fold_seed(base_seed, fold)))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(CrossValidation { folds, wall_time: started.elapsed() })
+}
+
+/// Runs `job(0..n_jobs)` on at most `max_threads` scoped threads, each taking
+/// the next job index as it becomes free. Results are returned in job order.
+pub(crate) fn run_parallel<T, F>(n_jobs: usize, max_threads: usize, job: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let next_job = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(n_jobs));
+    thread::scope(|scope| {
+        for _ in 0..max_threads.clamp(1, n_jobs.max(1)) {
+            scope.spawn(|| loop {
+                let index = next_job.fetch_add(1, Ordering::Relaxed);
+                if index >= n_jobs {
+                    break;
+                }
+                let result = job(index);
+                results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn run_fold(
+    dataset: &Dataset,
+    params: &TrainingParams,
+    k: usize,
+    fold: usize,
+    seed: u64,
+) -> Result<FoldResult, NnError> {
+    let started = Instant::now();
+    let (train, validation) = fold_split(dataset, k, fold);
+
+    let scaler = Scaler::fit(&train.features, params.scale_mode);
+    let mut scaled = Dataset { features: scaler.transform(&train.features), labels: train.labels, weights: train.weights };
+    if params.balance != Balance::None {
+        scaled = balance_classes(&scaled, params.balance, &mut StdRng::seed_from_u64(seed));
+    }
+
+    let fold_params = TrainingParams { seed: Some(seed), ..params.clone() };
+    let mut network = create_network(&fold_params)?;
+    network.set_scaler(scaler);
+    train_network(&mut network, &scaled, &fold_params, |_, _, _| {})?;
+
+    Ok(FoldResult { fold, seed, evaluation: evaluate(&network, &validation), elapsed: started.elapsed() })
+}
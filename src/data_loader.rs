@@ -0,0 +1,1125 @@
+use ndarray::Array2;
+use csv::{ReaderBuilder, Trim, WriterBuilder};
+use flate2::bufread::MultiGzDecoder;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use crate::error::NnError;
+use crate::neural_network::{PredictionResult, TaskType};
+use std::io::{self, BufRead, BufReader, Read};
+
+/// The water dataset has 9 physico-chemical features and a 0/1 Potability label.
+pub const NUM_FEATURES: usize = 9;
+/// Potability is the last column in the stock CSV.
+pub const DEFAULT_LABEL_COLUMN: usize = NUM_FEATURES;
+/// Fewest rows of each class [`check_split`] accepts before part of a
+/// dataset is held out.
+pub const MIN_SAMPLES_PER_CLASS: usize = 2;
+/// Feature names in the order of the stock CSV.
+pub const FEATURE_NAMES: [&str; NUM_FEATURES] = [
+    "pH",
+    "Hardness",
+    "Solids",
+    "Chloramines",
+    "Sulfate",
+    "Conductivity",
+    "Organic Carbon",
+    "Trihalomethanes",
+    "Turbidity",
+];
+
+/// Display name of feature `index`: its [`FEATURE_NAMES`] entry, or `x{index}`
+/// for inputs past the stock features.
+///
+/// ```
+/// use water_potability_nn::data_loader::feature_name;
+///
+/// assert_eq!(feature_name(0), "pH");
+/// assert_eq!(feature_name(12), "x12");
+/// ```
+pub fn feature_name(index: usize) -> String {
+    FEATURE_NAMES.get(index).map_or_else(|| format!("x{}", index), |name| name.to_string())
+}
+
+/// Range each feature can physically take, in [`FEATURE_NAMES`] order: pH is
+/// on the 0-14 scale, the other measurements cannot be negative.
+pub const FEATURE_BOUNDS: [(f64, f64); NUM_FEATURES] = [
+    (0.0, 14.0),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+    (0.0, f64::INFINITY),
+];
+
+/// Checks `value` of feature `index` against [`FEATURE_BOUNDS`]. Indices past
+/// the stock features are not checked.
+pub fn check_feature(index: usize, value: f64) -> Result<f64, NnError> {
+    let Some(&(min, max)) = FEATURE_BOUNDS.get(index) else {
+        return Ok(value);
+    };
+    if value >= min && value <= max {
+        return Ok(value);
+    }
+    Err(NnError::OutOfRange {
+        feature: FEATURE_NAMES[index].to_string(),
+        value,
+        range: if max.is_finite() { format!("{} to {}", min, max) } else { format!("at least {}", min) },
+    })
+}
+
+/// Parses user-entered text for feature `index`, rejecting anything that is
+/// not a number or lies outside [`FEATURE_BOUNDS`] instead of substituting a
+/// default.
+///
+/// ```
+/// use water_potability_nn::data_loader::parse_feature;
+///
+/// assert_eq!(parse_feature(0, " 7.2 ").unwrap(), 7.2);
+/// assert_eq!(parse_feature(0, "15").unwrap_err().to_string(), "pH = 15 is outside its possible range (0 to 14)");
+/// assert!(parse_feature(8, "2,5").is_err());
+/// ```
+pub fn parse_feature(index: usize, text: &str) -> Result<f64, NnError> {
+    let value = text.trim().parse::<f64>().map_err(|_| NnError::InvalidInput {
+        feature: feature_name(index),
+        value: text.to_string(),
+    })?;
+    check_feature(index, value)
+}
+
+// Columns whose spread is below this are treated as constant by the scaler
+const MIN_SCALE: f64 = 1e-8;
+
+// First two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Header of the optional CSV column of sample weights, compared ignoring case.
+pub const WEIGHT_COLUMN: &str = "weight";
+/// Header of the label column in the stock CSV, compared ignoring case.
+pub const LABEL_HEADER: &str = "Potability";
+
+/// Features and 0/1 labels, one sample per row.
+#[derive(Clone, Debug)]
+pub struct Dataset {
+    /// (n_samples, n_features)
+    pub features: Array2<f64>,
+    /// (n_samples, 1)
+    pub labels: Array2<f64>,
+    /// (n_samples, 1) non-negative sample weights, `None` weighing every
+    /// sample 1. Training scales each sample's loss and gradient by its weight,
+    /// so a weight of 2 counts like the sample appearing twice. Accuracy and
+    /// the validation and [`crate::metrics`] scores ignore weights.
+    pub weights: Option<Array2<f64>>,
+}
+
+impl Dataset {
+    pub fn n_samples(&self) -> usize {
+        self.features.nrows()
+    }
+
+    /// The given rows, in the given order, with their labels and weights.
+    pub fn select_rows(&self, indices: &[usize]) -> Dataset {
+        Dataset {
+            features: self.features.select(ndarray::Axis(0), indices),
+            labels: self.labels.select(ndarray::Axis(0), indices),
+            weights: self.weights.as_ref().map(|weights| weights.select(ndarray::Axis(0), indices)),
+        }
+    }
+}
+
+/// One batch of samples. Borrowed when it is a whole in-memory dataset, so
+/// full-batch training does not copy the data every epoch.
+pub type Batch<'a> = Cow<'a, Dataset>;
+
+/// Samples that training iterates over in batches. Implementors provide row
+/// access; the default [`DataSource::batches`] handles ordering and chunking.
+pub trait DataSource {
+    fn n_samples(&self) -> usize;
+
+    fn n_features(&self) -> usize;
+
+    /// The given rows, in the given order.
+    fn rows(&self, indices: &[usize]) -> Batch<'_>;
+
+    /// Every sample in its stored order.
+    fn all(&self) -> Batch<'_> {
+        self.rows(&(0..self.n_samples()).collect::<Vec<_>>())
+    }
+
+    /// Batches of `batch_size` samples covering every sample exactly once, the
+    /// last one possibly smaller. `batch_size` 0 means a single full batch.
+    /// With `shuffle` the order is drawn from `seed`, so the same seed yields
+    /// the same batches; without it a full batch is the data as stored.
+    fn batches(&self, batch_size: usize, shuffle: bool, seed: u64) -> impl Iterator<Item = Batch<'_>> {
+        let n = self.n_samples();
+        // Never larger than the data, so a tiny dataset still trains in one full batch
+        let size = if batch_size == 0 { n } else { batch_size.min(n) }.max(1);
+        let mut order: Vec<usize> = (0..n).collect();
+        if shuffle {
+            order.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        let whole = size >= n && !shuffle;
+        (0..n).step_by(size).map(move |start| {
+            if whole { self.all() } else { self.rows(&order[start..(start + size).min(n)]) }
+        })
+    }
+}
+
+impl DataSource for Dataset {
+    fn n_samples(&self) -> usize {
+        self.features.nrows()
+    }
+
+    fn n_features(&self) -> usize {
+        self.features.ncols()
+    }
+
+    fn rows(&self, indices: &[usize]) -> Batch<'_> {
+        Cow::Owned(self.select_rows(indices))
+    }
+
+    fn all(&self) -> Batch<'_> {
+        Cow::Borrowed(self)
+    }
+}
+
+/// Summary statistics of one feature column, in original units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+}
+
+/// Per-column min/max/mean/std of the dataset features.
+pub fn feature_stats(dataset: &Dataset) -> Vec<FeatureStats> {
+    column_stats(&dataset.features)
+}
+
+fn column_stats(data: &Array2<f64>) -> Vec<FeatureStats> {
+    data.columns().into_iter().map(|col| {
+        let mean = col.mean().unwrap_or(0.0);
+        FeatureStats {
+            min: col.fold(f64::INFINITY, |a, &b| a.min(b)),
+            max: col.fold(f64::NEG_INFINITY, |a, &b| a.max(b)),
+            mean,
+            std: col.mapv(|x| (x - mean).powi(2)).mean().unwrap_or(0.0).sqrt(),
+        }
+    }).collect()
+}
+
+// Opens `path` for reading, transparently decompressing gzip files (detected by
+// a `.gz` extension or the gzip magic bytes) as a stream
+fn open_source(path: &str) -> Result<(Box<dyn Read>, bool), NnError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let is_gzip = path.ends_with(".gz") || reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if is_gzip {
+        Ok((Box::new(MultiGzDecoder::new(reader)), true))
+    } else {
+        Ok((Box::new(reader), false))
+    }
+}
+
+// Lines read ahead of parsing to detect the CSV format
+const FORMAT_SAMPLE_LINES: usize = 20;
+
+/// Field delimiter and decimal separator of a CSV file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CsvFormat {
+    pub delimiter: u8,
+    pub decimal: char,
+}
+
+impl CsvFormat {
+    /// Commas between fields and decimal points, as in the stock dataset.
+    pub const COMMA: CsvFormat = CsvFormat { delimiter: b',', decimal: '.' };
+    /// Semicolons between fields and decimal commas, as spreadsheets export
+    /// under many European locales.
+    pub const SEMICOLON: CsvFormat = CsvFormat { delimiter: b';', decimal: ',' };
+
+    /// Detects the format from the first lines of a file. The delimiter is the
+    /// one of `,`, `;` and tab that splits every line into the same number of
+    /// fields, at least two; without one the file is taken as comma-separated.
+    /// With another delimiter than `,`, numbers decide the decimal separator.
+    ///
+    /// Returns [`NnError::AmbiguousFormat`] when several delimiters fit, or
+    /// when numbers are written with both decimal commas and points.
+    ///
+    /// ```
+    /// use water_potability_nn::data_loader::CsvFormat;
+    ///
+    /// assert_eq!(CsvFormat::detect(b"ph;Hardness\n7,1;204,5\n").unwrap(), CsvFormat::SEMICOLON);
+    /// assert_eq!(CsvFormat::detect(b"ph,Hardness\n7.1,204.5\n").unwrap(), CsvFormat::COMMA);
+    /// assert!(CsvFormat::detect(b"ph;Hardness\n7,1;204.5\n").is_err());
+    /// ```
+    pub fn detect(sample: &[u8]) -> Result<CsvFormat, NnError> {
+        let parse = |delimiter: u8| -> Option<Vec<csv::StringRecord>> {
+            let records: Vec<csv::StringRecord> = ReaderBuilder::new()
+                .has_headers(false)
+                .delimiter(delimiter)
+                .trim(Trim::All)
+                .flexible(true)
+                .from_reader(sample)
+                .records()
+                .collect::<Result<_, _>>()
+                .ok()?;
+            let n_fields = records.first()?.len();
+            (n_fields > 1 && records.iter().all(|record| record.len() == n_fields)).then_some(records)
+        };
+        let fitting: Vec<(u8, Vec<csv::StringRecord>)> =
+            [b',', b';', b'\t'].into_iter().filter_map(|delimiter| parse(delimiter).map(|records| (delimiter, records))).collect();
+
+        let (delimiter, records) = match fitting.len() {
+            0 => return Ok(CsvFormat::COMMA),
+            1 => fitting.into_iter().next().unwrap(),
+            _ => {
+                let names: Vec<String> = fitting.iter().map(|&(delimiter, _)| format!("{:?}", delimiter as char)).collect();
+                return Err(NnError::AmbiguousFormat(format!("every line splits evenly at {}", names.join(" and "))));
+            }
+        };
+        if delimiter == b',' {
+            return Ok(CsvFormat::COMMA);
+        }
+
+        // Data fields that are numbers with a decimal comma or point; the header is skipped
+        let numbers = || records.iter().skip(1).flat_map(|record| record.iter());
+        let written_with = |decimal: char| {
+            numbers().any(|field| field.contains(decimal) && field.replace(decimal, ".").parse::<f64>().is_ok())
+        };
+        let decimal = match (written_with(','), written_with('.')) {
+            (true, true) => {
+                return Err(NnError::AmbiguousFormat("numbers are written with both decimal commas and decimal points".to_string()));
+            }
+            (true, false) => ',',
+            (false, _) => '.',
+        };
+        Ok(CsvFormat { delimiter, decimal })
+    }
+
+    // A number written with this format's decimal separator
+    fn parse_number(self, text: &str) -> Option<f64> {
+        if self.decimal == '.' {
+            text.parse().ok()
+        } else {
+            text.replace(self.decimal, ".").parse().ok()
+        }
+    }
+}
+
+impl std::str::FromStr for CsvFormat {
+    type Err = String;
+
+    /// Parses a delimiter followed by a decimal separator, `tab` standing for
+    /// a tab delimiter: `;,` is [`CsvFormat::SEMICOLON`], `,.` is
+    /// [`CsvFormat::COMMA`] and `tab,` is tab-separated with decimal commas.
+    ///
+    /// ```
+    /// use water_potability_nn::data_loader::CsvFormat;
+    ///
+    /// assert_eq!(";,".parse(), Ok(CsvFormat::SEMICOLON));
+    /// assert_eq!("tab.".parse(), Ok(CsvFormat { delimiter: b'\t', decimal: '.' }));
+    /// assert!(",,".parse::<CsvFormat>().is_err());
+    /// ```
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid CSV format '{}', expected a delimiter and a decimal separator such as ';,'", text);
+        let (delimiter, decimal) = match text.strip_prefix("tab") {
+            Some(decimal) => ('\t', decimal),
+            None => {
+                let mut chars = text.chars();
+                (chars.next().ok_or_else(invalid)?, chars.as_str())
+            }
+        };
+        let decimal = match decimal {
+            "." => '.',
+            "," => ',',
+            _ => return Err(invalid()),
+        };
+        if !delimiter.is_ascii() || delimiter == decimal {
+            return Err(invalid());
+        }
+        Ok(CsvFormat { delimiter: delimiter as u8, decimal })
+    }
+}
+
+// `format`, or else the format detected from the first lines of `source`,
+// with a reader that yields those lines again followed by the rest
+fn resolve_format(
+    source: Box<dyn Read>,
+    format: Option<CsvFormat>,
+    read_error: impl Fn(csv::Error) -> NnError,
+) -> Result<(CsvFormat, Box<dyn Read>), NnError> {
+    if let Some(format) = format {
+        return Ok((format, source));
+    }
+    let mut source = BufReader::new(source);
+    let mut sample = Vec::new();
+    for _ in 0..FORMAT_SAMPLE_LINES {
+        if source.read_until(b'\n', &mut sample).map_err(|e| read_error(e.into()))? == 0 {
+            break;
+        }
+    }
+    let format = CsvFormat::detect(&sample)?;
+    Ok((format, Box::new(io::Cursor::new(sample).chain(source))))
+}
+
+/// How a CSV column is turned into features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Parsed as a number, one feature.
+    Numeric,
+    /// Any text (e.g. a source type or `true`/`false`), one-hot encoded into
+    /// one feature per distinct value.
+    Categorical,
+}
+
+/// Column types by header name, and how text labels map to numbers. Columns
+/// not named in the spec are numeric and labels must be numbers, so the
+/// default spec reads the stock all-numeric files. The [`CsvFormat`] is
+/// detected unless the spec gives one.
+#[derive(Clone, Debug, Default)]
+pub struct ColumnSpec {
+    categorical: Vec<String>,
+    labels: Vec<(String, f64)>,
+    label_header: Option<String>,
+    format: Option<CsvFormat>,
+}
+
+impl ColumnSpec {
+    /// Marks the column with header `name` as categorical.
+    pub fn categorical(mut self, name: &str) -> Self {
+        self.categorical.push(name.to_string());
+        self
+    }
+
+    /// Reads the label `text` (compared ignoring case) as `value`, e.g.
+    /// `.label("Yes", 1.0).label("No", 0.0)`. Numeric labels are still read as
+    /// numbers; any other text in the label column is an error.
+    pub fn label(mut self, text: &str, value: f64) -> Self {
+        self.labels.push((text.to_string(), value));
+        self
+    }
+
+    /// Takes the label from the column headed `name` (compared ignoring case),
+    /// wherever it sits, instead of by index. Without such a column the index
+    /// is used, unless a [`WEIGHT_COLUMN`] could have shifted it: that file is
+    /// rejected rather than read with the wrong column as its label.
+    pub fn label_header(mut self, name: &str) -> Self {
+        self.label_header = Some(name.to_string());
+        self
+    }
+
+    /// Reads the file in `format` instead of detecting it.
+    pub fn format(mut self, format: CsvFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn column_type(&self, name: &str) -> ColumnType {
+        if self.categorical.iter().any(|c| c == name) {
+            ColumnType::Categorical
+        } else {
+            ColumnType::Numeric
+        }
+    }
+
+    // The mapped value of a label, falling back to parsing it as a number
+    fn label_value(&self, text: &str, format: CsvFormat) -> Option<f64> {
+        self.labels
+            .iter()
+            .find(|(label, _)| label.eq_ignore_ascii_case(text))
+            .map(|&(_, value)| value)
+            .or_else(|| format.parse_number(text))
+    }
+}
+
+/// A dataset read with a [`ColumnSpec`], with its expanded feature layout.
+#[derive(Clone, Debug)]
+pub struct LoadedData {
+    pub dataset: Dataset,
+    /// One name per feature column; one-hot features are named `column=value`.
+    pub feature_names: Vec<String>,
+    /// Feature columns produced by one-hot encoding, see [`Scaler::pass_through`].
+    pub one_hot_columns: Vec<usize>,
+    /// Empty (missing) values per CSV column, label included, as `(header,
+    /// count)` in file order.
+    pub missing_values: Vec<(String, usize)>,
+    /// Rows skipped because they had at least one missing value.
+    pub skipped_rows: usize,
+    /// CSV column the labels were read from.
+    pub label_column: usize,
+    /// CSV column the sample weights were read from, if any.
+    pub weight_column: Option<usize>,
+}
+
+/// Loads the water dataset, taking the label from `label_column` (0-based) and
+/// treating every other column, in file order, as a feature.
+///
+/// Quoted fields, surrounding whitespace and a UTF-8 BOM are handled. Rows with
+/// an empty (missing) value are skipped; a row with the wrong number of fields
+/// or a non-numeric value is an error naming its line. Gzip-compressed files
+/// (`.csv.gz`) are decompressed on the fly. Semicolon- or tab-separated files
+/// and decimal commas are detected, see [`CsvFormat::detect`].
+///
+/// A column headed [`WEIGHT_COLUMN`], other than the label column, is read as
+/// [`Dataset::weights`] instead of as a feature. Without one every sample
+/// weighs 1.
+pub fn load_water_data(path: &str, label_column: usize) -> Result<Dataset, NnError> {
+    load_water_data_with_spec(path, label_column, &ColumnSpec::default())
+}
+
+/// Like [`load_water_data`], reading text labels such as `Yes`/`No` through
+/// the mapping given with [`ColumnSpec::label`]. A label that is neither
+/// mapped nor a number is an error naming its line.
+///
+/// ```no_run
+/// use water_potability_nn::data_loader::{load_water_data_with_spec, ColumnSpec, DEFAULT_LABEL_COLUMN};
+///
+/// let spec = ColumnSpec::default().label("Yes", 1.0).label("No", 0.0);
+/// let dataset = load_water_data_with_spec("water_potability.csv", DEFAULT_LABEL_COLUMN, &spec)?;
+/// # Ok::<(), water_potability_nn::NnError>(())
+/// ```
+pub fn load_water_data_with_spec(path: &str, label_column: usize, spec: &ColumnSpec) -> Result<Dataset, NnError> {
+    read_labeled_csv(path, label_column, spec, Some(NUM_FEATURES)).map(|loaded| loaded.dataset)
+}
+
+/// Loads a water data file the way the app does: like [`load_water_data`],
+/// with the label taken from the column headed [`LABEL_HEADER`] wherever it
+/// sits, and from [`DEFAULT_LABEL_COLUMN`] in files without that header; see
+/// [`ColumnSpec::label_header`]. `format` overrides the detected
+/// [`CsvFormat`].
+pub fn load_potability_data(path: &str, format: Option<CsvFormat>) -> Result<Dataset, NnError> {
+    let spec = ColumnSpec { format, ..ColumnSpec::default() }.label_header(LABEL_HEADER);
+    load_water_data_with_spec(path, DEFAULT_LABEL_COLUMN, &spec)
+}
+
+/// Like [`load_water_data`], but for files with any number of columns, typed by
+/// `spec`. Categorical columns are expanded into one 0/1 feature per distinct
+/// value (sorted, so the layout does not depend on row order), which widens the
+/// input dimension accordingly. Labels are numbers or text mapped by the spec.
+pub fn load_data_with_spec(path: &str, label_column: usize, spec: &ColumnSpec) -> Result<LoadedData, NnError> {
+    read_labeled_csv(path, label_column, spec, None)
+}
+
+// A non-label field of a parsed row
+enum Field {
+    Number(f64),
+    Category(String),
+}
+
+fn read_labeled_csv(
+    path: &str,
+    label_column: usize,
+    spec: &ColumnSpec,
+    expected_features: Option<usize>,
+) -> Result<LoadedData, NnError> {
+    let (source, is_gzip) = open_source(path)?;
+
+    // I/O errors while reading a gzip stream mean the compressed data is bad
+    let read_error = |e: csv::Error| {
+        if is_gzip && e.is_io_error() {
+            NnError::Decompress { path: path.to_string(), source: e }
+        } else {
+            NnError::Csv(e)
+        }
+    };
+
+    let (format, source) = resolve_format(source, spec.format, read_error)?;
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .flexible(true)  // Field count is checked per record below
+        .from_reader(source);
+
+    let headers = rdr.headers().map_err(read_error)?.clone();
+    let n_columns = headers.len();
+    let by_header = |name: &str| headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+    let label_column = match spec.label_header.as_deref() {
+        Some(name) => match by_header(name) {
+            Some(j) => j,
+            None if by_header(WEIGHT_COLUMN).is_some() => {
+                return Err(NnError::Columns(format!(
+                    "No column is headed '{}'; name the label column so, as a '{}' column shifts the others", name, WEIGHT_COLUMN
+                )));
+            }
+            None => label_column,
+        },
+        None => label_column,
+    };
+    if label_column >= n_columns {
+        return Err(NnError::Columns(format!("Label column {} is out of range, file has {} columns", label_column, n_columns)));
+    }
+    let weight_column = by_header(WEIGHT_COLUMN).filter(|&j| j != label_column);
+    let n_feature_columns = n_columns - 1 - usize::from(weight_column.is_some());
+    if let Some(expected) = expected_features
+        && n_feature_columns != expected
+    {
+        return Err(NnError::Columns(format!(
+            "Expected {} feature columns besides the label, found {}", expected, n_feature_columns
+        )));
+    }
+    let types: Vec<ColumnType> = headers
+        .iter()
+        .enumerate()
+        .map(|(j, name)| if j == label_column || Some(j) == weight_column { ColumnType::Numeric } else { spec.column_type(name) })
+        .collect();
+
+    let mut rows: Vec<Vec<Field>> = Vec::new();
+    let mut labels: Vec<f64> = Vec::new();
+    let mut weights: Vec<f64> = Vec::new();
+    let mut missing = vec![0; n_columns];
+    let mut skipped_rows = 0;
+
+    for result in rdr.records() {
+        let record = result.map_err(read_error)?;
+        let line = record.position().map_or(0, |pos| pos.line());
+        if record.len() != n_columns {
+            return Err(NnError::FieldCount { line, expected: n_columns, found: record.len() });
+        }
+
+        // Missing measurement, drop the whole row
+        let mut complete = true;
+        for (count, field) in missing.iter_mut().zip(record.iter()) {
+            if field.is_empty() {
+                *count += 1;
+                complete = false;
+            }
+        }
+        if !complete {
+            skipped_rows += 1;
+            continue;
+        }
+
+        let mut fields = Vec::with_capacity(n_columns);
+        for (j, ((field, header), column_type)) in record.iter().zip(headers.iter()).zip(&types).enumerate() {
+            if j == label_column && !spec.labels.is_empty() {
+                let label = spec.label_value(field, format).ok_or_else(|| NnError::UnknownLabel {
+                    line,
+                    value: field.to_string(),
+                    expected: spec.labels.iter().map(|(label, _)| label.as_str()).collect::<Vec<_>>().join(", "),
+                })?;
+                fields.push(Field::Number(label));
+                continue;
+            }
+            match column_type {
+                ColumnType::Categorical => fields.push(Field::Category(field.to_string())),
+                ColumnType::Numeric => match format.parse_number(field) {
+                    Some(value) => fields.push(Field::Number(value)),
+                    None => {
+                        return Err(NnError::NotANumber { line, column: header.to_string(), value: field.to_string() });
+                    }
+                },
+            }
+        }
+
+        let Field::Number(label) = fields.remove(label_column) else { unreachable!("the label column is numeric") };
+        if let Some(j) = weight_column {
+            // Its index has shifted if the label came before it
+            let j = if j > label_column { j - 1 } else { j };
+            let Field::Number(weight) = fields.remove(j) else { unreachable!("the weight column is numeric") };
+            if !(weight >= 0.0 && weight.is_finite()) {
+                return Err(NnError::InvalidWeight { line, value: weight });
+            }
+            weights.push(weight);
+        }
+        rows.push(fields);
+        labels.push(label);
+    }
+
+    // Distinct values of every categorical column, in sorted order
+    let feature_columns: Vec<(&str, ColumnType)> = headers
+        .iter()
+        .zip(types)
+        .enumerate()
+        .filter(|&(j, _)| j != label_column && Some(j) != weight_column)
+        .map(|(_, column)| column)
+        .collect();
+    let mut levels: Vec<Vec<String>> = vec![Vec::new(); feature_columns.len()];
+    for row in &rows {
+        for (j, field) in row.iter().enumerate() {
+            if let Field::Category(value) = field
+                && let Err(pos) = levels[j].binary_search(value)
+            {
+                levels[j].insert(pos, value.clone());
+            }
+        }
+    }
+
+    let mut feature_names = Vec::new();
+    let mut one_hot_columns = Vec::new();
+    for (j, &(header, column_type)) in feature_columns.iter().enumerate() {
+        if column_type == ColumnType::Categorical {
+            for value in &levels[j] {
+                one_hot_columns.push(feature_names.len());
+                feature_names.push(format!("{}={}", header, value));
+            }
+        } else {
+            feature_names.push(header.to_string());
+        }
+    }
+
+    let n_features = feature_names.len();
+    let mut features = Vec::with_capacity(rows.len() * n_features);
+    for row in &rows {
+        for (j, field) in row.iter().enumerate() {
+            match field {
+                Field::Number(value) => features.push(*value),
+                Field::Category(value) => features.extend(levels[j].iter().map(|level| if level == value { 1.0 } else { 0.0 })),
+            }
+        }
+    }
+
+    Ok(LoadedData {
+        dataset: Dataset {
+            features: Array2::from_shape_vec((rows.len(), n_features), features)?,
+            labels: Array2::from_shape_vec((labels.len(), 1), labels)?,
+            weights: weight_column.map(|_| Array2::from_shape_vec((weights.len(), 1), weights)).transpose()?,
+        },
+        feature_names,
+        one_hot_columns,
+        missing_values: headers.iter().map(str::to_string).zip(missing).collect(),
+        skipped_rows,
+        label_column,
+        weight_column,
+    })
+}
+
+/// Unlabeled samples read by [`read_samples`] for batch prediction.
+#[derive(Clone, Debug)]
+pub struct SampleFile {
+    pub headers: Vec<String>,
+    /// (n_samples, NUM_FEATURES)
+    pub samples: Array2<f64>,
+    /// Line numbers of rows that were skipped, with the reason.
+    pub skipped: Vec<(u64, String)>,
+}
+
+/// Reads a CSV of unlabeled samples with exactly [`NUM_FEATURES`] columns, in
+/// `format` or else the one [`CsvFormat::detect`] finds. Rows with a
+/// different column count, non-numeric values or values outside
+/// [`FEATURE_BOUNDS`] are skipped and reported in [`SampleFile::skipped`]
+/// rather than failing the whole file.
+pub fn read_samples(path: &str, format: Option<CsvFormat>) -> Result<SampleFile, NnError> {
+    let (source, _) = open_source(path)?;
+    let (format, source) = resolve_format(source, format, NnError::Csv)?;
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(source);
+
+    let headers: Vec<String> = rdr.headers()?.iter().map(str::to_string).collect();
+    if headers.len() != NUM_FEATURES {
+        return Err(NnError::Columns(format!("Expected {} feature columns, found {}", NUM_FEATURES, headers.len())));
+    }
+
+    let mut values = Vec::new();
+    let mut skipped = Vec::new();
+    for result in rdr.records() {
+        let record = result?;
+        let line = record.position().map_or(0, |pos| pos.line());
+        if record.len() != NUM_FEATURES {
+            skipped.push((line, format!("expected {} fields, found {}", NUM_FEATURES, record.len())));
+            continue;
+        }
+        let row = match record.iter().map(|field| format.parse_number(field)).collect::<Option<Vec<_>>>() {
+            Some(row) => row,
+            None => {
+                skipped.push((line, "missing or non-numeric value".to_string()));
+                continue;
+            }
+        };
+        match row.iter().enumerate().try_for_each(|(j, &value)| check_feature(j, value).map(|_| ())) {
+            Ok(()) => values.extend(row),
+            Err(e) => skipped.push((line, e.to_string())),
+        }
+    }
+
+    Ok(SampleFile {
+        headers,
+        samples: Array2::from_shape_vec((values.len() / NUM_FEATURES, NUM_FEATURES), values)?,
+        skipped,
+    })
+}
+
+/// Writes `file`'s samples to `path` with `probability` and `verdict` columns
+/// appended, or a single `predicted` column for regression results.
+pub fn write_predictions(path: &str, file: &SampleFile, results: &[PredictionResult]) -> Result<(), NnError> {
+    let mut writer = WriterBuilder::new().from_path(path)?;
+
+    // Regression predictions are a single value, without a verdict
+    let regression = results.first().is_some_and(|result| result.task == TaskType::Regression);
+    let mut header = file.headers.clone();
+    if regression {
+        header.push("predicted".to_string());
+    } else {
+        header.push("probability".to_string());
+        header.push("verdict".to_string());
+    }
+    writer.write_record(&header)?;
+
+    for (row, result) in file.samples.rows().into_iter().zip(results) {
+        let mut record: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        record.push(format!("{:.6}", result.probability));
+        if !regression {
+            record.push(result.verdict().unwrap_or_default());
+        }
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// How feature columns are rescaled before training.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScaleMode {
+    /// Zero mean and unit variance per column.
+    #[default]
+    Standardize,
+    /// Maps each column's training range onto [0, 1].
+    MinMax,
+    /// Leaves features in their original units.
+    None,
+}
+
+/// Per-column scaling fitted on the training data. The same instance must be
+/// applied at prediction time, so it is stored alongside the trained network.
+///
+/// ```
+/// use ndarray::array;
+/// use water_potability_nn::data_loader::{ScaleMode, Scaler};
+///
+/// let scaler = Scaler::fit(&array![[0.0], [14.0]], ScaleMode::MinMax);
+/// // An unseen sample is scaled with the training range, not its own
+/// assert_eq!(scaler.transform_row(&[7.0]), vec![0.5]);
+/// assert_eq!(scaler.transform_row(&[21.0]), vec![1.5]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Scaler {
+    mode: ScaleMode,
+    offset: Vec<f64>,
+    scale: Vec<f64>,
+    // Training data statistics in original units
+    stats: Vec<FeatureStats>,
+}
+
+impl Scaler {
+    pub fn fit(data: &Array2<f64>, mode: ScaleMode) -> Self {
+        let stats = column_stats(data);
+        let mut offset = Vec::with_capacity(data.ncols());
+        let mut scale = Vec::with_capacity(data.ncols());
+
+        for (col, col_stats) in data.columns().into_iter().zip(&stats) {
+            let (col_offset, col_scale) = match mode {
+                ScaleMode::Standardize => {
+                    let mean = col.mean().unwrap_or(0.0);
+                    let std = col.mapv(|x| (x - mean).powi(2)).mean().unwrap_or(0.0).sqrt();
+                    (mean, std)
+                }
+                ScaleMode::MinMax => {
+                    let min = col.fold(f64::INFINITY, |a, &b| a.min(b));
+                    let max = col.fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+                    (min, max - min)
+                }
+                ScaleMode::None => (0.0, 1.0),
+            };
+            offset.push(col_offset);
+            // Dividing by a zero spread would blow up unseen values, so a
+            // constant column is only shifted, keeping its original units
+            let is_constant = col_stats.max - col_stats.min < MIN_SCALE;
+            scale.push(if is_constant { 1.0 } else { col_scale.max(MIN_SCALE) });
+        }
+
+        Self { mode, offset, scale, stats }
+    }
+
+    /// Indices of the columns that were (nearly) constant in the fitted data
+    /// and are therefore left unscaled. They carry no information for training.
+    pub fn constant_columns(&self) -> Vec<usize> {
+        self.stats
+            .iter()
+            .enumerate()
+            .filter(|(_, stats)| stats.max - stats.min < MIN_SCALE)
+            .map(|(j, _)| j)
+            .collect()
+    }
+
+    /// Leaves `columns` in their original units, e.g. the 0/1 features from
+    /// [`load_data_with_spec`] that need no rescaling.
+    pub fn pass_through(mut self, columns: &[usize]) -> Self {
+        for &j in columns {
+            self.offset[j] = 0.0;
+            self.scale[j] = 1.0;
+        }
+        self
+    }
+
+    // Rebuilds a scaler from its saved parts, see neural_network::load_model
+    pub(crate) fn from_parts(mode: ScaleMode, offset: Vec<f64>, scale: Vec<f64>, stats: Vec<FeatureStats>) -> Self {
+        Self { mode, offset, scale, stats }
+    }
+
+    pub(crate) fn parts(&self) -> (&[f64], &[f64]) {
+        (&self.offset, &self.scale)
+    }
+
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+
+    /// Statistics of the data the scaler was fitted on, in original units.
+    pub fn feature_stats(&self) -> &[FeatureStats] {
+        &self.stats
+    }
+
+    pub fn transform(&self, data: &Array2<f64>) -> Array2<f64> {
+        let mut scaled = data.clone();
+        for (j, mut col) in scaled.columns_mut().into_iter().enumerate() {
+            col -= self.offset[j];
+            col /= self.scale[j];
+        }
+        scaled
+    }
+
+    pub fn transform_row(&self, row: &[f64]) -> Vec<f64> {
+        row.iter()
+            .enumerate()
+            .map(|(j, &v)| (v - self.offset[j]) / self.scale[j])
+            .collect()
+    }
+}
+
+/// Checks that [`train_validation_split`] of `dataset` at `val_fraction`
+/// leaves rows on both sides and, for 0/1 labels, that each class has at
+/// least [`MIN_SAMPLES_PER_CLASS`] rows to share between them.
+///
+/// ```
+/// use ndarray::array;
+/// use water_potability_nn::data_loader::{check_split, Dataset};
+///
+/// let dataset = Dataset {
+///     features: array![[0.1], [0.2], [0.3], [0.4], [0.5]],
+///     labels: array![[0.0], [1.0], [0.0], [1.0], [1.0]],
+///     weights: None,
+/// };
+/// assert!(check_split(&dataset, 0.2).is_ok());
+/// assert!(check_split(&dataset, 0.05).is_err(), "nothing would be held out");
+/// ```
+pub fn check_split(dataset: &Dataset, val_fraction: f64) -> Result<(), NnError> {
+    if !(val_fraction > 0.0 && val_fraction < 1.0) {
+        return Err(NnError::Config(format!("Held-out fraction must be between 0 and 1, got {}", val_fraction)));
+    }
+    let n = dataset.n_samples();
+    let both_sides = |n: usize| (1..n).contains(&validation_rows(n, val_fraction));
+    if !both_sides(n) {
+        let needed = (2..).find(|&n| both_sides(n)).unwrap_or(2);
+        return Err(NnError::TooFewSamples { purpose: "hold out part of them".to_string(), needed, found: n });
+    }
+    if dataset.labels.iter().all(|&y| y == 0.0 || y == 1.0) {
+        let positives = dataset.labels.iter().filter(|&&y| y == 1.0).count();
+        let fewest = positives.min(n - positives);
+        if fewest < MIN_SAMPLES_PER_CLASS {
+            let class = if positives < n - positives { "potable" } else { "not potable" };
+            return Err(NnError::TooFewSamples {
+                purpose: format!("share the {} class between training and held-out data", class),
+                needed: MIN_SAMPLES_PER_CLASS,
+                found: fewest,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Rows train_validation_split holds out of `n`
+fn validation_rows(n: usize, val_fraction: f64) -> usize {
+    (n as f64 * val_fraction.clamp(0.0, 1.0)).round() as usize
+}
+
+/// Splits off the last `val_fraction` of the rows as a validation set, returning
+/// `(train, validation)`. Shuffle first, the split itself keeps row order; see
+/// [`check_split`] for whether both parts are usable.
+pub fn train_validation_split(dataset: &Dataset, val_fraction: f64) -> (Dataset, Dataset) {
+    let n_val = validation_rows(dataset.n_samples(), val_fraction);
+    let n_train = dataset.n_samples() - n_val;
+    let rows = |from: usize, to: usize| Dataset {
+        features: dataset.features.slice(ndarray::s![from..to, ..]).to_owned(),
+        labels: dataset.labels.slice(ndarray::s![from..to, ..]).to_owned(),
+        weights: dataset.weights.as_ref().map(|weights| weights.slice(ndarray::s![from..to, ..]).to_owned()),
+    };
+    (rows(0, n_train), rows(n_train, dataset.n_samples()))
+}
+
+/// Splits `dataset` into `(train, validation, test)` parts holding about
+/// `val_fraction` and `test_fraction` of the rows, drawn at random from `rng`.
+/// For 0/1 labels each class is split separately, so every part keeps the
+/// class balance of the whole. A fraction of 0 gives no part; the rows keep
+/// their order within each part.
+///
+/// Fit on the training part, select models and stop early on the validation
+/// part, and score only the final model on the test part, so its scores are
+/// not biased by any choice made while training.
+///
+/// ```
+/// use ndarray::Array2;
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use water_potability_nn::data_loader::{stratified_split, Dataset};
+///
+/// let dataset = Dataset {
+///     features: Array2::from_shape_fn((100, 1), |(i, _)| i as f64),
+///     labels: Array2::from_shape_fn((100, 1), |(i, _)| if i < 20 { 1.0 } else { 0.0 }),
+///     weights: None,
+/// };
+/// let (train, validation, test) = stratified_split(&dataset, 0.2, 0.1, &mut StdRng::seed_from_u64(1))?;
+/// let (validation, test) = (validation.unwrap(), test.unwrap());
+/// assert_eq!((train.n_samples(), validation.n_samples(), test.n_samples()), (70, 20, 10));
+/// assert_eq!(test.labels.sum(), 2.0, "a fifth of the test rows are positive, as in the whole");
+/// assert!(stratified_split(&dataset, 0.5, 0.5, &mut StdRng::seed_from_u64(1)).is_err());
+/// # Ok::<(), water_potability_nn::NnError>(())
+/// ```
+pub fn stratified_split<R: Rng>(
+    dataset: &Dataset,
+    val_fraction: f64,
+    test_fraction: f64,
+    rng: &mut R,
+) -> Result<(Dataset, Option<Dataset>, Option<Dataset>), NnError> {
+    let in_range = |fraction: f64| (0.0..1.0).contains(&fraction);
+    if !(in_range(val_fraction) && in_range(test_fraction)) || val_fraction + test_fraction >= 1.0 {
+        return Err(NnError::Config(format!(
+            "Validation and test fractions must each be at least 0 and together below 1, got {} and {}",
+            val_fraction, test_fraction
+        )));
+    }
+
+    // One group per class for 0/1 labels, a single group otherwise
+    let n = dataset.n_samples();
+    let binary = dataset.labels.iter().all(|&y| y == 0.0 || y == 1.0);
+    let (positives, negatives): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| binary && dataset.labels[(i, 0)] == 1.0);
+    let (mut train, mut validation, mut test) = (Vec::new(), Vec::new(), Vec::new());
+    for mut group in [positives, negatives] {
+        group.shuffle(rng);
+        let n_test = validation_rows(group.len(), test_fraction);
+        let n_val = validation_rows(group.len(), val_fraction).min(group.len() - n_test);
+        test.extend_from_slice(&group[..n_test]);
+        validation.extend_from_slice(&group[n_test..n_test + n_val]);
+        train.extend_from_slice(&group[n_test + n_val..]);
+    }
+
+    // Each requested part needs a row, and training needs the rest
+    let usable = |n: usize| {
+        let (n_val, n_test) = (validation_rows(n, val_fraction), validation_rows(n, test_fraction));
+        (val_fraction == 0.0 || n_val > 0) && (test_fraction == 0.0 || n_test > 0) && n_val + n_test < n
+    };
+    let empty = |rows: &Vec<usize>, fraction: f64| fraction > 0.0 && rows.is_empty();
+    if train.is_empty() || empty(&validation, val_fraction) || empty(&test, test_fraction) {
+        let needed = (n + 1..).find(|&n| usable(n)).unwrap_or(n + 1);
+        return Err(NnError::TooFewSamples {
+            purpose: "split them into training, validation and test sets".to_string(),
+            needed,
+            found: n,
+        });
+    }
+    let part = |mut rows: Vec<usize>| {
+        rows.sort_unstable();
+        dataset.select_rows(&rows)
+    };
+    let held_out = |rows: Vec<usize>, fraction: f64| (fraction > 0.0).then(|| part(rows));
+    Ok((part(train), held_out(validation, val_fraction), held_out(test, test_fraction)))
+}
+
+// Bit patterns of a row, so rows can be hashed and compared exactly
+fn row_key<'a>(values: impl IntoIterator<Item = &'a f64>) -> Vec<u64> {
+    values.into_iter().map(|v| v.to_bits()).collect()
+}
+
+/// Removes rows whose features and label exactly repeat an earlier row,
+/// keeping the first occurrence and the order of the rest. Returns the number
+/// of rows removed. Run it before splitting so copies of a sample cannot end
+/// up on both sides of a split.
+pub fn dedup(dataset: &mut Dataset) -> usize {
+    let mut seen = HashSet::new();
+    let keep: Vec<usize> = (0..dataset.n_samples())
+        .filter(|&i| seen.insert(row_key(dataset.features.row(i).iter().chain(dataset.labels.row(i).iter()))))
+        .collect();
+
+    let removed = dataset.n_samples() - keep.len();
+    if removed > 0 {
+        *dataset = dataset.select_rows(&keep);
+    }
+    removed
+}
+
+/// Number of rows in `held_out` whose features exactly match a row of
+/// `train`, whatever their labels. Such rows leak training data into the
+/// held-out score.
+pub fn shared_rows(train: &Dataset, held_out: &Dataset) -> usize {
+    let train_rows: HashSet<Vec<u64>> = train.features.rows().into_iter().map(|row| row_key(row.iter())).collect();
+    held_out.features.rows().into_iter().filter(|row| train_rows.contains(&row_key(row.iter()))).count()
+}
+
+/// Returns a copy of `dataset` with its rows in random order.
+pub fn shuffle_data(dataset: &Dataset) -> Dataset {
+    shuffle_data_with(dataset, &mut thread_rng())
+}
+
+/// Like [`shuffle_data`], drawing the order from `rng` so it can be reproduced.
+pub fn shuffle_data_with<R: Rng>(dataset: &Dataset, rng: &mut R) -> Dataset {
+    let mut indices: Vec<usize> = (0..dataset.n_samples()).collect();
+    indices.shuffle(rng);
+    dataset.select_rows(&indices)
+}
+
+/// How class imbalance in the training data is evened out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Balance {
+    /// Train on the data as it is.
+    #[default]
+    None,
+    /// Repeat randomly drawn minority-class samples until both classes are equally frequent.
+    Oversample,
+    /// Keep a random subset of the majority class the size of the minority class.
+    Undersample,
+}
+
+/// Returns a class-balanced copy of `dataset` in random order. Only training
+/// data should be balanced: evaluate on data that was split off beforehand so
+/// duplicated samples never leak into it. A dataset holding a single class is
+/// returned unchanged.
+pub fn balance_classes<R: Rng>(dataset: &Dataset, balance: Balance, rng: &mut R) -> Dataset {
+    let (mut positives, mut negatives): (Vec<usize>, Vec<usize>) =
+        (0..dataset.n_samples()).partition(|&i| dataset.labels[(i, 0)] >= 0.5);
+    if balance == Balance::None || positives.is_empty() || negatives.is_empty() {
+        return dataset.clone();
+    }
+
+    let (minority, majority) = if positives.len() < negatives.len() {
+        (&mut positives, &mut negatives)
+    } else {
+        (&mut negatives, &mut positives)
+    };
+    match balance {
+        Balance::Oversample => {
+            let extra: Vec<usize> = (minority.len()..majority.len())
+                .map(|_| minority[rng.gen_range(0..minority.len())])
+                .collect();
+            minority.extend(extra);
+        }
+        Balance::Undersample => {
+            majority.shuffle(rng);
+            majority.truncate(minority.len());
+        }
+        Balance::None => unreachable!(),
+    }
+
+    let mut indices = [positives, negatives].concat();
+    indices.shuffle(rng);
+    dataset.select_rows(&indices)
+}
@@ -0,0 +1,88 @@
+use thiserror::Error;
+
+/// Errors returned by the library. Every variant renders a message suitable
+/// for showing to the user as is.
+#[derive(Debug, Error)]
+pub enum NnError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// The CSV reader failed, e.g. on invalid UTF-8.
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    /// The compressed stream of a `.gz` file is corrupt.
+    #[error("Failed to decompress {path}: {source}")]
+    Decompress { path: String, source: csv::Error },
+
+    /// A CSV row has a different number of fields than the header.
+    #[error("Line {line}: expected {expected} fields, found {found}")]
+    FieldCount { line: u64, expected: usize, found: usize },
+
+    /// A CSV field that should hold a number does not.
+    #[error("Line {line}, column '{column}': '{value}' is not a number")]
+    NotANumber { line: u64, column: String, value: String },
+
+    /// A label is neither a number nor one of the mapped label texts.
+    #[error("Line {line}: unknown label '{value}', expected a number or one of {expected}")]
+    UnknownLabel { line: u64, value: String, expected: String },
+
+    /// A sample weight is negative or not finite.
+    #[error("Line {line}: sample weight {value} must be a non-negative number")]
+    InvalidWeight { line: u64, value: f64 },
+
+    /// The field delimiter or decimal separator of a CSV file cannot be told
+    /// apart; see [`crate::data_loader::CsvFormat::detect`].
+    #[error("Cannot detect the CSV format: {0}. Name it with --csv-format, e.g. ';,' for semicolons and decimal commas")]
+    AmbiguousFormat(String),
+
+    /// The columns of a data file do not match what is expected.
+    #[error("{0}")]
+    Columns(String),
+
+    /// A user-entered feature value is not a number.
+    #[error("{feature}: '{value}' is not a number")]
+    InvalidInput { feature: String, value: String },
+
+    /// A feature value outside the range the quantity can physically take.
+    #[error("{feature} = {value} is outside its possible range ({range})")]
+    OutOfRange { feature: String, value: f64, range: String },
+
+    /// Invalid network or training configuration.
+    #[error("{0}")]
+    Config(String),
+
+    /// Training would need more memory than `TrainingParams::max_memory` allows.
+    #[error(
+        "Training would need about {} MiB, more than the {} MiB limit. Use fewer or smaller layers or a smaller batch size",
+        .estimated >> 20, .limit >> 20
+    )]
+    MemoryLimit { estimated: usize, limit: usize },
+
+    /// A dataset is too small for what was asked of it.
+    #[error("{found} samples are too few to {purpose}, at least {needed} are needed")]
+    TooFewSamples { purpose: String, needed: usize, found: usize },
+
+    /// The training loss stopped being a finite number.
+    #[error("Training diverged at epoch {epoch} (loss {loss}), try a lower learning rate")]
+    Diverged { epoch: usize, loss: f64 },
+
+    /// Input has a different number of features than the network expects.
+    #[error("Expected {expected} features but got {found}")]
+    InputDimension { expected: usize, found: usize },
+
+    /// The operation needs the scaler the network was trained with.
+    #[error("Network has no scaler, the training feature ranges are unknown")]
+    MissingScaler,
+
+    /// A saved model file is not readable.
+    #[error("{path}: {reason}")]
+    ModelFormat { path: String, reason: String },
+
+    /// A saved model was written by a build with a different file layout.
+    #[error("{path} was saved in model format version {found}, this build reads version {expected}")]
+    UnsupportedVersion { path: String, found: u32, expected: u32 },
+
+    #[error(transparent)]
+    Shape(#[from] ndarray::ShapeError),
+}
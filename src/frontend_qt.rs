@@ -1,104 +1,1579 @@
 use eframe::egui;
-use egui_plot::{Line, Plot, PlotPoints};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use egui_plot::{Bar, BarChart, Line, Plot, PlotPoints};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::notify::desktop_notification;
+use crate::settings::{self, LastSession, Palette, Settings};
+use water_potability_nn::data_loader::{self, Balance, CsvFormat, FeatureStats, ScaleMode};
+use water_potability_nn::lr_finder::{self, LrRange, LrRangeTest};
+use water_potability_nn::metric_series::{MetricPoint, MetricSeries};
+use water_potability_nn::metrics::{explain_prediction, sweep_feature};
+use water_potability_nn::neural_network::{
+    dry_run, export_rust, learning_rate_at, make_prediction, make_predictions, save_model, DivergencePolicy, LayerStats, LossFn, Network, NetworkSummary, PredictionResult, TaskType, TrainingParams,
+    HIGH_LEARNING_RATE,
+};
+use water_potability_nn::pause::PauseSwitch;
+use water_potability_nn::progress::{progress_channel, ProgressSender};
+use water_potability_nn::snapshot::Snapshot;
+use water_potability_nn::variance::{self, EpochSpread, SeedVariance};
 
-// Training parameters struct to share between threads
-#[derive(Clone, Debug)]
-pub struct TrainingParams {
-    pub epochs: usize,
-    pub hidden_layers: usize,
-    pub neurons_per_layer: usize,
-    pub learning_rate: f64,
-    pub restart_training: bool,
+// Maximum number of lines kept in the training log panel
+const LOG_CAPACITY: usize = 500;
+// Default number of points a plot series keeps before older points are decimated
+const DEFAULT_MAX_PLOT_POINTS: usize = 5000;
+// Epoch interval for activation statistics when the debug option is on
+const ACTIVATION_STATS_INTERVAL: usize = 50;
+// Minimum spacing between heartbeats sent by a background thread
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+// Without a heartbeat for this long the backend is reported as stalled
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(3);
+// How long the what-if sliders must rest before the prediction is refreshed
+const PREDICTION_DEBOUNCE: Duration = Duration::from_millis(150);
+// Maximum number of what-if predictions kept in the history panel
+const HISTORY_CAPACITY: usize = 200;
+// Number of points in the what-if feature sweep plot
+const SWEEP_POINTS: usize = 100;
+// Default threshold of the Huber loss option
+const HUBER_DELTA: f64 = 0.5;
+// Default number of seeds trained by the variance analysis
+const VARIANCE_SEEDS: usize = 3;
+// Maximum number of shaded segments drawn for the seed variance band
+const VARIANCE_BAND_SEGMENTS: usize = 200;
+// Number of most recent losses drawn in the status line sparkline
+const SPARKLINE_POINTS: usize = 50;
+const SPARKLINE_SIZE: egui::Vec2 = egui::Vec2::new(100.0, 16.0);
+// Shortest idle refresh interval offered in the settings
+const MIN_IDLE_REFRESH_MS: u64 = 50;
+// Below this height per plot the plots are shown one at a time in tabs
+const MIN_PLOT_HEIGHT: f32 = 120.0;
+// Metric updates queued for the UI before the training thread drops new ones
+const METRICS_CHANNEL_CAPACITY: usize = 4096;
+
+// (epoch, accuracy, loss). Drops updates while the UI lags; flush it before RunFinished
+pub type MetricsSender = ProgressSender<(usize, f64, f64)>;
+pub type ParamsReceiver = Receiver<Arc<Mutex<TrainingParams>>>;
+pub type ActivationStatsSender = Sender<(usize, Vec<LayerStats>)>;
+// Last fully trained model, published by the training thread. Readers take a
+// snapshot, so predicting never blocks the training thread or the plots.
+pub type SharedModel = Arc<Snapshot<TrainedModel>>;
+
+// A trained network together with the statistics of the data it was trained on
+pub struct TrainedModel {
+    pub network: Network,
+    pub feature_stats: Vec<FeatureStats>,
+    pub pr_curve: Vec<(f64, f64)>,  // (recall, precision)
+    pub average_precision: f64,
+    pub importance: Vec<f64>,  // Permutation importance per feature, in input order
+}
+
+// Cloneable handle used by background threads to mirror their diagnostics
+// into the UI log panel (the log, stdout by default, still gets a copy)
+#[derive(Clone)]
+pub struct LogSender {
+    sender: Sender<String>,
+}
+
+impl LogSender {
+    pub fn log(&self, message: impl AsRef<str>) {
+        let message = message.as_ref();
+        log::info!("{}", message);
+        // The UI may already be gone during shutdown, nothing to do then
+        let _ = self.sender.send(timestamped(message));
+    }
+
+    // Like `log`, but recorded at error level
+    pub fn error(&self, message: impl AsRef<str>) {
+        let message = message.as_ref();
+        log::error!("{}", message);
+        let _ = self.sender.send(timestamped(message));
+    }
+}
+
+// Liveness ping sent by a background thread. Dropping it (thread exit or
+// panic) disconnects the channel, which the UI reports as a dead backend.
+pub struct Heartbeat {
+    sender: Sender<()>,
+    last_sent: Cell<Option<Instant>>,
+}
+
+impl Heartbeat {
+    // Cheap to call in hot loops, pings are rate limited to HEARTBEAT_INTERVAL
+    pub fn beat(&self) {
+        if self.last_sent.get().is_none_or(|at| at.elapsed() >= HEARTBEAT_INTERVAL) {
+            let _ = self.sender.send(());
+            self.last_sent.set(Some(Instant::now()));
+        }
+    }
+}
+
+// Whether the backend has a run in hand, from the moment Start is accepted
+// until the run has finished. The UI claims it before sending parameters and
+// the backend releases it, so a second Start can never queue a run behind one
+// that is still going, even when the UI missed the end of the first
+#[derive(Clone, Default)]
+pub struct BusyFlag(Arc<AtomicBool>);
+
+impl BusyFlag {
+    // Marks the backend busy, for runs it starts on its own
+    pub fn claim(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    // Marks the backend busy; false when it already was
+    pub fn try_claim(&self) -> bool {
+        self.0.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+
+    pub fn release(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+// Shown when Start is pressed while the backend is busy
+pub const BUSY_MESSAGE: &str = "Training is already running; wait for the current run to finish before starting another";
+
+// Epoch of a run with the lowest validation loss, or training loss without a validation set
+#[derive(Clone, Copy, Debug)]
+pub struct BestEpoch {
+    pub epoch: usize,
+    pub loss: f64,
+    pub validation: bool,
+}
+
+impl std::fmt::Display for BestEpoch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.validation { "val" } else { "training" };
+        write!(f, "Best {} loss at epoch {} ({:.4})", kind, self.epoch, self.loss)
+    }
+}
+
+// Sent by the training thread exactly once per run, after its last metrics
+// update. Its channel is unbounded, so it is never dropped.
+pub enum RunFinished {
+    Completed { epochs: usize, accuracy: f64, loss: f64, best: Option<BestEpoch> },
+    Failed(String),
+}
+
+// Rows a run trained on, sent by the training thread before each run starts
+pub struct DataSummary {
+    pub samples: usize,  // After duplicate removal
+    pub duplicates_removed: Option<usize>,  // None when duplicate removal is off
+    pub training: usize,
+    pub held_out: usize,  // Calibration and validation rows
+    pub shared_with_held_out: usize,  // Held-out rows whose features repeat a training row
+}
+
+impl std::fmt::Display for DataSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} samples", self.samples)?;
+        if let Some(removed) = self.duplicates_removed {
+            write!(f, " ({} duplicates removed)", removed)?;
+        }
+        write!(f, ", {} training / {} held out", self.training, self.held_out)
+    }
+}
+
+// Channel ends handed to the training thread
+pub struct BackendHandles {
+    pub metrics: MetricsSender,
+    pub params: ParamsReceiver,
+    pub logger: LogSender,
+    pub heartbeat: Heartbeat,
+    pub activation_stats: ActivationStatsSender,
+    pub summary: Sender<NetworkSummary>,
+    pub data_summary: Sender<DataSummary>,
+    pub gradient_norms: Sender<(usize, f64)>,  // (epoch, norm)
+    pub seed: Sender<u64>,  // Seed in effect for the run, sent before it starts
+    pub pause: PauseSwitch,  // Checked at the end of every epoch
+    pub busy: BusyFlag,  // Released once a run has finished
+    pub finished: Sender<RunFinished>,
+}
+
+// A what-if prediction entered by the user, kept for the history panel
+struct HistoryEntry {
+    time: chrono::DateTime<chrono::Local>,
+    inputs: Vec<f64>,
+    result: PredictionResult,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BackendStatus {
+    Alive,
+    Stalled,
+    Dead,
+}
+
+fn timestamped(message: &str) -> String {
+    format!("[{}] {}", chrono::Local::now().format("%H:%M:%S"), message)
+}
+
+// "Accuracy: 85.00%" or "R²: 0.8500", for an epoch's score
+fn format_score(task: TaskType, score: f64) -> String {
+    match task {
+        TaskType::BinaryClassification => format!("Accuracy: {:.2}%", score),
+        TaskType::Regression => format!("R²: {:.4}", score),
+    }
+}
+
+// Tiny line chart of `values` scaled to their own range, for trends at a glance
+fn sparkline(ui: &mut egui::Ui, values: &[f64], color: egui::Color32) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(SPARKLINE_SIZE, egui::Sense::hover());
+    let finite: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if finite.len() < 2 {
+        return response;
+    }
+    let min = finite.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = finite.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    let step = rect.width() / (finite.len() - 1) as f32;
+    let points = finite
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            // A flat series sits in the middle instead of dividing by zero
+            let t = if range > 0.0 { ((v - min) / range) as f32 } else { 0.5 };
+            egui::pos2(rect.left() + i as f32 * step, rect.bottom() - t * rect.height())
+        })
+        .collect();
+    ui.painter().add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+    response.on_hover_text(format!("Loss over the last {} updates: {:.4} to {:.4}", finite.len(), min, max))
+}
+
+// Horizontal bars of permutation importance, most important feature on top
+fn importance_plot(ui: &mut egui::Ui, importance: &[f64], color: egui::Color32) -> egui::Response {
+    let mut ranked: Vec<(String, f64)> = importance.iter().enumerate().map(|(i, &value)| (data_loader::feature_name(i), value)).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let names: Vec<String> = ranked.iter().map(|(name, _)| name.clone()).collect();
+    let n = ranked.len();
+    let bars = ranked
+        .iter()
+        .enumerate()
+        .map(|(rank, (name, value))| Bar::new((n - 1 - rank) as f64, *value).name(name))
+        .collect();
+    Plot::new("importance_plot")
+        .height(200.0)
+        .allow_zoom(false)
+        .allow_drag(false)
+        .include_x(0.0)
+        .y_axis_formatter(move |y, _, _| {
+            // One label per bar, none between them
+            let rank = n as f64 - 1.0 - y;
+            if rank.fract() == 0.0 && (0.0..n as f64).contains(&rank) { names[rank as usize].clone() } else { String::new() }
+        })
+        .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars).horizontal().color(color).name("Importance")))
+        .response
+}
+
+// Diverging bars of each feature's contribution to a prediction, largest push
+// on top, labelled with the feature's value in original units
+fn explanation_plot(ui: &mut egui::Ui, contributions: &[f64], inputs: &[f64], (toward, away): (egui::Color32, egui::Color32)) -> egui::Response {
+    let mut ranked: Vec<(usize, f64)> = contributions.iter().copied().enumerate().collect();
+    ranked.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+    let labels: Vec<String> = ranked
+        .iter()
+        .map(|&(feature, _)| format!("{} = {:.2}", data_loader::feature_name(feature), inputs.get(feature).copied().unwrap_or(f64::NAN)))
+        .collect();
+    let n = ranked.len();
+    let bars = ranked
+        .iter()
+        .enumerate()
+        .map(|(rank, &(_, value))| {
+            Bar::new((n - 1 - rank) as f64, value).name(&labels[rank]).fill(if value >= 0.0 { toward } else { away })
+        })
+        .collect();
+    Plot::new("explanation_plot")
+        .height(200.0)
+        .allow_zoom(false)
+        .allow_drag(false)
+        .include_x(0.0)
+        .y_axis_formatter(move |y, _, _| {
+            // One label per bar, none between them
+            let rank = n as f64 - 1.0 - y;
+            if rank.fract() == 0.0 && (0.0..n as f64).contains(&rank) { labels[rank as usize].clone() } else { String::new() }
+        })
+        .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars).horizontal().name("Contribution")))
+        .response
+}
+
+// What the x-axis of the training plots counts
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlotXAxis {
+    Epoch,   // Epochs completed, 0 for the untrained baseline
+    Update,  // Index of the update as received
+}
+
+impl PlotXAxis {
+    fn label(self) -> &'static str {
+        match self {
+            PlotXAxis::Epoch => "Epoch",
+            PlotXAxis::Update => "Update #",
+        }
+    }
+
+    fn x(self, point: &MetricPoint) -> f64 {
+        match self {
+            PlotXAxis::Epoch => point.epoch,
+            PlotXAxis::Update => point.update,
+        }
+    }
+
+    fn plot_points(self, series: &MetricSeries) -> PlotPoints {
+        series.points().iter().map(|p| [self.x(p), p.value]).collect()
+    }
+
+    // Right edge of the plot: one past the last epoch or update
+    fn extent(self, series: &MetricSeries) -> f64 {
+        series.newest().map_or(0.0, |p| self.x(p) + 1.0)
+    }
+}
+
+// Marks where `series` received NaN or infinite values, which its line skips,
+// at height `y` of the plot
+fn dropped_markers(plot_ui: &mut egui_plot::PlotUi, series: &MetricSeries, axis: PlotXAxis, y: f64) {
+    if series.dropped().is_empty() {
+        return;
+    }
+    let points: PlotPoints = series.dropped().iter().map(|p| [axis.x(p), y]).collect();
+    plot_ui.points(
+        egui_plot::Points::new(points)
+            .shape(egui_plot::MarkerShape::Cross)
+            .radius(5.0)
+            .color(egui::Color32::from_rgb(255, 165, 0))
+            .name(format!("Non-finite, not plotted ({})", series.dropped().len())),
+    );
+}
+
+// Plot shown when the window is too short to stack every plot
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlotTab {
+    Accuracy,
+    Loss,
+    GradientNorm,
+    Variance,
+    LrRange,
+}
+
+impl PlotTab {
+    fn label(self, task: TaskType) -> &'static str {
+        match self {
+            PlotTab::Accuracy if task == TaskType::Regression => "R²",
+            PlotTab::Accuracy => "Accuracy",
+            PlotTab::Loss => "Loss",
+            PlotTab::GradientNorm => "Gradient Norm",
+            PlotTab::Variance => "Seed Variance",
+            PlotTab::LrRange => "LR Finder",
+        }
+    }
 }
 
 pub struct TrainingWindow {
-    accuracies: Vec<f64>,
-    losses: Vec<f64>,
-    receiver: Receiver<(f64, f64)>,  // Changed to receive (accuracy, loss) tuple
+    pub accuracy_color: egui::Color32,
+    pub loss_color: egui::Color32,
+    settings: Settings,
+    accuracies: MetricSeries,
+    losses: MetricSeries,
+    max_plot_points: usize,  // Cap before older plot points are decimated
+    receiver: Receiver<(usize, f64, f64)>,  // (epochs completed, accuracy, loss) updates, 0 for the untrained baseline
+    plot_x_axis: PlotXAxis,
+    plot_tab: PlotTab,  // Plot shown in the tabbed layout of short windows
     training_params: Arc<Mutex<TrainingParams>>,
     params_sender: Sender<Arc<Mutex<TrainingParams>>>,
+    pause: PauseSwitch,  // Shared with the training thread, which holds between epochs while set
+    busy: BusyFlag,  // Set while the backend has a run in hand, whatever is_training says
     epochs_input: String,
     hidden_layers_input: String,
     neurons_input: String,
     learning_rate_input: String,
+    seed_input: String,  // Empty for a new random seed every run
+    target_accuracy_input: String,  // Empty to run every epoch
+    warmup_input: String,
+    scale_mode: ScaleMode,
+    balance: Balance,
+    divergence_policy: DivergencePolicy,
+    auto_reduce_lr: bool,
+    lr_patience: usize,
+    early_stopping_patience: usize,
+    min_delta: f64,
+    val_fraction: f64,
+    test_fraction: f64,
+    task: TaskType,
+    loss_fn: LossFn,
+    huber_delta: f64,  // Kept separately so switching losses does not reset it
+    l1_lambda: f64,
+    l2_lambda: f64,
+    noise_std: f64,
+    debug_activations: bool,
+    track_gradient_norm: bool,
+    calibrate: bool,
+    dedup: bool,
+    fixed_batch_order: bool,
+    gradient_norm_receiver: Receiver<(usize, f64)>,
+    gradient_norms: MetricSeries,
+    variance_seeds: usize,
+    cli_workers: Option<usize>,  // --workers, overrides the setting for this session
+    variance_receiver: Option<Receiver<Result<SeedVariance, String>>>,  // Set while an analysis runs
+    variance: Option<SeedVariance>,
+    lr_range_receiver: Option<Receiver<Result<LrRange, String>>>,  // Set while the LR finder runs
+    lr_range: Option<LrRange>,
+    plot_view_pinned: bool,  // User zoomed or dragged a plot; stop fitting it to live data
+    reset_plot_view: bool,  // Reset View was clicked this frame
+    activation_stats_receiver: Receiver<(usize, Vec<LayerStats>)>,
+    activation_stats: Option<(usize, Vec<LayerStats>)>,  // Latest (epoch, per-layer stats)
+    summary_receiver: Receiver<NetworkSummary>,
+    network_summary: Option<NetworkSummary>,  // Architecture of the network being trained
+    data_summary_receiver: Receiver<DataSummary>,
+    data_summary: Option<DataSummary>,  // Rows of the run being trained
+    seed_receiver: Receiver<u64>,
+    run_seed: Option<u64>,  // Seed of the run being trained, drawn by the backend when none is set
     is_training: bool,
-    last_received_time: std::time::Instant,
+    finished_receiver: Receiver<RunFinished>,
     training_completed: bool,
+    best_epoch: Option<BestEpoch>,  // Of the last completed run
     first_run: bool,  // Track if this is the first run
+    log_receiver: Receiver<String>,
+    log_lines: VecDeque<String>,  // Ring buffer of the last LOG_CAPACITY lines
+    heartbeat_receiver: Receiver<()>,
+    last_heartbeat: Instant,
+    backend_status: BackendStatus,
+    shared_model: SharedModel,
+    model: Option<Arc<TrainedModel>>,  // Model the what-if panel currently predicts with
+    what_if_inputs: Vec<f64>,
+    what_if_changed_at: Option<Instant>,  // Set while a prediction refresh is pending
+    what_if_result: Option<Result<PredictionResult, String>>,
+    what_if_by_user: bool,  // Whether the pending refresh comes from a slider change
+    history: VecDeque<HistoryEntry>,  // Last HISTORY_CAPACITY user predictions, oldest first
+    history_path: String,
+    sweep_feature: usize,  // Feature varied in the sensitivity plot
+    sweep: Vec<(f64, f64)>,
+    explanation: Option<Vec<f64>>,  // Per-feature contributions to the what-if prediction
+    batch_input_path: String,
+    batch_summary: Option<String>,
+    model_path: String,
+    data_path: String,  // CSV the backend trains on
+    csv_format: Option<CsvFormat>,  // --csv-format, for every data and batch file read
 }
 
 impl TrainingWindow {
-    pub fn new() -> (Self, Sender<(f64, f64)>, Receiver<Arc<Mutex<TrainingParams>>>) {
-        let (sender, receiver) = channel();
+    // `defaults` fill the input fields, e.g. the last session's parameters
+    pub fn new(shared_model: SharedModel, defaults: TrainingParams, data_path: String, workers: Option<usize>, csv_format: Option<CsvFormat>) -> (Self, BackendHandles) {
+        let (sender, receiver) = progress_channel(METRICS_CHANNEL_CAPACITY);
         let (params_sender, params_receiver) = channel();
+        let pause = PauseSwitch::new();
+        let busy = BusyFlag::default();
+        let (log_sender, log_receiver) = channel();
+        let (heartbeat_sender, heartbeat_receiver) = channel();
+        let (activation_stats_sender, activation_stats_receiver) = channel();
+        let (summary_sender, summary_receiver) = channel();
+        let (data_summary_sender, data_summary_receiver) = channel();
+        let (seed_sender, seed_receiver) = channel();
+        let (gradient_norm_sender, gradient_norm_receiver) = channel();
+        let (finished_sender, finished_receiver) = channel();
         
-        let training_params = Arc::new(Mutex::new(TrainingParams {
-            epochs: 2000,
-            hidden_layers: 2,
-            neurons_per_layer: 32,
-            learning_rate: 0.5,
-            restart_training: false,
-        }));
+        let settings = Settings::load();
+        let (accuracy_color, loss_color) = settings.palette.colors();
         
         (Self {
-            accuracies: Vec::new(),
-            losses: Vec::new(),
+            accuracy_color,
+            loss_color,
+            settings,
+            accuracies: MetricSeries::default(),
+            losses: MetricSeries::default(),
+            plot_x_axis: PlotXAxis::Epoch,
+            plot_tab: PlotTab::Accuracy,
+            max_plot_points: DEFAULT_MAX_PLOT_POINTS,
             receiver,
-            training_params: training_params.clone(),
+            training_params: Arc::new(Mutex::new(defaults.clone())),
             params_sender,
-            epochs_input: "2000".to_string(),
-            hidden_layers_input: "2".to_string(),
-            neurons_input: "32".to_string(),
-            learning_rate_input: "0.5".to_string(),
+            pause: pause.clone(),
+            busy: busy.clone(),
+            epochs_input: defaults.epochs.to_string(),
+            hidden_layers_input: defaults.hidden_layers.to_string(),
+            neurons_input: defaults.neurons_per_layer.to_string(),
+            learning_rate_input: defaults.learning_rate.to_string(),
+            seed_input: defaults.seed.map_or(String::new(), |seed| seed.to_string()),
+            target_accuracy_input: defaults.target_accuracy.map_or(String::new(), |target| target.to_string()),
+            warmup_input: defaults.warmup_epochs.to_string(),
+            scale_mode: defaults.scale_mode,
+            balance: defaults.balance,
+            divergence_policy: defaults.divergence_policy,
+            auto_reduce_lr: defaults.auto_reduce_lr,
+            lr_patience: defaults.lr_patience,
+            early_stopping_patience: defaults.early_stopping_patience,
+            min_delta: defaults.min_delta,
+            val_fraction: defaults.val_fraction,
+            test_fraction: defaults.test_fraction,
+            task: defaults.task,
+            loss_fn: defaults.loss,
+            huber_delta: match defaults.loss {
+                LossFn::Huber { delta } => delta,
+                _ => HUBER_DELTA,
+            },
+            l1_lambda: defaults.l1_lambda,
+            l2_lambda: defaults.l2_lambda,
+            noise_std: defaults.noise_std,
+            debug_activations: defaults.activation_stats_every > 0,
+            track_gradient_norm: defaults.track_gradient_norm,
+            calibrate: defaults.calibrate,
+            dedup: defaults.dedup,
+            fixed_batch_order: defaults.fixed_batch_order,
+            gradient_norm_receiver,
+            gradient_norms: MetricSeries::default(),
+            variance_seeds: VARIANCE_SEEDS,
+            cli_workers: workers,
+            variance_receiver: None,
+            variance: None,
+            lr_range_receiver: None,
+            lr_range: None,
+            plot_view_pinned: false,
+            reset_plot_view: false,
+            activation_stats_receiver,
+            activation_stats: None,
+            summary_receiver,
+            network_summary: None,
+            data_summary_receiver,
+            data_summary: None,
+            seed_receiver,
+            run_seed: None,
             is_training: false,
-            last_received_time: std::time::Instant::now(),
+            finished_receiver,
             training_completed: false,
+            best_epoch: None,
             first_run: true,
-        }, sender, params_receiver)
+            log_receiver,
+            log_lines: VecDeque::with_capacity(LOG_CAPACITY),
+            heartbeat_receiver,
+            last_heartbeat: Instant::now(),
+            backend_status: BackendStatus::Alive,
+            shared_model,
+            model: None,
+            what_if_inputs: Vec::new(),
+            what_if_changed_at: None,
+            what_if_result: None,
+            what_if_by_user: false,
+            history: VecDeque::with_capacity(HISTORY_CAPACITY),
+            history_path: "prediction_history.csv".to_string(),
+            sweep_feature: 0,
+            sweep: Vec::new(),
+            explanation: None,
+            batch_input_path: String::new(),
+            batch_summary: None,
+            model_path: "model.bin".to_string(),
+            data_path,
+            csv_format,
+        }, BackendHandles {
+            metrics: sender,
+            params: params_receiver,
+            logger: LogSender { sender: log_sender },
+            heartbeat: Heartbeat { sender: heartbeat_sender, last_sent: Cell::new(None) },
+            activation_stats: activation_stats_sender,
+            summary: summary_sender,
+            data_summary: data_summary_sender,
+            gradient_norms: gradient_norm_sender,
+            seed: seed_sender,
+            pause,
+            busy,
+            finished: finished_sender,
+        })
+    }
+
+    fn update_backend_status(&mut self) {
+        let mut disconnected = false;
+        loop {
+            match self.heartbeat_receiver.try_recv() {
+                Ok(()) => self.last_heartbeat = Instant::now(),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+
+        let status = if disconnected {
+            BackendStatus::Dead
+        } else if self.last_heartbeat.elapsed() > HEARTBEAT_TIMEOUT {
+            BackendStatus::Stalled
+        } else {
+            BackendStatus::Alive
+        };
+        if status != self.backend_status {
+            match status {
+                BackendStatus::Alive => self.push_log_line(timestamped("Backend heartbeat restored")),
+                BackendStatus::Stalled => self.push_log_line(timestamped("No heartbeat from the backend, it may be stuck")),
+                BackendStatus::Dead => self.push_log_line(timestamped("Backend thread has stopped")),
+            }
+            self.backend_status = status;
+        }
+    }
+
+    fn backend_status_label(&self, ui: &mut egui::Ui) {
+        let (text, color) = match self.backend_status {
+            BackendStatus::Alive => ("Backend: alive", egui::Color32::from_rgb(50, 205, 50)),
+            BackendStatus::Stalled => ("Backend: stalled", egui::Color32::from_rgb(255, 165, 0)),
+            BackendStatus::Dead => ("Backend: dead", egui::Color32::from_rgb(220, 50, 50)),
+        };
+        ui.colored_label(color, format!("● {}", text));
+        match self.backend_status {
+            BackendStatus::Alive => {}
+            BackendStatus::Stalled => {
+                ui.label("⚠ No response from the training thread, it may have crashed.");
+            }
+            BackendStatus::Dead => {
+                ui.label("⚠ The training thread has stopped. Restart the application to train again.");
+            }
+        }
+    }
+
+    fn on_run_finished(&mut self, finished: &RunFinished) {
+        if !self.settings.notify_on_finish {
+            return;
+        }
+        let task = self.training_params.lock().unwrap().task;
+        let (title, body) = match finished {
+            RunFinished::Completed { epochs, accuracy, loss, best } => (
+                "Training finished",
+                {
+                    let mut body = format!("{} epochs: {}, loss {:.4}", epochs, format_score(task, *accuracy), loss);
+                    if let Some(best) = best {
+                        body.push_str(&format!(". {}", best));
+                    }
+                    if let Some(seed) = self.run_seed {
+                        body.push_str(&format!(" (seed {})", seed));
+                    }
+                    body
+                },
+            ),
+            RunFinished::Failed(error) => ("Training failed", error.clone()),
+        };
+        if let Err(e) = desktop_notification(title, &body) {
+            let message = format!("Could not show a desktop notification: {}", e);
+            log::warn!("{}", message);
+            self.push_log_line(timestamped(&message));
+        }
+    }
+
+    // Empties the plot buffers; the model published by the backend is untouched
+    fn clear_plots(&mut self) {
+        self.accuracies.clear();
+        self.losses.clear();
+        self.gradient_norms.clear();
+        self.activation_stats = None;
+        self.variance = None;
+        self.lr_range = None;
+        self.plot_view_pinned = false;
+    }
+
+    // Training parameters from the input fields, on top of the last submitted ones
+    fn params_from_inputs(&self) -> Result<TrainingParams, String> {
+        fn parse<T: std::str::FromStr>(name: &str, text: &str) -> Result<T, String> {
+            text.trim().parse().map_err(|_| format!("{} '{}' is not a valid number", name, text))
+        }
+        let hidden_layers: usize = parse("Hidden layers", &self.hidden_layers_input)?;
+        let neurons_per_layer: usize = parse("Neurons per layer", &self.neurons_input)?;
+        if hidden_layers == 0 {
+            return Err("Hidden layers must be at least 1".to_string());
+        }
+        if neurons_per_layer == 0 {
+            return Err("Neurons per layer must be at least 1".to_string());
+        }
+        
+        let seed = match self.seed_input.trim() {
+            "" => None,
+            text => Some(text.parse().map_err(|_| format!("Seed '{}' is not a whole number", text))?),
+        };
+        
+        let target_accuracy = match self.target_accuracy_input.trim() {
+            "" => None,
+            text => Some(parse("Accuracy target", text)?),
+        };
+        
+        let params = TrainingParams {
+            seed,
+            target_accuracy,
+            epochs: parse("Epochs", &self.epochs_input)?,
+            hidden_layers,
+            neurons_per_layer,
+            learning_rate: parse("Learning rate", &self.learning_rate_input)?,
+            warmup_epochs: parse("Warmup epochs", &self.warmup_input)?,
+            scale_mode: self.scale_mode,
+            balance: self.balance,
+            divergence_policy: self.divergence_policy,
+            auto_reduce_lr: self.auto_reduce_lr,
+            lr_patience: self.lr_patience,
+            early_stopping_patience: self.early_stopping_patience,
+            min_delta: self.min_delta,
+            val_fraction: self.val_fraction,
+            test_fraction: self.test_fraction,
+            task: self.task,
+            loss: self.loss_fn,
+            l1_lambda: self.l1_lambda,
+            l2_lambda: self.l2_lambda,
+            noise_std: self.noise_std,
+            track_gradient_norm: self.track_gradient_norm,
+            calibrate: self.calibrate,
+            dedup: self.dedup,
+            fixed_batch_order: self.fixed_batch_order,
+            activation_stats_every: if self.debug_activations { ACTIVATION_STATS_INTERVAL } else { 0 },
+            ..self.training_params.lock().unwrap().clone()
+        };
+        params.validate().map_err(|e| e.to_string())?;
+        Ok(params)
+    }
+    
+    // Dry run of the current inputs on the training data, reported in the log
+    fn validate_configuration(&mut self) {
+        let report = self.params_from_inputs().and_then(|params| {
+            let raw = data_loader::load_potability_data(&self.data_path, self.csv_format).map_err(|e| e.to_string())?;
+            let scaler = data_loader::Scaler::fit(&raw.features, params.scale_mode);
+            let scaled = data_loader::Dataset { features: scaler.transform(&raw.features), labels: raw.labels, weights: raw.weights };
+            dry_run(&scaled, &params).map(|check| (check, params.max_memory)).map_err(|e| e.to_string())
+        });
+        let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        let line = match report {
+            Ok((check, max_memory)) => format!(
+                "Validation passed: {} samples, {}, forward pass on {} samples ok, about {:.2} MiB to train{}",
+                check.n_samples, check.summary, check.batch_size, mib(check.estimated_bytes),
+                if max_memory > 0 { format!(" (limit {:.0} MiB)", mib(max_memory)) } else { String::new() }
+            ),
+            Err(e) => format!("Validation failed: {}", e),
+        };
+        self.push_log_line(timestamped(&line));
+    }
+
+    // Trains the last submitted configuration once per seed on a background
+    // thread; the result is picked up by poll_variance_analysis
+    fn start_variance_analysis(&mut self) {
+        let params = self.training_params.lock().unwrap().clone();
+        let n_seeds = self.variance_seeds;
+        let (data_path, csv_format) = (self.data_path.clone(), self.csv_format);
+        let workers = settings::worker_count(self.cli_workers.or(self.settings.workers));
+        let (sender, receiver) = channel();
+        self.variance_receiver = Some(receiver);
+        self.push_log_line(timestamped(&format!(
+            "Variance analysis started: {} seeds x {} epochs, up to {} at once", n_seeds, params.epochs, workers.min(n_seeds)
+        )));
+
+        std::thread::spawn(move || {
+            let result = data_loader::load_potability_data(&data_path, csv_format)
+                .and_then(|dataset| variance::seed_variance(&dataset, &params, n_seeds, workers))
+                .map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+    }
+
+    fn poll_variance_analysis(&mut self) {
+        let Some(receiver) = &self.variance_receiver else { return };
+        match receiver.try_recv() {
+            Ok(Ok(result)) => {
+                if let Some(last) = result.spread.last() {
+                    self.push_log_line(timestamped(&format!(
+                        "Variance analysis finished: final accuracy {:.2}% ± {:.2} (min {:.2}%, max {:.2}%)",
+                        last.mean, last.std, last.min, last.max
+                    )));
+                }
+                self.variance = Some(result);
+                self.variance_receiver = None;
+            }
+            Ok(Err(e)) => {
+                self.push_log_line(timestamped(&format!("Variance analysis failed: {}", e)));
+                self.variance_receiver = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                self.push_log_line(timestamped("Variance analysis thread stopped unexpectedly"));
+                self.variance_receiver = None;
+            }
+        }
+    }
+
+    // Sweeps the learning rate of the configuration in the input fields on a
+    // fresh network on a background thread; picked up by poll_lr_range_test
+    fn start_lr_range_test(&mut self) {
+        let params = match self.params_from_inputs() {
+            Ok(params) => params,
+            Err(e) => {
+                self.push_log_line(timestamped(&format!("Cannot find a learning rate: {}", e)));
+                return;
+            }
+        };
+        let (data_path, csv_format) = (self.data_path.clone(), self.csv_format);
+        let (sender, receiver) = channel();
+        self.lr_range_receiver = Some(receiver);
+        self.push_log_line(timestamped("Learning rate finder started"));
+
+        std::thread::spawn(move || {
+            let result = data_loader::load_potability_data(&data_path, csv_format)
+                .and_then(|dataset| lr_finder::lr_range_test(&dataset, &params, &LrRangeTest::default()))
+                .map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+    }
+
+    fn poll_lr_range_test(&mut self) {
+        let Some(receiver) = &self.lr_range_receiver else { return };
+        match receiver.try_recv() {
+            Ok(Ok(result)) => {
+                let line = match result.suggested {
+                    Some(lr) => format!("Learning rate finder finished: loss falls fastest at lr {:.2e}", lr),
+                    None => "Learning rate finder finished: the loss never decreased, try a different configuration".to_string(),
+                };
+                self.push_log_line(timestamped(&line));
+                self.lr_range = Some(result);
+                self.lr_range_receiver = None;
+            }
+            Ok(Err(e)) => {
+                self.push_log_line(timestamped(&format!("Learning rate finder failed: {}", e)));
+                self.lr_range_receiver = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                self.push_log_line(timestamped("Learning rate finder thread stopped unexpectedly"));
+                self.lr_range_receiver = None;
+            }
+        }
+    }
+
+    // Smoothed loss against the learning rate on a log scale, with the suggested rate marked
+    fn lr_range_plot(&self, ui: &mut egui::Ui, range: &LrRange, height: f32, reset_view: bool) -> egui::Response {
+        let mut plot = Plot::new("lr_range_plot");
+        if reset_view {
+            plot = plot.reset();
+        }
+        plot.height(height)
+            .x_axis_label("Learning rate")
+            .x_axis_formatter(|value, _, _| format!("{:.0e}", 10f64.powf(value)))
+            .label_formatter(|_, point| format!("lr {:.2e}\nloss {:.4}", 10f64.powf(point.x), point.y))
+            .show(ui, |plot_ui| {
+                let points: PlotPoints = range.points.iter().map(|&(lr, loss)| [lr.log10(), loss]).collect();
+                plot_ui.line(Line::new(points).name("Smoothed loss").width(2.0).color(self.loss_color));
+                if let Some(lr) = range.suggested {
+                    plot_ui.vline(
+                        egui_plot::VLine::new(lr.log10())
+                            .name(format!("Suggested {:.2e}", lr))
+                            .color(self.accuracy_color)
+                            .style(egui_plot::LineStyle::dashed_dense()),
+                    );
+                }
+            })
+            .response
+    }
+
+    // Mean accuracy across seeds with a shaded ±1 std band and faint min/max lines
+    fn variance_plot(&self, ui: &mut egui::Ui, variance: &SeedVariance, height: f32, reset_view: bool) -> egui::Response {
+        let mut plot = Plot::new("variance_plot");
+        if reset_view {
+            plot = plot.reset();
+        }
+        plot.height(height)
+            .x_axis_label("Epoch")
+            .include_y(0.0)
+            .show(ui, |plot_ui| {
+                let spread = &variance.spread;
+                let stride = spread.len().div_ceil(VARIANCE_BAND_SEGMENTS).max(1);
+                let band_color = self.accuracy_color.gamma_multiply(0.25);
+                // One quad per segment: egui only fills convex polygons reliably
+                for i in (0..spread.len().saturating_sub(1)).step_by(stride) {
+                    let (a, b) = (&spread[i], &spread[(i + stride).min(spread.len() - 1)]);
+                    let quad = vec![
+                        [a.epoch as f64, a.mean - a.std],
+                        [b.epoch as f64, b.mean - b.std],
+                        [b.epoch as f64, b.mean + b.std],
+                        [a.epoch as f64, a.mean + a.std],
+                    ];
+                    plot_ui.polygon(
+                        egui_plot::Polygon::new(PlotPoints::new(quad))
+                            .fill_color(band_color)
+                            .stroke(egui::Stroke::NONE),
+                    );
+                }
+
+                let series = |value: fn(&EpochSpread) -> f64| -> PlotPoints {
+                    spread.iter().map(|s| [s.epoch as f64, value(s)]).collect()
+                };
+                let dashed = egui_plot::LineStyle::dashed_dense();
+                plot_ui.line(Line::new(series(|s| s.min)).name("Min").color(band_color).style(dashed));
+                plot_ui.line(Line::new(series(|s| s.max)).name("Max").color(band_color).style(dashed));
+                plot_ui.line(Line::new(series(|s| s.mean)).name("Mean accuracy (%)").width(2.0).color(self.accuracy_color));
+            })
+            .response
+    }
+
+    fn reset_view_button(&mut self, ui: &mut egui::Ui) {
+        if ui.add_enabled(self.plot_view_pinned, egui::Button::new("Reset View"))
+            .on_hover_text("Fit every plot to all of the current data again (double-clicking a plot does the same)")
+            .clicked()
+        {
+            self.plot_view_pinned = false;
+            self.reset_plot_view = true;
+        }
+    }
+
+    // Zooming, panning or dragging a plot pins its view until Reset View
+    fn note_plot_interaction(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        if response.double_clicked() {
+            // egui_plot fits the plot again on double-click, follow the data again too
+            self.plot_view_pinned = false;
+        } else if response.dragged()
+            || (response.hovered() && ui.input(|i| i.scroll_delta != egui::Vec2::ZERO || i.zoom_delta() != 1.0))
+        {
+            self.plot_view_pinned = true;
+        }
+    }
+
+    fn push_log_line(&mut self, line: String) {
+        if self.log_lines.len() == LOG_CAPACITY {
+            self.log_lines.pop_front();
+        }
+        self.log_lines.push_back(line);
+    }
+
+    // Pick up a newly published model and refresh the what-if prediction when due
+    fn update_what_if(&mut self) {
+        if let Some(latest) = self.shared_model.latest() {
+            let is_new = self.model.as_ref().is_none_or(|current| !Arc::ptr_eq(current, &latest));
+            if is_new {
+                if self.what_if_inputs.len() != latest.feature_stats.len() {
+                    self.what_if_inputs = latest.feature_stats.iter().map(|stats| stats.mean).collect();
+                }
+                self.model = Some(latest);
+                // Predict straight away with the new model
+                self.what_if_changed_at = Some(Instant::now() - PREDICTION_DEBOUNCE);
+                self.what_if_by_user = false;
+            }
+        }
+
+        if let (Some(model), Some(changed_at)) = (&self.model, self.what_if_changed_at)
+            && changed_at.elapsed() >= PREDICTION_DEBOUNCE
+        {
+            let result = make_prediction(&model.network, &self.what_if_inputs);
+            if self.what_if_by_user
+                && let Ok(result) = &result
+            {
+                if self.history.len() == HISTORY_CAPACITY {
+                    self.history.pop_front();
+                }
+                self.history.push_back(HistoryEntry {
+                    time: chrono::Local::now(),
+                    inputs: self.what_if_inputs.clone(),
+                    result: result.clone(),
+                });
+            }
+            self.what_if_result = Some(result.map_err(|e| e.to_string()));
+            self.what_if_by_user = false;
+            self.sweep = sweep_feature(&model.network, &self.what_if_inputs, self.sweep_feature, SWEEP_POINTS)
+                .unwrap_or_default();
+            self.explanation = explain_prediction(&model.network, &self.what_if_inputs).ok();
+            self.what_if_changed_at = None;
+        }
+    }
+
+    fn activation_stats_panel(&self, ui: &mut egui::Ui) {
+        let Some((epoch, stats)) = &self.activation_stats else {
+            ui.label("Enable 'Activation Stats' and train to capture per-layer statistics.");
+            return;
+        };
+        ui.label(format!("Epoch {}", epoch));
+
+        let charts = [
+            ("Zero fraction", stats.iter().map(|s| s.zero_fraction).collect::<Vec<_>>(), egui::Color32::from_rgb(220, 50, 50)),
+            ("Mean", stats.iter().map(|s| s.mean).collect(), egui::Color32::from_rgb(70, 130, 180)),
+            ("Std", stats.iter().map(|s| s.std).collect(), egui::Color32::from_rgb(50, 205, 50)),
+        ];
+        for (name, values, color) in charts {
+            ui.label(name);
+            let bars = values
+                .into_iter()
+                .enumerate()
+                .map(|(layer, value)| Bar::new(layer as f64 + 1.0, value).name(format!("Layer {}", layer + 1)))
+                .collect();
+            Plot::new(format!("activation_{}", name))
+                .height(70.0)
+                .allow_zoom(false)
+                .allow_drag(false)
+                .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars).color(color).name(name)));
+        }
+    }
+
+    // Scores every row of the CSV at `input_path` and writes them, with the
+    // predictions appended, next to it as `<name>_predictions.csv`
+    fn run_batch_prediction(model: &TrainedModel, input_path: &str, format: Option<CsvFormat>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let file = data_loader::read_samples(input_path, format)?;
+        let results = make_predictions(&model.network, &file.samples)?;
+
+        let stem = input_path.trim_end_matches(".gz").trim_end_matches(".csv");
+        let output_path = format!("{}_predictions.csv", stem);
+        data_loader::write_predictions(&output_path, &file, &results)?;
+
+        let potable = results.iter().filter(|r| r.is_potable).count();
+        let mut summary = match model.network.task() {
+            TaskType::BinaryClassification => format!(
+                "Scored {} samples: {} potable, {} not potable. Written to {}",
+                results.len(), potable, results.len() - potable, output_path
+            ),
+            TaskType::Regression => format!("Scored {} samples. Written to {}", results.len(), output_path),
+        };
+        if !file.skipped.is_empty() {
+            let lines: Vec<String> = file.skipped.iter().map(|(line, reason)| format!("line {} ({})", line, reason)).collect();
+            summary.push_str(&format!("\nSkipped {} rows: {}", file.skipped.len(), lines.join(", ")));
+        }
+        Ok(summary)
+    }
+
+    fn batch_prediction_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Batch Prediction");
+        ui.horizontal(|ui| {
+            ui.label("CSV file:");
+            ui.text_edit_singleline(&mut self.batch_input_path);
+        });
+
+        let model = self.model.clone();
+        let can_run = model.is_some() && !self.batch_input_path.trim().is_empty();
+        if ui.add_enabled(can_run, egui::Button::new("Predict CSV")).clicked()
+            && let Some(model) = model
+        {
+            let path = self.batch_input_path.trim().to_string();
+            let summary = match Self::run_batch_prediction(&model, &path, self.csv_format) {
+                Ok(summary) => summary,
+                Err(e) => format!("Batch prediction failed: {}", e),
+            };
+            self.push_log_line(timestamped(&summary));
+            self.batch_summary = Some(summary);
+        }
+
+        if let Some(summary) = &self.batch_summary {
+            ui.label(summary);
+        }
+    }
+
+    fn export_history(&self, path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut writer = csv::Writer::from_path(path)?;
+        let n_inputs = self.history.iter().map(|entry| entry.inputs.len()).max().unwrap_or(0);
+        let mut header = vec!["timestamp".to_string()];
+        header.extend((0..n_inputs).map(data_loader::feature_name));
+        header.extend(["probability".to_string(), "verdict".to_string()]);
+        writer.write_record(&header)?;
+        for entry in &self.history {
+            let mut record = vec![entry.time.format("%Y-%m-%d %H:%M:%S").to_string()];
+            record.extend(entry.inputs.iter().map(|v| v.to_string()));
+            record.push(format!("{:.6}", entry.result.probability));
+            record.push(entry.result.verdict().unwrap_or_default());
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn history_panel(&mut self, ui: &mut egui::Ui) {
+        if self.history.is_empty() {
+            ui.label("Predictions made with the what-if sliders are listed here.");
+        }
+        egui::ScrollArea::vertical().id_source("history_scroll").max_height(150.0).stick_to_bottom(true).show(ui, |ui| {
+            for entry in &self.history {
+                let outcome = match entry.result.verdict() {
+                    Some(verdict) => format!("{:.1}% {}", entry.result.probability * 100.0, verdict),
+                    None => format!("= {:.4}", entry.result.probability),
+                };
+                let inputs: Vec<String> = entry.inputs.iter().take(3).map(|v| format!("{:.2}", v)).collect();
+                ui.label(format!("{}  {}  ({}, ...)", entry.time.format("%H:%M:%S"), outcome, inputs.join(", ")))
+                .on_hover_text(
+                    entry.inputs.iter().enumerate().map(|(i, v)| format!("{}: {}", data_loader::feature_name(i), v)).collect::<Vec<_>>().join("\n"),
+                );
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("CSV file:");
+            ui.text_edit_singleline(&mut self.history_path);
+        });
+        ui.horizontal(|ui| {
+            let has_history = !self.history.is_empty();
+            if ui.add_enabled(has_history, egui::Button::new("Export history CSV")).clicked() {
+                let path = self.history_path.trim().to_string();
+                let message = match self.export_history(&path) {
+                    Ok(()) => format!("Exported {} predictions to {}", self.history.len(), path),
+                    Err(e) => format!("Failed to export history: {}", e),
+                };
+                self.push_log_line(timestamped(&message));
+            }
+            if ui.add_enabled(has_history, egui::Button::new("Clear History")).clicked() {
+                self.history.clear();
+            }
+        });
+    }
+
+    // Plain-text report of the last run for pasting elsewhere
+    fn results_summary(&self) -> String {
+        let params = self.training_params.lock().unwrap();
+        let mut lines = vec![
+            format!(
+                "Parameters: {} epochs, {} hidden layers x {} neurons, learning rate {} ({} warmup epochs), scaling {:?}, balance {:?}, L1 {}, L2 {}, input noise {}",
+                params.epochs, params.hidden_layers, params.neurons_per_layer, params.learning_rate,
+                params.warmup_epochs, params.scale_mode, params.balance, params.l1_lambda, params.l2_lambda, params.noise_std
+            ),
+            format!("Epochs run: {}", self.accuracies.last_epoch().unwrap_or(0)),
+        ];
+        if let Some(seed) = self.run_seed {
+            lines.push(format!("Seed: {}", seed));
+        }
+        if let Some(summary) = &self.network_summary {
+            lines.push(format!("Model: {}", summary));
+        }
+        if let Some(data) = &self.data_summary {
+            lines.push(format!("Data: {}", data));
+        }
+        if let (Some(accuracy), Some(loss)) = (self.accuracies.last(), self.losses.last()) {
+            lines.push(format!("Final {}", format_score(params.task, accuracy)));
+            lines.push(format!("Final loss: {:.4}", loss));
+        }
+        if let Some(best) = &self.best_epoch {
+            lines.push(best.to_string());
+        }
+        if let Some(model) = &self.model {
+            lines.push(format!("Average precision: {:.4}", model.average_precision));
+        }
+        lines.join("\n")
+    }
+
+    fn save_model_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Model file:");
+            ui.text_edit_singleline(&mut self.model_path);
+        });
+        let model = self.model.clone();
+        let (save, export) = ui.horizontal(|ui| {
+            let save = ui.add_enabled(model.is_some(), egui::Button::new("Save Model")).clicked();
+            let export = ui
+                .add_enabled(model.is_some(), egui::Button::new("Export as Rust"))
+                .on_hover_text("Write the model as a dependency-free .rs file next to the model file, with a predict() function")
+                .clicked();
+            (save, export)
+        }).inner;
+        let Some(model) = model else { return };
+        if save {
+            let path = self.model_path.trim();
+            let message = match save_model(&model.network, path) {
+                Ok(()) => format!("Model saved to {}", path),
+                Err(e) => format!("Failed to save model: {}", e),
+            };
+            self.push_log_line(timestamped(&message));
+        }
+        if export {
+            let path = std::path::Path::new(self.model_path.trim()).with_extension("rs");
+            let message = match export_rust(&model.network, &path.to_string_lossy()) {
+                Ok(()) => format!("Model exported as Rust source to {}", path.display()),
+                Err(e) => format!("Failed to export model: {}", e),
+            };
+            self.push_log_line(timestamped(&message));
+        }
+    }
+
+    fn what_if_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("What-if Prediction");
+        ui.add_space(5.0);
+
+        let Some(model) = self.model.clone() else {
+            ui.label("Train a model to enable predictions.");
+            return;
+        };
+
+        // What the network sees of each input after the scaler fitted on the training data
+        let scaler = model.network.scaler();
+        let scaled = scaler.map(|scaler| scaler.transform_row(&self.what_if_inputs));
+        let scaled_meaning = match scaler.map(|scaler| scaler.mode()) {
+            Some(ScaleMode::Standardize) => "standard deviations from the training mean",
+            Some(ScaleMode::MinMax) => "the position within the training range, 0 at its minimum and 1 at its maximum",
+            Some(ScaleMode::None) | None => "the raw value, unscaled",
+        };
+        for (i, stats) in model.feature_stats.iter().enumerate() {
+            let name = data_loader::feature_name(i);
+            // The training range, kept within what the feature can physically take
+            let (min, max) = data_loader::FEATURE_BOUNDS.get(i).map_or((stats.min, stats.max), |&(low, high)| (stats.min.max(low), stats.max.min(high)));
+            ui.horizontal(|ui| {
+                let slider = egui::Slider::new(&mut self.what_if_inputs[i], min..=max).text(name);
+                if ui.add(slider).changed() {
+                    self.what_if_changed_at = Some(Instant::now());
+                    self.what_if_by_user = true;
+                }
+                if let Some(value) = scaled.as_ref().and_then(|scaled| scaled.get(i)) {
+                    ui.weak(format!("→ {:+.2}", value))
+                        .on_hover_text(format!("Input the network sees: {}", scaled_meaning));
+                }
+            });
+        }
+
+        ui.add_space(10.0);
+        match &self.what_if_result {
+            Some(Ok(result)) => {
+                let color = if result.is_potable { egui::Color32::from_rgb(50, 205, 50) } else { egui::Color32::from_rgb(220, 50, 50) };
+                ui.colored_label(color, result.to_string());
+            }
+            Some(Err(e)) => {
+                ui.label(format!("Prediction failed: {}", e));
+            }
+            None => {}
+        }
+
+        ui.add_space(10.0);
+        ui.heading("Feature Sensitivity");
+        let previous = self.sweep_feature;
+        egui::ComboBox::from_label("Vary")
+            .selected_text(data_loader::feature_name(self.sweep_feature))
+            .show_ui(ui, |ui| {
+                for i in 0..model.feature_stats.len() {
+                    ui.selectable_value(&mut self.sweep_feature, i, data_loader::feature_name(i));
+                }
+            });
+        if self.sweep_feature != previous {
+            self.what_if_changed_at = Some(Instant::now() - PREDICTION_DEBOUNCE);
+        }
+        Plot::new("sweep_plot")
+            .height(150.0)
+            .include_y(0.0)
+            .include_y(1.0)
+            .x_axis_label(data_loader::feature_name(self.sweep_feature))
+            .y_axis_label("P(potable)")
+            .show(ui, |plot_ui| {
+                let points: PlotPoints = self.sweep.iter().map(|&(x, p)| [x, p]).collect();
+                plot_ui.line(
+                    Line::new(points)
+                        .name("P(potable)")
+                        .width(2.0)
+                        .color(self.accuracy_color),
+                );
+            });
+
+        if let Some(explanation) = &self.explanation {
+            ui.add_space(10.0);
+            ui.heading("Why This Prediction");
+            ui.label(match model.network.task() {
+                TaskType::BinaryClassification => "Push of each feature toward potable (right) or not potable (left), in log-odds",
+                TaskType::Regression => "Push of each feature toward a higher (right) or lower (left) value",
+            });
+            explanation_plot(ui, explanation, &self.what_if_inputs, (self.accuracy_color, self.loss_color));
+        }
+        
+        ui.add_space(10.0);
+        ui.heading("Precision-Recall");
+        ui.label(format!("Average precision: {:.3}", model.average_precision));
+        Plot::new("pr_curve_plot")
+            .height(200.0)
+            .data_aspect(1.0)
+            .include_x(0.0)
+            .include_x(1.0)
+            .include_y(0.0)
+            .include_y(1.0)
+            .x_axis_label("Recall")
+            .y_axis_label("Precision")
+            .show(ui, |plot_ui| {
+                let points: PlotPoints = model.pr_curve.iter().map(|&(r, p)| [r, p]).collect();
+                plot_ui.line(
+                    Line::new(points)
+                        .name("Precision-Recall")
+                        .width(2.0)
+                        .color(egui::Color32::from_rgb(70, 130, 180)), // Steel blue
+                );
+            });
+
+        ui.add_space(10.0);
+        ui.heading("Feature Importance");
+        ui.label(format!("Drop in {} when a feature is shuffled", model.network.task().score_name()));
+        importance_plot(ui, &model.importance, self.accuracy_color);
+    }
+}
+
+// A run paused when the window closes would otherwise wait forever
+impl Drop for TrainingWindow {
+    fn drop(&mut self) {
+        self.pause.resume();
     }
 }
 
 impl eframe::App for TrainingWindow {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Check for new accuracy values
-        let mut received_data = false;
-        while let Ok((accuracy, loss)) = self.receiver.try_recv() {
-            self.accuracies.push(accuracy);
-            self.losses.push(loss);
-            self.is_training = true;
-            self.training_completed = false;
+        while let Ok((epoch, accuracy, loss)) = self.receiver.try_recv() {
+            self.accuracies.push(epoch, accuracy, self.max_plot_points);
+            self.losses.push(epoch, loss, self.max_plot_points);
+            // Updates still queued when the finish message was handled don't restart the run
+            self.is_training = !self.training_completed;
             self.first_run = false;  // No longer the first run
-            self.last_received_time = std::time::Instant::now();
-            received_data = true;
         }
         
-        // Check if training has completed (no updates for 2 seconds)
-        if self.is_training && !received_data && 
-           self.last_received_time.elapsed() > std::time::Duration::from_secs(2) &&
-           !self.training_completed {
+        while let Ok(summary) = self.summary_receiver.try_recv() {
+            self.network_summary = Some(summary);
+        }
+        
+        while let Ok(seed) = self.seed_receiver.try_recv() {
+            self.run_seed = Some(seed);
+        }
+        while let Ok(summary) = self.data_summary_receiver.try_recv() {
+            self.data_summary = Some(summary);
+        }
+        
+        while let Ok((epoch, norm)) = self.gradient_norm_receiver.try_recv() {
+            // Plotted by epochs completed, like the accuracy and loss
+            self.gradient_norms.push(epoch + 1, norm, self.max_plot_points);
+        }
+        
+        while let Ok(stats) = self.activation_stats_receiver.try_recv() {
+            self.activation_stats = Some(stats);
+        }
+        
+        self.poll_variance_analysis();
+        self.poll_lr_range_test();
+        
+        // Collect diagnostics from the background threads
+        while let Ok(line) = self.log_receiver.try_recv() {
+            self.push_log_line(line);
+        }
+        
+        // The backend reports the end of every run explicitly
+        while let Ok(finished) = self.finished_receiver.try_recv() {
             self.is_training = false;
             self.training_completed = true;
-            println!("UI detected training completion");
+            self.first_run = false;
+            self.best_epoch = match &finished {
+                RunFinished::Completed { best, .. } => *best,
+                RunFinished::Failed(_) => None,
+            };
+            self.on_run_finished(&finished);
         }
+        
+        self.update_backend_status();
+        self.update_what_if();
 
         egui::TopBottomPanel::top("parameters_panel").show(ctx, |ui| {
-            ui.heading("Neural Network Water Potability Training");
+            ui.horizontal(|ui| {
+                ui.heading("Neural Network Water Potability Training");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    self.backend_status_label(ui);
+                });
+            });
             ui.add_space(10.0);
             
             ui.horizontal(|ui| {
                 ui.label("Epochs:");
-                ui.text_edit_singleline(&mut self.epochs_input);
+                ui.text_edit_singleline(&mut self.epochs_input)
+                    .on_hover_text("Full passes over the training data. 500-3000 is typical; more takes longer but may fit better.");
                 
                 ui.label("Hidden Layers:");
-                ui.text_edit_singleline(&mut self.hidden_layers_input);
+                ui.text_edit_singleline(&mut self.hidden_layers_input)
+                    .on_hover_text("Number of hidden layers between input and output. 1-3 is plenty for this dataset.");
                 
                 ui.label("Neurons per Layer:");
-                ui.text_edit_singleline(&mut self.neurons_input);
+                ui.text_edit_singleline(&mut self.neurons_input)
+                    .on_hover_text("Units in each hidden layer. 8-64 is a good range; larger layers train slower and overfit more easily.");
                 
                 ui.label("Learning Rate:");
-                ui.text_edit_singleline(&mut self.learning_rate_input);
+                ui.text_edit_singleline(&mut self.learning_rate_input)
+                    .on_hover_text("Step size of each weight update. 0.01-0.5 is a good range; values above ~1.0 often diverge.");
+                if self.learning_rate_input.trim().parse::<f64>().is_ok_and(|rate| rate > HIGH_LEARNING_RATE) {
+                    ui.colored_label(egui::Color32::from_rgb(255, 165, 0), "⚠ High")
+                        .on_hover_text(format!(
+                            "Learning rates above {} usually make the loss blow up within a few epochs. \
+                             Training still starts, and stops with an explanation if it diverges",
+                            HIGH_LEARNING_RATE
+                        ));
+                }
+                
+                ui.label("Warmup Epochs:");
+                ui.text_edit_singleline(&mut self.warmup_input)
+                    .on_hover_text("Epochs over which the learning rate ramps up from near zero. 0 disables; 10-100 helps with high rates.");
+                
+                ui.label("Seed:");
+                ui.add(egui::TextEdit::singleline(&mut self.seed_input).hint_text("random").desired_width(120.0))
+                    .on_hover_text("Seeds the data shuffle, weights and batches. Leave empty for a new random seed each run; \
+                                    the seed used is shown in the status line.");
+                if let Some(seed) = self.run_seed
+                    && self.seed_input.trim() != seed.to_string()
+                    && ui.button("Reuse").on_hover_text(format!("Fill in the last run's seed, {}, to reproduce it", seed)).clicked()
+                {
+                    self.seed_input = seed.to_string();
+                }
+            });
+            
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Scaling")
+                    .selected_text(format!("{:?}", self.scale_mode))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.scale_mode, ScaleMode::Standardize, "Standardize");
+                        ui.selectable_value(&mut self.scale_mode, ScaleMode::MinMax, "MinMax");
+                        ui.selectable_value(&mut self.scale_mode, ScaleMode::None, "None");
+                    })
+                    .response
+                    .on_hover_text("How features are rescaled before training. Standardize is recommended; None leaves raw units.");
+                
+                egui::ComboBox::from_label("Balance")
+                    .selected_text(format!("{:?}", self.balance))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.balance, Balance::None, "None");
+                        ui.selectable_value(&mut self.balance, Balance::Oversample, "Oversample");
+                        ui.selectable_value(&mut self.balance, Balance::Undersample, "Undersample");
+                    })
+                    .response
+                    .on_hover_text("Even out potable/not potable samples in the training data by repeating or dropping samples.");
+                
+                egui::ComboBox::from_label("On Divergence")
+                    .selected_text(format!("{:?}", self.divergence_policy))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.divergence_policy, DivergencePolicy::Abort, "Abort");
+                        ui.selectable_value(&mut self.divergence_policy, DivergencePolicy::SkipBatch, "SkipBatch");
+                        ui.selectable_value(&mut self.divergence_policy, DivergencePolicy::ReduceLr, "ReduceLr");
+                    })
+                    .response
+                    .on_hover_text("What to do when a batch's loss or update turns NaN or infinite: stop the run, discard that \
+                                    batch's update, or discard it and retry at half the learning rate. Each action is logged.");
+                
+                ui.checkbox(&mut self.auto_reduce_lr, "Auto-reduce LR")
+                    .on_hover_text("Halve the learning rate when the loss rises for 'patience' epochs in a row");
+                ui.label("Patience:");
+                ui.add_enabled(self.auto_reduce_lr, egui::DragValue::new(&mut self.lr_patience).clamp_range(1..=1000))
+                    .on_hover_text("Consecutive epochs of rising loss before the learning rate is halved. 3-10 is typical.");
+                
+                ui.label("Early Stop:");
+                ui.add(egui::DragValue::new(&mut self.early_stopping_patience).clamp_range(0..=10_000))
+                    .on_hover_text("Stop after this many epochs without improvement in validation loss (the validation share of the data is held out). 0 disables.");
+                ui.label("Min Delta:");
+                ui.add_enabled(
+                    self.early_stopping_patience > 0,
+                    egui::DragValue::new(&mut self.min_delta).speed(0.0001).clamp_range(0.0..=1.0),
+                )
+                .on_hover_text("An epoch only resets the early stop count if validation loss drops by more than this. \
+                                Larger values stop slowly creeping curves sooner; 0 counts any decrease.");
+                
+                ui.label("Validation:");
+                ui.add(egui::DragValue::new(&mut self.val_fraction).speed(0.01).clamp_range(0.05..=0.5).max_decimals(2))
+                    .on_hover_text("Share of the data held out and scored every epoch for the plots, early stopping and the accuracy target, split per class");
+                ui.label("Test:");
+                ui.add(egui::DragValue::new(&mut self.test_fraction).speed(0.01).clamp_range(0.0..=0.45).max_decimals(2))
+                    .on_hover_text("Share of the data kept out of training and early stopping entirely, split per class. \
+                                    The final scores are measured on it. 0 disables.");
+                
+                ui.label("Target:");
+                ui.add(egui::TextEdit::singleline(&mut self.target_accuracy_input).hint_text("none").desired_width(60.0))
+                    .on_hover_text(match self.task {
+                        TaskType::BinaryClassification => "Stop once validation accuracy reaches this percentage (the validation share of the data is held out). \
+                                                           Empty runs every epoch; early stopping may still end the run first.",
+                        TaskType::Regression => "Stop once validation R² reaches this (the validation share of the data is held out). \
+                                                 Empty runs every epoch; early stopping may still end the run first.",
+                    });
+                
+                egui::ComboBox::from_label("Task")
+                    .selected_text(match self.task {
+                        TaskType::BinaryClassification => "Classification",
+                        TaskType::Regression => "Regression",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.task, TaskType::BinaryClassification, "Classification");
+                        if ui.selectable_value(&mut self.task, TaskType::Regression, "Regression").clicked() {
+                            // Regression fits a linear output with a squared error; classes do not apply
+                            if self.loss_fn == LossFn::Bce {
+                                self.loss_fn = LossFn::Mse;
+                            }
+                            self.balance = Balance::None;
+                            self.calibrate = false;
+                        }
+                    })
+                    .response
+                    .on_hover_text("Classification predicts potable/not potable from 0/1 labels and reports accuracy. \
+                                    Regression predicts a continuous label such as a quality score and reports R².");
+                
+                egui::ComboBox::from_label("Loss")
+                    .selected_text(match self.loss_fn {
+                        LossFn::Bce => "BCE",
+                        LossFn::Mse => "MSE",
+                        LossFn::Huber { .. } => "Huber",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.loss_fn, LossFn::Bce, "BCE");
+                        ui.selectable_value(&mut self.loss_fn, LossFn::Mse, "MSE");
+                        ui.selectable_value(&mut self.loss_fn, LossFn::Huber { delta: self.huber_delta }, "Huber");
+                    })
+                    .response
+                    .on_hover_text("Loss minimised during training and shown in the loss plot. Accuracy is measured the same way for every loss.");
+                if let LossFn::Huber { delta } = &mut self.loss_fn {
+                    ui.label("Delta:");
+                    ui.add(egui::DragValue::new(delta).speed(0.01).clamp_range(0.01..=1.0))
+                        .on_hover_text("Residual beyond which the Huber loss grows linearly instead of quadratically");
+                    self.huber_delta = *delta;
+                }
+                
+                ui.label("L1:");
+                ui.add(egui::DragValue::new(&mut self.l1_lambda).speed(0.0001).clamp_range(0.0..=1.0))
+                    .on_hover_text("Weight penalty pushing small weights to zero. 0 disables; try 0.0001-0.01.");
+                ui.label("L2:");
+                ui.add(egui::DragValue::new(&mut self.l2_lambda).speed(0.0001).clamp_range(0.0..=1.0))
+                    .on_hover_text("Weight decay penalty, combinable with L1. 0 disables; try 0.0001-0.01.");
+                ui.add(egui::Slider::new(&mut self.noise_std, 0.0..=1.0).text("Input Noise"))
+                    .on_hover_text("Std of Gaussian noise added to the scaled training inputs each batch, like sensor \
+                                    measurement error. Validation and predictions see clean inputs. 0 disables; try 0.05-0.2.");
+                
+                ui.checkbox(&mut self.debug_activations, "Activation Stats")
+                    .on_hover_text("Capture per-layer activation statistics while training (slower)");
+                ui.checkbox(&mut self.calibrate, "Calibrate")
+                    .on_hover_text("Hold out 20% of the training data to fit Platt scaling, making probabilities less overconfident");
+                ui.checkbox(&mut self.dedup, "Remove Duplicates")
+                    .on_hover_text("Drop rows that exactly repeat an earlier row before splitting. Off keeps intentional duplicates");
+                ui.checkbox(&mut self.track_gradient_norm, "Gradient Norm")
+                    .on_hover_text("Plot the global gradient norm: collapsing toward zero means convergence, growing means instability");
+                ui.checkbox(&mut self.fixed_batch_order, "Deterministic Batches")
+                    .on_hover_text("Shuffle once and keep that batch order every epoch; with a fixed seed every run, even after a \
+                                    restart, sees identical batches. For debugging: reshuffling each epoch usually generalizes slightly better");
+                
+                ui.separator();
+                
+                let mut colorblind = self.settings.palette == Palette::Colorblind;
+                if ui.checkbox(&mut colorblind, "Colorblind Palette").changed() {
+                    self.settings.palette = if colorblind { Palette::Colorblind } else { Palette::Standard };
+                    (self.accuracy_color, self.loss_color) = self.settings.palette.colors();
+                    if let Err(e) = self.settings.save() {
+                        self.push_log_line(timestamped(&format!("Failed to save settings: {}", e)));
+                    }
+                }
+                
+                if ui.checkbox(&mut self.settings.notify_on_finish, "Notify When Done")
+                    .on_hover_text("Show a desktop notification when a training run finishes or fails")
+                    .changed()
+                    && let Err(e) = self.settings.save()
+                {
+                    self.push_log_line(timestamped(&format!("Failed to save settings: {}", e)));
+                }
+                
+                let mut idle_refresh_ms = self.settings.idle_refresh.as_millis() as u64;
+                ui.label("Idle Refresh:");
+                let response = ui.add(egui::DragValue::new(&mut idle_refresh_ms).clamp_range(MIN_IDLE_REFRESH_MS..=10_000).suffix(" ms"))
+                    .on_hover_text("How often the window redraws while no run is in progress. Training always redraws every frame; \
+                                    longer intervals use less CPU when the window is left open");
+                if response.changed() {
+                    self.settings.idle_refresh = Duration::from_millis(idle_refresh_ms);
+                }
+                if (response.drag_released() || response.lost_focus())
+                    && let Err(e) = self.settings.save()
+                {
+                    self.push_log_line(timestamped(&format!("Failed to save settings: {}", e)));
+                }
+                
+                egui::ComboBox::from_label("X Axis")
+                    .selected_text(self.plot_x_axis.label())
+                    .show_ui(ui, |ui| {
+                        for axis in [PlotXAxis::Epoch, PlotXAxis::Update] {
+                            ui.selectable_value(&mut self.plot_x_axis, axis, axis.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Plot against the epoch each value was reported at, or against the order updates arrived in.");
+                
+                ui.label("Max Plot Points:");
+                ui.add(egui::DragValue::new(&mut self.max_plot_points).clamp_range(100..=1_000_000))
+                    .on_hover_text("Points kept per curve before older ones are thinned out. Lower it if long runs make the UI sluggish.");
             });
             
             ui.horizontal(|ui| {
@@ -113,70 +1588,164 @@ impl eframe::App for TrainingWindow {
                 };
                 
                 if ui.button(button_text).clicked() && !self.is_training {
-                    if let Ok(parsed_epochs) = self.epochs_input.parse::<usize>() {
-                        if let Ok(parsed_hidden_layers) = self.hidden_layers_input.parse::<usize>() {
-                            if let Ok(parsed_neurons) = self.neurons_input.parse::<usize>() {
-                                if let Ok(parsed_lr) = self.learning_rate_input.parse::<f64>() {
-                                    // Parameter validation
-                                    if parsed_hidden_layers == 0 {
-                                        ui.label("Hidden layers must be at least 1");
-                                        return;
-                                    }
-                                    if parsed_neurons == 0 {
-                                        ui.label("Neurons per layer must be at least 1");
-                                        return;
-                                    }
-                                    if parsed_lr <= 0.0 {
-                                        ui.label("Learning rate must be greater than 0");
-                                        return;
-                                    }
-                                    
-                                    let mut params = self.training_params.lock().unwrap();
-                                    params.epochs = parsed_epochs;
-                                    params.hidden_layers = parsed_hidden_layers;
-                                    params.neurons_per_layer = parsed_neurons;
-                                    params.learning_rate = parsed_lr;
-                                    params.restart_training = true;
-                                    self.accuracies.clear();
-                                    self.losses.clear();
-                                    self.is_training = false;
-                                    self.training_completed = false;
-                                    
-                                    // Send updated parameters to the training thread
-                                    self.params_sender.send(self.training_params.clone()).unwrap_or_else(|e| {
-                                        println!("Failed to send parameters: {}", e);
-                                    });
-                                }
-                            }
+                    match self.params_from_inputs() {
+                        Ok(_) if !self.busy.try_claim() => self.push_log_line(timestamped(BUSY_MESSAGE)),
+                        Ok(params) => {
+                            *self.training_params.lock().unwrap() = TrainingParams { restart_training: true, ..params };
+                            self.clear_plots();
+                            self.run_seed = None;
+                            self.pause.resume();
+                            self.is_training = false;
+                            self.training_completed = false;
+                            
+                            // Send updated parameters to the training thread
+                            self.params_sender.send(self.training_params.clone()).unwrap_or_else(|e| {
+                                log::error!("Failed to send parameters: {}", e);
+                                self.busy.release();
+                            });
                         }
+                        Err(e) => self.push_log_line(timestamped(&format!("Cannot start training: {}", e))),
                     }
                 }
+                
+                if ui.button("Validate")
+                    .on_hover_text("Check the parameters and data and run one forward pass, without training")
+                    .clicked()
+                {
+                    self.validate_configuration();
+                }
+                
+                // Only the plot buffers are cleared, the trained model keeps serving predictions
+                let has_plots = !self.accuracies.is_empty() || !self.gradient_norms.is_empty()
+                    || self.activation_stats.is_some() || self.variance.is_some() || self.lr_range.is_some();
+                if ui.add_enabled(!self.is_training && has_plots, egui::Button::new("Reset Plots"))
+                    .on_hover_text("Clear the curves without discarding the trained model")
+                    .clicked()
+                {
+                    self.clear_plots();
+                }
+                
+                ui.separator();
+                ui.add(egui::DragValue::new(&mut self.variance_seeds).clamp_range(2..=20).prefix("Seeds: "))
+                    .on_hover_text("Number of seeds trained by the variance analysis");
+                let mut workers = self.cli_workers.or(self.settings.workers).unwrap_or(0);
+                let response = ui.add_enabled(
+                    self.cli_workers.is_none(),
+                    egui::DragValue::new(&mut workers)
+                        .clamp_range(0..=256)
+                        .prefix("Workers: ")
+                        .custom_formatter(|n, _| if n == 0.0 { "all".to_string() } else { format!("{}", n) }),
+                )
+                    .on_hover_text("Most seeds the variance analysis trains at once, one thread each; 0 runs one per core. \
+                                    Each seed's training itself uses a single thread")
+                    .on_disabled_hover_text("Set by --workers for this session");
+                if response.changed() {
+                    self.settings.workers = (workers > 0).then_some(workers);
+                }
+                if (response.drag_released() || response.lost_focus())
+                    && let Err(e) = self.settings.save()
+                {
+                    self.push_log_line(timestamped(&format!("Failed to save settings: {}", e)));
+                }
+                let analysing = self.variance_receiver.is_some();
+                if ui.add_enabled(!analysing, egui::Button::new(if analysing { "Analysing..." } else { "Run Variance Analysis" }))
+                    .on_hover_text("Train the last started configuration once per seed and plot the mean accuracy with a ±1 std band. This takes as long as several training runs.")
+                    .clicked()
+                {
+                    self.start_variance_analysis();
+                }
+                
+                let finding = self.lr_range_receiver.is_some();
+                if ui.add_enabled(!finding, egui::Button::new(if finding { "Finding LR..." } else { "Find LR" }))
+                    .on_hover_text("Train a fresh copy of the configured network for a few batches while raising the learning rate, \
+                                    and plot the loss against it. Pick a rate where the loss falls steeply, before it blows up.")
+                    .clicked()
+                {
+                    self.start_lr_range_test();
+                }
+                if let Some(suggested) = self.lr_range.as_ref().and_then(|range| range.suggested)
+                    && ui.button(format!("Use LR {:.2e}", suggested))
+                        .on_hover_text("Fill the learning rate field with the finder's suggestion")
+                        .clicked()
+                {
+                    self.learning_rate_input = format!("{:e}", suggested);
+                }
             });
             
             ui.add_space(5.0);
             
+            if let Some(summary) = &self.network_summary {
+                ui.label(format!("Model: {}", summary));
+            }
+            if let Some(data) = &self.data_summary {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Data: {}", data));
+                    if data.shared_with_held_out > 0 {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 165, 0),
+                            format!("⚠ {} held-out rows duplicate training rows", data.shared_with_held_out),
+                        )
+                        .on_hover_text("Held-out scores are optimistic. Enable Remove Duplicates to drop them");
+                    }
+                });
+            }
+            
             // Display status
             if self.is_training {
                 ui.horizontal(|ui| {
-                    ui.label("🔄 Training in progress...");
-                    if let Some(&last_accuracy) = self.accuracies.last() {
-                        if let Some(&last_loss) = self.losses.last() {
-                            ui.label(format!("Current Accuracy: {:.2}%, Loss: {:.4}", last_accuracy, last_loss));
-                            ui.label(format!("Epoch: {}/{}", self.accuracies.len(), 
-                                            self.training_params.lock().unwrap().epochs));
+                    // Pausing holds the run between epochs; the plots and the optimizer state stay as they are
+                    if self.pause.is_paused() {
+                        ui.label("⏸ Training paused");
+                        if ui.button("▶ Resume").clicked() {
+                            self.pause.resume();
                         }
+                    } else {
+                        ui.label("🔄 Training in progress...");
+                        if ui.button("⏸ Pause").on_hover_text("Hold training after the current epoch, freeing the CPU until resumed").clicked() {
+                            self.pause.pause();
+                        }
+                    }
+                    if let Some(last_accuracy) = self.accuracies.last()
+                        && let Some(last_loss) = self.losses.last()
+                    {
+                        let task = self.training_params.lock().unwrap().task;
+                        ui.label(format!("Current {}, Loss: {:.4}", format_score(task, last_accuracy), last_loss));
+                        sparkline(ui, &self.losses.tail(SPARKLINE_POINTS), self.loss_color);
+                        let params = self.training_params.lock().unwrap();
+                        let epoch = self.accuracies.last_epoch().unwrap_or(0);
+                        ui.label(format!("Epoch: {}/{}", epoch, params.epochs));
+                        ui.label(format!("LR: {:.4}", learning_rate_at(&params, epoch.saturating_sub(1))));
+                    }
+                    if let Some(seed) = self.run_seed {
+                        ui.label(format!("Seed: {}", seed));
                     }
                 });
             } else if self.training_completed {
                 ui.horizontal(|ui| {
                     ui.label("✅ Training completed.");
-                    if let Some(&last_accuracy) = self.accuracies.last() {
-                        if let Some(&last_loss) = self.losses.last() {
-                            ui.label(format!("Final Accuracy: {:.2}%, Loss: {:.4}", last_accuracy, last_loss));
-                        }
+                    if let Some(last_accuracy) = self.accuracies.last()
+                        && let Some(last_loss) = self.losses.last()
+                    {
+                        let task = self.training_params.lock().unwrap().task;
+                        ui.label(format!("Final {}, Loss: {:.4}", format_score(task, last_accuracy), last_loss));
+                        sparkline(ui, &self.losses.tail(SPARKLINE_POINTS), self.loss_color);
+                    }
+                    if let Some(seed) = self.run_seed {
+                        ui.label(format!("Seed: {}", seed))
+                            .on_hover_text("Enter this seed before starting to reproduce the run");
+                    }
+                    if let Some(best) = &self.best_epoch {
+                        ui.label(best.to_string())
+                            .on_hover_text("A fixed epoch count around here reproduces the best model without early stopping");
+                    }
+                    if ui.button("📋 Copy Results").on_hover_text("Copy parameters and final metrics to the clipboard").clicked() {
+                        let summary = self.results_summary();
+                        ui.output_mut(|output| output.copied_text = summary);
                     }
                 });
                 ui.label("You can change parameters and restart training.");
+            } else if self.busy.is_busy() {
+                ui.label("⏳ Preparing the run...");
             } else if self.first_run {
                 ui.label("👆 Set parameters and click 'Start Training' to begin");
             } else {
@@ -184,100 +1753,259 @@ impl eframe::App for TrainingWindow {
             }
         });
 
+        // Training log, newest lines at the bottom
+        egui::TopBottomPanel::bottom("log_panel").resizable(true).show(ctx, |ui| {
+            ui.collapsing("Training Log", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(150.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        let mut text = self.log_lines.iter().cloned().collect::<Vec<_>>().join("\n");
+                        ui.add(
+                            egui::TextEdit::multiline(&mut text)
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(f32::INFINITY)
+                                .interactive(false),
+                        );
+                    });
+            });
+        });
+
+        egui::SidePanel::right("prediction_panel").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.what_if_panel(ui);
+                ui.add_space(10.0);
+                self.batch_prediction_panel(ui);
+                ui.add_space(10.0);
+                self.save_model_panel(ui);
+                ui.add_space(10.0);
+                ui.collapsing("Prediction History", |ui| self.history_panel(ui));
+                ui.collapsing("Activation Statistics", |ui| self.activation_stats_panel(ui));
+            });
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // Simplify the layout to ensure plots are visible
             ui.vertical(|ui| {
                 let available_height = ui.available_height();
+                let task = self.training_params.lock().unwrap().task;
+                let show_gradient_norm = self.track_gradient_norm || !self.gradient_norms.is_empty();
+                let tabs: Vec<PlotTab> = [
+                    (PlotTab::Accuracy, true),
+                    (PlotTab::Loss, true),
+                    (PlotTab::GradientNorm, show_gradient_norm),
+                    (PlotTab::Variance, self.variance.is_some()),
+                    (PlotTab::LrRange, self.lr_range.is_some()),
+                ]
+                .into_iter()
+                .filter_map(|(tab, shown)| shown.then_some(tab))
+                .collect();
+                let n_plots = tabs.len();
+                let mut plot_height = available_height * if n_plots > 2 { 0.78 } else { 0.8 } / n_plots as f32;
+                
+                // Too short to stack the plots readably: show one at a time with the full height
+                let tabbed = plot_height < MIN_PLOT_HEIGHT;
+                if tabbed {
+                    if !tabs.contains(&self.plot_tab) {
+                        self.plot_tab = PlotTab::Accuracy;
+                    }
+                    ui.horizontal(|ui| {
+                        for &tab in &tabs {
+                            ui.selectable_value(&mut self.plot_tab, tab, tab.label(task));
+                        }
+                        ui.separator();
+                        self.reset_view_button(ui);
+                    });
+                    // Leave room for the tab row and the plot heading
+                    plot_height = (ui.available_height() - 50.0).max(40.0);
+                }
+                let shown = |tab: PlotTab| !tabbed || self.plot_tab == tab;
+                let show_accuracy = shown(PlotTab::Accuracy);
+                let show_loss = shown(PlotTab::Loss);
+                let show_gradient_norm = show_gradient_norm && shown(PlotTab::GradientNorm);
+                let show_variance = shown(PlotTab::Variance);
+                let show_lr_range = shown(PlotTab::LrRange);
                 
-                // Accuracy plot with green line
-                ui.heading("Accuracy (%)");
-                Plot::new("accuracy_plot")
-                    .height(available_height * 0.4)
-                    .show_axes(true)
-                    .allow_zoom(true)
-                    .allow_drag(true)
-                    .show(ui, |plot_ui| {
-                        if !self.accuracies.is_empty() {
-                            // Convert accuracies to points
-                            let points: PlotPoints = self.accuracies
-                                .iter()
-                                .enumerate()
-                                .map(|(i, &acc)| [i as f64, acc])
-                                .collect();
+                let reset_view = std::mem::take(&mut self.reset_plot_view);
+                
+                // Accuracy plot
+                if show_accuracy {
+                    ui.horizontal(|ui| {
+                        ui.heading(format!("Validation {}", task.score_name()));
+                        if !tabbed {
+                            self.reset_view_button(ui);
+                        }
+                    });
+                    let mut accuracy_plot = Plot::new("accuracy_plot");
+                    if reset_view {
+                        accuracy_plot = accuracy_plot.reset();
+                    }
+                    let response = accuracy_plot
+                        .height(plot_height)
+                        .x_axis_label(self.plot_x_axis.label())
+                        .show_axes(true)
+                        .allow_zoom(true)
+                        .allow_drag(true)
+                        .show(ui, |plot_ui| {
+                            if !self.accuracies.is_empty() {
+                                let points = self.plot_x_axis.plot_points(&self.accuracies);
                             
-                            // Create a line from the points with green color
-                            let line = Line::new(points)
-                                .name("Accuracy (%)")
-                                .width(2.0)
-                                .color(egui::Color32::from_rgb(50, 205, 50)); // Green
+                                // Create a line from the points in the palette's accuracy color
+                                let line = Line::new(points)
+                                    .name(task.score_name())
+                                    .width(2.0)
+                                    .color(self.accuracy_color);
                             
-                            // Add the line to the plot
-                            plot_ui.line(line);
+                                // Add the line to the plot
+                                plot_ui.line(line);
+                                dropped_markers(plot_ui, &self.accuracies, self.plot_x_axis, 0.0);
                             
-                            // Set the plot bounds
-                            let max_y = self.accuracies.iter().fold(0.0f64, |a, &b| a.max(b)).max(1.0);
-                            plot_ui.set_plot_bounds(egui_plot::PlotBounds::from_min_max(
-                                [0.0, 0.0],
-                                [self.accuracies.len() as f64, max_y * 1.1],
-                            ));
-                        } else {
-                            // If no data yet, show a message in the plot area
-                            plot_ui.text(
-                                egui_plot::Text::new(
-                                    egui_plot::PlotPoint::new(0.5, 0.5),
-                                    "Accuracy data will appear here"
-                                )
-                            );
-                        }
-                    });
+                                // Fit the plot to the data unless the user has zoomed in
+                                if !self.plot_view_pinned {
+                                    // R² can be negative early on
+                                    let max_y = match task {
+                                        TaskType::BinaryClassification => self.accuracies.max_value().max(1.0) * 1.1,
+                                        TaskType::Regression => 1.05,
+                                    };
+                                    plot_ui.set_plot_bounds(egui_plot::PlotBounds::from_min_max(
+                                        [0.0, self.accuracies.min_value() * 1.1],
+                                        [self.plot_x_axis.extent(&self.accuracies), max_y],
+                                    ));
+                                }
+                            } else {
+                                // If no data yet, show a message in the plot area
+                                plot_ui.text(
+                                    egui_plot::Text::new(
+                                        egui_plot::PlotPoint::new(0.5, 0.5),
+                                        format!("{} data will appear here", task.score_name())
+                                    )
+                                );
+                            }
+                        });
+                    self.note_plot_interaction(ui, &response.response);
+                }
                 
-                ui.add_space(10.0); // Add some space between plots
-                
-                // Loss plot with red line
-                ui.heading("Loss");
-                Plot::new("loss_plot")
-                    .height(available_height * 0.4)
-                    .show_axes(true)
-                    .allow_zoom(true)
-                    .allow_drag(true)
-                    .show(ui, |plot_ui| {
-                        if !self.losses.is_empty() {
-                            // Convert losses to points
-                            let points: PlotPoints = self.losses
-                                .iter()
-                                .enumerate()
-                                .map(|(i, &loss)| [i as f64, loss])
-                                .collect();
+                // Loss plot
+                if show_loss {
+                    if show_accuracy {
+                        ui.add_space(10.0); // Add some space between plots
+                    }
+                    ui.heading("Validation Loss");
+                    let mut loss_plot = Plot::new("loss_plot");
+                    if reset_view {
+                        loss_plot = loss_plot.reset();
+                    }
+                    let response = loss_plot
+                        .height(plot_height)
+                        .x_axis_label(self.plot_x_axis.label())
+                        .show_axes(true)
+                        .allow_zoom(true)
+                        .allow_drag(true)
+                        .show(ui, |plot_ui| {
+                            if !self.losses.is_empty() {
+                                let points = self.plot_x_axis.plot_points(&self.losses);
                             
-                            // Create a line from the points with red color
-                            let line = Line::new(points)
-                                .name("Loss")
-                                .width(2.0)
-                                .color(egui::Color32::from_rgb(220, 50, 50)); // Red
+                                // Create a line from the points in the palette's loss color
+                                let line = Line::new(points)
+                                    .name("Loss")
+                                    .width(2.0)
+                                    .color(self.loss_color);
                             
-                            // Add the line to the plot
-                            plot_ui.line(line);
+                                // Add the line to the plot
+                                plot_ui.line(line);
+                                dropped_markers(plot_ui, &self.losses, self.plot_x_axis, 0.0);
                             
-                            // Set the plot bounds
-                            let max_y = self.losses.iter().fold(0.0f64, |a, &b| a.max(b)).max(0.1);
-                            plot_ui.set_plot_bounds(egui_plot::PlotBounds::from_min_max(
-                                [0.0, 0.0],
-                                [self.losses.len() as f64, max_y * 1.1],
-                            ));
-                        } else {
-                            // If no data yet, show a message in the plot area
-                            plot_ui.text(
-                                egui_plot::Text::new(
-                                    egui_plot::PlotPoint::new(0.5, 0.5),
-                                    "Loss data will appear here"
-                                )
+                                // Fit the plot to the data unless the user has zoomed in
+                                if !self.plot_view_pinned {
+                                    let max_y = self.losses.max_value().max(0.1);
+                                    plot_ui.set_plot_bounds(egui_plot::PlotBounds::from_min_max(
+                                        [0.0, 0.0],
+                                        [self.plot_x_axis.extent(&self.losses), max_y * 1.1],
+                                    ));
+                                }
+                            } else {
+                                // If no data yet, show a message in the plot area
+                                plot_ui.text(
+                                    egui_plot::Text::new(
+                                        egui_plot::PlotPoint::new(0.5, 0.5),
+                                        "Loss data will appear here"
+                                    )
+                                );
+                            }
+                        });
+                    self.note_plot_interaction(ui, &response.response);
+                }
+                
+                if show_gradient_norm {
+                    ui.add_space(10.0);
+                    ui.heading("Gradient Norm");
+                    let mut gradient_norm_plot = Plot::new("gradient_norm_plot");
+                    if reset_view {
+                        gradient_norm_plot = gradient_norm_plot.reset();
+                    }
+                    let response = gradient_norm_plot
+                        .height(plot_height)
+                        .x_axis_label(self.plot_x_axis.label())
+                        .show_axes(true)
+                        .allow_zoom(true)
+                        .allow_drag(true)
+                        .include_y(0.0)
+                        .show(ui, |plot_ui| {
+                            let points = self.plot_x_axis.plot_points(&self.gradient_norms);
+                            plot_ui.line(
+                                Line::new(points)
+                                    .name("Gradient norm")
+                                    .width(2.0)
+                                    .color(egui::Color32::from_rgb(255, 140, 0)), // Dark orange
                             );
-                        }
-                    });
+                            dropped_markers(plot_ui, &self.gradient_norms, self.plot_x_axis, 0.0);
+                        });
+                    self.note_plot_interaction(ui, &response.response);
+                }
+                
+                let variance_response = self.variance.as_ref().filter(|_| show_variance).map(|variance| {
+                    ui.add_space(10.0);
+                    ui.heading(format!("Accuracy Across {} Seeds (%)", variance.seeds.len()));
+                    self.variance_plot(ui, variance, plot_height, reset_view)
+                });
+                if let Some(response) = variance_response {
+                    self.note_plot_interaction(ui, &response);
+                }
+                
+                let lr_range_response = self.lr_range.as_ref().filter(|_| show_lr_range).map(|range| {
+                    ui.add_space(10.0);
+                    ui.heading("Loss vs Learning Rate");
+                    self.lr_range_plot(ui, range, plot_height, reset_view)
+                });
+                if let Some(response) = lr_range_response {
+                    self.note_plot_interaction(ui, &response);
+                }
             });
         });
         
-        // Request continuous repainting while training
-        ctx.request_repaint();
+        // Repaint every frame only while updates stream in; when idle, input
+        // wakes the window by itself and a slow timer picks up log lines and
+        // the backend heartbeat
+        let streaming = self.is_training
+            || self.busy.is_busy()
+            || self.variance_receiver.is_some()
+            || self.lr_range_receiver.is_some()
+            || self.what_if_changed_at.is_some();
+        if streaming {
+            ctx.request_repaint();
+        } else {
+            ctx.request_repaint_after(self.settings.idle_refresh);
+        }
+    }
+
+    // Remember the last started parameters and the data for the next session
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let session = LastSession {
+            params: self.training_params.lock().unwrap().clone(),
+            data_path: Some(self.data_path.clone()),
+        };
+        if let Err(e) = session.save() {
+            log::warn!("Failed to save the session: {}", e);
+        }
     }
 } 
\ No newline at end of file
@@ -0,0 +1,107 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crate::neural_network::{EpochState, TrainingCallback};
+
+/// Training callback appending one JSON object per epoch to `writer`, e.g.
+/// `{"epoch":3,"accuracy":71.5,"loss":0.5412,"val_loss":null,"val_accuracy":null}`,
+/// for external dashboards to tail. Lines are flushed as they are written.
+/// `accuracy` and `loss` are the training metrics; the validation ones, and
+/// non-finite values, are `null` when not measured.
+///
+/// A write error does not interrupt training: later epochs are skipped and
+/// the error is returned by [`finish`](Self::finish).
+pub struct JsonlMetrics<W: Write> {
+    writer: W,
+    run: Option<String>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonlMetrics<W> {
+    pub fn new(writer: W) -> Self {
+        JsonlMetrics { writer, run: None, error: None }
+    }
+
+    /// Labels every line with a leading `"run"` field, to tell several runs
+    /// written to the same stream apart.
+    ///
+    /// ```
+    /// use water_potability_nn::jsonl::JsonlMetrics;
+    /// use water_potability_nn::neural_network::{EpochState, TrainingCallback};
+    ///
+    /// let mut metrics = JsonlMetrics::new(Vec::new()).with_run("lr=0.1");
+    /// let state = EpochState {
+    ///     epoch: 0, epochs: 10, accuracy: 50.0, loss: 0.69, learning_rate: 0.1,
+    ///     validation_loss: Some(0.7), validation_accuracy: Some(45.0),
+    /// };
+    /// metrics.on_epoch_end(&state);
+    /// let written = String::from_utf8(metrics.finish().unwrap()).unwrap();
+    /// assert_eq!(
+    ///     written,
+    ///     "{\"run\":\"lr=0.1\",\"epoch\":0,\"accuracy\":50,\"loss\":0.69,\"val_loss\":0.7,\"val_accuracy\":45}\n"
+    /// );
+    /// ```
+    pub fn with_run(self, run: impl Into<String>) -> Self {
+        JsonlMetrics { run: Some(run.into()), ..self }
+    }
+
+    /// Flushes the stream and returns it, or the first write error.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush().map(|()| self.writer),
+        }
+    }
+
+    fn write_line(&mut self, state: &EpochState) -> io::Result<()> {
+        let mut line = String::from("{");
+        if let Some(run) = &self.run {
+            let _ = write!(line, "\"run\":{},", json_string(run));
+        }
+        let _ = write!(
+            line,
+            "\"epoch\":{},\"accuracy\":{},\"loss\":{},\"val_loss\":{},\"val_accuracy\":{}}}",
+            state.epoch,
+            json_number(Some(state.accuracy)),
+            json_number(Some(state.loss)),
+            json_number(state.validation_loss),
+            json_number(state.validation_accuracy)
+        );
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> TrainingCallback for JsonlMetrics<W> {
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        if self.error.is_none()
+            && let Err(e) = self.write_line(state)
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+// JSON has no NaN or infinity
+fn json_number(value: Option<f64>) -> String {
+    match value {
+        Some(value) if value.is_finite() => value.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
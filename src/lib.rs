@@ -0,0 +1,54 @@
+//! Neural network core of the water potability analyzer, usable without the
+//! egui or Qt frontends.
+//!
+//! ```
+//! use ndarray::array;
+//! use water_potability_nn::data_loader::Dataset;
+//! use water_potability_nn::neural_network::{
+//!     make_prediction, train_network, Activation, Init, NetworkBuilder, TrainingParams,
+//! };
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! // Logical OR: potable when either input is set
+//! let dataset = Dataset {
+//!     features: array![[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]],
+//!     labels: array![[0.0], [1.0], [1.0], [1.0]],
+//!     weights: None,
+//! };
+//!
+//! let mut network = NetworkBuilder::new(2)
+//!     .hidden_layers(&[8])
+//!     .activation(Activation::Tanh)
+//!     .init(Init::Xavier)
+//!     .seed(7)
+//!     .build()?;
+//!
+//! let params = TrainingParams { epochs: 500, ..TrainingParams::default() };
+//! train_network(&mut network, &dataset, &params, |_epoch, _accuracy, _loss| {})?;
+//!
+//! assert!(make_prediction(&network, &[1.0, 0.0])?.is_potable);
+//! assert!(!make_prediction(&network, &[0.0, 0.0])?.is_potable);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod cross_validation;
+pub mod data_loader;
+pub mod error;
+pub mod jsonl;
+pub mod live_metrics;
+pub mod logging;
+pub mod lr_finder;
+pub mod metric_series;
+pub mod metrics;
+pub mod neural_network;
+pub mod pause;
+pub mod progress;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod snapshot;
+#[cfg(feature = "tensorboard")]
+pub mod tensorboard;
+pub mod variance;
+
+pub use error::NnError;
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::error::NnError;
+use crate::neural_network::{EpochState, StopReason, TrainingCallback, TrainingOutcome};
+use crate::snapshot::Snapshot;
+
+/// Where a run watched by [`SharedMetrics`] stands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrainingStatus {
+    /// At least one epoch finished and training goes on.
+    Running,
+    /// Training returned normally.
+    Finished(StopReason),
+    /// Training ended with an error, such as a diverged loss.
+    Failed(String),
+}
+
+/// Latest metrics of a run, as read from [`SharedMetrics::latest`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiveMetrics {
+    pub status: TrainingStatus,
+    /// The last finished epoch, `None` when training failed before any. A
+    /// [`SharedMetrics`] reused across runs keeps the previous run's until then.
+    pub state: Option<EpochState>,
+}
+
+/// Training callback publishing the current epoch's metrics for other
+/// threads to poll, for library users who prefer pulling the state of a run
+/// to pushing it through a callback of their own.
+///
+/// Clones share the same metrics: hand one to the training function and keep
+/// another to read. Reading clones an `Arc` under a short read lock, so it is
+/// cheap and never waits on an epoch in progress; training only holds the
+/// lock to swap in the new metrics once per epoch.
+///
+/// ```
+/// use ndarray::array;
+/// use water_potability_nn::data_loader::Dataset;
+/// use water_potability_nn::live_metrics::{SharedMetrics, TrainingStatus};
+/// use water_potability_nn::neural_network::{train_network, NetworkBuilder, StopReason, TrainingParams};
+///
+/// let dataset = Dataset { features: array![[0.0], [1.0]], labels: array![[0.0], [1.0]], weights: None };
+/// let mut network = NetworkBuilder::new(1).hidden_layers(&[4]).seed(1).build().unwrap();
+/// let params = TrainingParams { epochs: 20, ..TrainingParams::default() };
+///
+/// let live = SharedMetrics::new();
+/// assert!(live.latest().is_none());
+/// train_network(&mut network, &dataset, &params, live.clone()).unwrap();
+///
+/// let metrics = live.latest().unwrap();
+/// assert_eq!(metrics.status, TrainingStatus::Finished(StopReason::Completed));
+/// assert_eq!(metrics.state.as_ref().map(|state| state.epoch), Some(19));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SharedMetrics {
+    latest: Arc<Snapshot<LiveMetrics>>,
+}
+
+impl SharedMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Metrics after the most recent epoch, `None` until the first one ends.
+    pub fn latest(&self) -> Option<Arc<LiveMetrics>> {
+        self.latest.latest()
+    }
+}
+
+impl TrainingCallback for SharedMetrics {
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        self.latest.publish(LiveMetrics { status: TrainingStatus::Running, state: Some(state.clone()) });
+    }
+
+    fn on_train_end(&mut self, result: Result<&TrainingOutcome, &NnError>) {
+        let status = match result {
+            Ok(outcome) => TrainingStatus::Finished(outcome.stopped_reason),
+            Err(e) => TrainingStatus::Failed(e.to_string()),
+        };
+        let state = self.latest().and_then(|metrics| metrics.state.clone());
+        self.latest.publish(LiveMetrics { status, state });
+    }
+}
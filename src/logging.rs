@@ -0,0 +1,108 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Environment variable with the most verbose level logged: `off`, `error`,
+/// `warn`, `info` (the default), `debug` or `trace`.
+pub const LOG_LEVEL_VAR: &str = "WPNN_LOG";
+/// Environment variable naming a file to append the log to instead of stdout.
+pub const LOG_FILE_VAR: &str = "WPNN_LOG_FILE";
+
+// Records from other crates (egui, winit, ...) are only shown from this level up
+const DEPENDENCY_LEVEL: LevelFilter = LevelFilter::Warn;
+
+/// Minimal `log` subscriber for the diagnostics of the training thread and
+/// the UI. Every record is written and flushed at once, so nothing is lost in
+/// a buffer when the process ends or its stdout is hidden.
+///
+/// Plain output writes `info` records as the bare message and prefixes the
+/// other levels with theirs. Structured output, used for log files, starts
+/// every line with a timestamp, the level and the module it came from.
+///
+/// ```
+/// use log::{Level, LevelFilter, Log, Record};
+/// use water_potability_nn::logging::Logger;
+///
+/// let logger = Logger::new(LevelFilter::Info, Box::new(std::io::sink()));
+/// let debug = Record::builder().level(Level::Debug).target("water_potability_nn").build();
+/// assert!(!logger.enabled(debug.metadata()));
+/// ```
+pub struct Logger {
+    level: LevelFilter,
+    structured: bool,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    /// Plain logger writing records up to `level` to `output`.
+    pub fn new(level: LevelFilter, output: Box<dyn Write + Send>) -> Self {
+        Logger { level, structured: false, output: Mutex::new(output) }
+    }
+
+    /// Starts every line with a timestamp, the level and the module.
+    pub fn structured(mut self) -> Self {
+        self.structured = true;
+        self
+    }
+
+    /// Logger configured by [`LOG_LEVEL_VAR`] and [`LOG_FILE_VAR`]: plain on
+    /// stdout, or structured and appended to the file.
+    pub fn from_env() -> Result<Self, String> {
+        let level = match std::env::var(LOG_LEVEL_VAR) {
+            Ok(text) => LevelFilter::from_str(text.trim())
+                .map_err(|_| format!("Invalid {} '{}', expected off, error, warn, info, debug or trace", LOG_LEVEL_VAR, text))?,
+            Err(_) => LevelFilter::Info,
+        };
+        match std::env::var(LOG_FILE_VAR) {
+            Ok(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|e| format!("Cannot open log file {}: {}", path, e))?;
+                Ok(Logger::new(level, Box::new(file)).structured())
+            }
+            Err(_) => Ok(Logger::new(level, Box::new(io::stdout()))),
+        }
+    }
+
+    /// Makes this the global logger. Fails if one is already installed.
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let own = metadata.target().split("::").next() == Some(env!("CARGO_CRATE_NAME"));
+        let level = if own { self.level } else { self.level.min(DEPENDENCY_LEVEL) };
+        metadata.level() <= level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+        // A broken log sink must not take the logging thread down with it
+        let _ = if self.structured {
+            let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+            writeln!(output, "{} {:<5} {}: {}", time, record.level(), record.target(), record.args())
+        } else if record.level() == log::Level::Info {
+            writeln!(output, "{}", record.args())
+        } else {
+            writeln!(output, "{}: {}", record.level(), record.args())
+        };
+        let _ = output.flush();
+    }
+
+    fn flush(&self) {
+        let _ = self.output.lock().unwrap_or_else(PoisonError::into_inner).flush();
+    }
+}
@@ -0,0 +1,115 @@
+use crate::data_loader::{DataSource, Dataset, Scaler};
+use crate::error::NnError;
+use crate::neural_network::{create_network, weight_penalty, TrainingParams};
+
+// Weight of the previous value in the exponentially smoothed loss
+const SMOOTHING: f64 = 0.98;
+// The test stops once the smoothed loss exceeds this multiple of its minimum
+const DIVERGENCE_FACTOR: f64 = 4.0;
+
+/// Learning rates swept by [`lr_range_test`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LrRangeTest {
+    pub min_lr: f64,
+    pub max_lr: f64,
+    /// Number of batches trained, one learning rate each.
+    pub steps: usize,
+}
+
+impl Default for LrRangeTest {
+    fn default() -> Self {
+        LrRangeTest { min_lr: 1e-5, max_lr: 10.0, steps: 100 }
+    }
+}
+
+/// Results of [`lr_range_test`].
+#[derive(Clone, Debug)]
+pub struct LrRange {
+    /// `(learning rate, smoothed loss)` per step, up to where the loss diverged.
+    pub points: Vec<(f64, f64)>,
+    /// Learning rate where the loss fell fastest, if it fell at all.
+    pub suggested: Option<f64>,
+}
+
+/// Learning-rate range test: trains a fresh network built from `params` on
+/// `dataset` (raw features) for `test.steps` batches, raising the learning
+/// rate exponentially from `min_lr` to `max_lr`, and records the loss at each
+/// rate. The loss is smoothed exponentially and the test stops early once it
+/// blows up. The suggested rate is the one where the smoothed loss falls most
+/// steeply against the log of the rate.
+///
+/// Nothing outside the function is trained, so it is safe to run next to a
+/// training run.
+pub fn lr_range_test(dataset: &Dataset, params: &TrainingParams, test: &LrRangeTest) -> Result<LrRange, NnError> {
+    if !(test.min_lr > 0.0 && test.max_lr > test.min_lr && test.max_lr.is_finite()) {
+        return Err(NnError::Config(format!(
+            "Learning rate range must satisfy 0 < min < max, got {} to {}", test.min_lr, test.max_lr
+        )));
+    }
+    if test.steps < 2 {
+        return Err(NnError::Config(format!("Need at least 2 steps to sweep learning rates, got {}", test.steps)));
+    }
+    params.check_data(dataset)?;
+
+    let scaler = Scaler::fit(&dataset.features, params.scale_mode);
+    let scaled = Dataset { features: scaler.transform(&dataset.features), labels: dataset.labels.clone(), weights: dataset.weights.clone() };
+    let mut network = create_network(params)?;
+    network.check_input_dim(scaled.n_features())?;
+    let shuffle_seed = params.seed.unwrap_or_else(rand::random);
+    let growth = (test.max_lr / test.min_lr).powf(1.0 / (test.steps - 1) as f64);
+
+    let mut points = Vec::with_capacity(test.steps);
+    let (mut average, mut best) = (0.0, f64::INFINITY);
+    let mut pass = 0;
+    'sweep: loop {
+        let batches = scaled.batches(params.batch_size, params.shuffle_batches, params.batch_order_seed(shuffle_seed, pass));
+        for batch in batches {
+            let step = points.len();
+            if step == test.steps {
+                break 'sweep;
+            }
+            let learning_rate = test.min_lr * growth.powi(step as i32);
+            let (z_values, activations, y_pred) = network.forward(&batch.features);
+            let loss = params.loss.weighted_loss(&y_pred, &batch.labels, batch.weights.as_ref()) + weight_penalty(&network, params);
+
+            // Bias-corrected exponential moving average, so early steps are not pulled toward 0
+            average = SMOOTHING * average + (1.0 - SMOOTHING) * loss;
+            let smoothed = average / (1.0 - SMOOTHING.powi(step as i32 + 1));
+            if !smoothed.is_finite() || smoothed > DIVERGENCE_FACTOR * best {
+                break 'sweep;
+            }
+            best = best.min(smoothed);
+            points.push((learning_rate, smoothed));
+
+            let (dw, db) = network.gradients(&batch.features, &batch.labels, batch.weights.as_ref(), &z_values, &activations, &y_pred, params);
+            network.apply_gradients(&dw, &db, learning_rate, &params.frozen_layers);
+        }
+        pass += 1;
+    }
+
+    let suggested = steepest_descent(&points);
+    Ok(LrRange { points, suggested })
+}
+
+/// Learning rate at the start of the steepest drop of `(learning rate, loss)`
+/// points, measured per decade of learning rate. `None` when the loss never
+/// decreases.
+///
+/// ```
+/// use water_potability_nn::lr_finder::steepest_descent;
+///
+/// let points = [(0.001, 0.7), (0.01, 0.69), (0.1, 0.5), (1.0, 0.45), (10.0, 2.0)];
+/// assert_eq!(steepest_descent(&points), Some(0.01));
+/// assert_eq!(steepest_descent(&[(0.1, 0.5), (1.0, 0.6)]), None);
+/// ```
+pub fn steepest_descent(points: &[(f64, f64)]) -> Option<f64> {
+    points
+        .windows(2)
+        .map(|pair| {
+            let ((lr_a, loss_a), (lr_b, loss_b)) = (pair[0], pair[1]);
+            (lr_a, (loss_b - loss_a) / (lr_b / lr_a).log10())
+        })
+        .filter(|&(_, slope)| slope < 0.0)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(lr, _)| lr)
+}
@@ -1,311 +1,421 @@
-use ndarray::{Array2, Axis};
-use ndarray_rand::RandomExt;
-use rand_distr::StandardNormal;
-use rand::thread_rng;
-use rand::seq::SliceRandom;
-use csv::ReaderBuilder;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::error::Error;
+use std::path::Path;
 use std::thread;
+use std::time::Duration;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Sender;
+mod cli;
 mod frontend_qt;
-use frontend_qt::{TrainingWindow, TrainingParams};
+mod notify;
+mod recording;
+mod settings;
+use frontend_qt::{BackendHandles, BestEpoch, BUSY_MESSAGE, DataSummary, RunFinished, SharedModel, TrainedModel, TrainingWindow};
+use recording::{Recorder, Recording};
+use settings::{LastSession, Settings};
+use water_potability_nn::data_loader::{self, Balance, Dataset, Scaler};
+use water_potability_nn::logging::Logger;
+use water_potability_nn::metrics;
+use water_potability_nn::neural_network::{self, DivergencePolicy, StopReason, TaskType, TrainingCallback, TrainingEvent, TrainingParams, ValidationMetrics, WithBaseline};
+use water_potability_nn::snapshot::Snapshot;
+use water_potability_nn::NnError;
 
-// These are default values, will be overridden by GUI inputs
-const DEFAULT_EPOCHS: usize = 2000;
-const DEFAULT_LR: f64 = 0.5;
-const DEFAULT_HIDDEN: usize = 32;
-const DEFAULT_HIDDEN_LAYERS: usize = 2;
 const LOG_INTERVAL: usize = 100;
+// Share of the training data held out to fit probability calibration on
+const CALIBRATION_FRACTION: f64 = 0.2;
+// How often a paused run wakes up to send a heartbeat
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const DATA_PATH: &str = "src/water_potability.csv";
 
-fn shuffle_data(x: &Array2<f64>, y: &Array2<f64>) -> (Array2<f64>, Array2<f64>) {
-    let mut indices: Vec<usize> = (0..x.nrows()).collect();
-    let mut rng = thread_rng();
-    indices.shuffle(&mut rng);
-
-    let x_shuffled = Array2::from_shape_fn(x.raw_dim(), |(i, j)| x[(indices[i], j)]);
-    let y_shuffled = Array2::from_shape_fn(y.raw_dim(), |(i, j)| y[(indices[i], j)]);
-    (x_shuffled, y_shuffled)
-}
-
-fn relu(x: &Array2<f64>) -> Array2<f64> {
-    x.mapv(|v| v.max(0.0))
-}
-
-fn relu_deriv(x: &Array2<f64>) -> Array2<f64> {
-    x.mapv(|v| if v > 0.0 { 1.0 } else { 0.0 })
-}
-
-fn sigmoid(x: &Array2<f64>) -> Array2<f64> {
-    x.mapv(|v| 1.0 / (1.0 + (-v).exp()))
-}
-
-fn normalize(mut data: Array2<f64>) -> Array2<f64> {
-    for mut col in data.columns_mut() {
-        let mean = col.mean().unwrap();
-        let std = col.mapv(|x| (x - mean).powi(2)).mean().unwrap().sqrt();
-        col -= mean;
-        col /= std.max(1e-8);
-    }
-    data
-}
-
-fn load_data(path: &str) -> Result<(Array2<f64>, Array2<f64>), Box<dyn Error + Send + Sync>> {
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_path(path)?;
-
-    let mut features: Vec<Vec<f64>> = Vec::new();
-    let mut labels: Vec<f64> = Vec::new();
-
-    for result in rdr.records() {
-        let record = result?;
-        let vals: Result<Vec<f64>, _> = record.iter().map(|s| s.trim().parse::<f64>()).collect();
-        if let Ok(vals) = vals {
-            if vals.len() == 10 {
-                let (x, y) = vals.split_at(9); // 9 fitur, 1 label
-                features.push(x.to_vec());
-                labels.push(y[0]);
-            }
-        }
-    }
-
-    let feature_array = Array2::from_shape_vec((features.len(), 9), features.concat())?;
-    let label_array = Array2::from_shape_vec((labels.len(), 1), labels)?;
-
-    Ok((feature_array, label_array))
-}
-
-fn train_network(
-    x: &Array2<f64>, 
-    y_true: &Array2<f64>, 
-    params: &TrainingParams,
-    sender: &Sender<(f64, f64)>
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let (n_samples, n_features) = x.dim();
-    let mut rng = thread_rng();
-    
-    // Guard against bad parameters
-    if params.hidden_layers == 0 {
-        return Err("Number of hidden layers must be at least 1".into());
-    }
-    
-    if params.neurons_per_layer == 0 {
-        return Err("Number of neurons per layer must be at least 1".into());
-    }
-    
-    // Initialize weights and biases for variable number of layers
-    let mut weights = Vec::new();
-    let mut biases = Vec::new();
-    
-    println!("Initializing network with {} features, {} hidden layers, {} neurons per layer", 
-             n_features, params.hidden_layers, params.neurons_per_layer);
-    
-    // Input layer -> first hidden layer
-    weights.push(Array2::random_using((n_features, params.neurons_per_layer), StandardNormal, &mut rng) * 0.1);
-    biases.push(Array2::zeros((1, params.neurons_per_layer)));
-    
-    // Hidden layers
-    for _ in 1..params.hidden_layers {
-        weights.push(Array2::random_using(
-            (params.neurons_per_layer, params.neurons_per_layer), 
-            StandardNormal, 
-            &mut rng
-        ) * 0.1);
-        biases.push(Array2::zeros((1, params.neurons_per_layer)));
-    }
-    
-    // Output layer
-    weights.push(Array2::random_using((params.neurons_per_layer, 1), StandardNormal, &mut rng) * 0.1);
-    biases.push(Array2::zeros((1, 1)));
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Diagnostics go through `log`; WPNN_LOG and WPNN_LOG_FILE pick the level and destination
+    let logger = Logger::from_env().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    logger.install()?;
     
-    // Training loop
-    for epoch in 0..params.epochs {
-        // Forward pass
-        let mut activations = Vec::new();
-        let mut z_values = Vec::new();
-        
-        // Input layer -> first hidden layer
-        let mut z = x.dot(&weights[0]) + &biases[0];
-        z_values.push(z.clone());
-        let mut a = relu(&z);
-        activations.push(a.clone());
-        
-        // Hidden layers
-        for i in 1..params.hidden_layers {
-            z = a.dot(&weights[i]) + &biases[i];
-            z_values.push(z.clone());
-            a = relu(&z);
-            activations.push(a.clone());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli::Args { command, metrics_jsonl, record, workers, csv_format } = cli::parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let replay = match command {
+        cli::Command::Gui => None,
+        cli::Command::Replay { path, epochs_per_second } => {
+            let recording = Recording::load(&path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            Some((recording, epochs_per_second))
         }
-        
-        // Output layer
-        z = a.dot(&weights[params.hidden_layers]) + &biases[params.hidden_layers];
-        z_values.push(z.clone());
-        let y_pred = sigmoid(&z);
-        
-        // Calculate binary cross-entropy loss
-        let epsilon = 1e-15; // To prevent log(0)
-        let y_pred_clipped = y_pred.mapv(|v| v.max(epsilon).min(1.0 - epsilon));
-        let loss_per_sample = -y_true * &y_pred_clipped.mapv(|v| v.ln()) - 
-                             (1.0 - y_true) * &(1.0 - &y_pred_clipped).mapv(|v| v.ln());
-        
-        let loss = loss_per_sample.sum() / n_samples as f64;
-        
-        // Backpropagation
-        // Initialize gradients for all layers
-        let mut dw = Vec::with_capacity(params.hidden_layers + 1);
-        let mut db = Vec::with_capacity(params.hidden_layers + 1);
-        
-        // For all layers, initialize with appropriate dimensions
-        for i in 0..=params.hidden_layers {
-            dw.push(Array2::zeros(weights[i].dim()));
-            db.push(Array2::zeros(biases[i].dim()));
+        cli::Command::LrSweep { rates, epochs, seed } => {
+            return cli::run_lr_sweep(DATA_PATH, &rates, epochs, seed, metrics_jsonl.as_deref(), csv_format);
         }
-        
-        // Output layer error
-        let mut delta = &y_pred - y_true;
-        
-        // Output layer gradients
-        dw[params.hidden_layers] = activations.last().unwrap().t().dot(&delta) / n_samples as f64;
-        let db_out = delta.sum_axis(Axis(0)) / n_samples as f64;
-        db[params.hidden_layers] = db_out.into_shape(biases[params.hidden_layers].raw_dim()).unwrap();
-        
-        // Hidden layers gradients
-        for i in (0..params.hidden_layers).rev() {
-            delta = delta.dot(&weights[i+1].t()) * relu_deriv(&z_values[i]);
-            
-            let input = if i == 0 {
-                x
-            } else {
-                &activations[i-1]
-            };
-            
-            dw[i] = input.t().dot(&delta) / n_samples as f64;
-            let db_layer = delta.sum_axis(Axis(0)) / n_samples as f64;
-            db[i] = db_layer.into_shape(biases[i].raw_dim()).unwrap();
+        cli::Command::CrossValidate { folds, epochs, seed } => {
+            let workers = settings::worker_count(workers.or(Settings::load().workers));
+            return cli::run_cross_validation(DATA_PATH, folds, epochs, seed, workers, csv_format);
         }
-        
-        // Update weights and biases with learning rate
-        let lr = if epoch < params.epochs / 10 {
-            params.learning_rate  // High learning rate for first 10%
-        } else if epoch < params.epochs / 2 {
-            params.learning_rate * 0.5  // Medium learning rate until halfway
-        } else {
-            params.learning_rate * 0.1  // Low learning rate for final half
-        };
-        
-        for i in 0..=params.hidden_layers {
-            weights[i] -= &(dw[i].clone() * lr);
-            biases[i] -= &(db[i].clone() * lr);
-        }
-        
-        // Calculate accuracy for monitoring
-        let pred_labels = y_pred.mapv(|v| if v >= 0.5 { 1.0 } else { 0.0 });
-        let correct = pred_labels
-            .iter()
-            .zip(y_true.iter())
-            .filter(|(p, y)| (*p - *y).abs() < 1e-6)
-            .count();
-        let accuracy = correct as f64 / n_samples as f64;
-        
-        // Convert accuracy to percentage
-        let accuracy_pct = accuracy * 100.0;
-        
-        if epoch % LOG_INTERVAL == 0 || epoch == params.epochs - 1 {
-            println!("Epoch {}/{}: Accuracy = {:.2}%, Loss = {:.4}", epoch, params.epochs, accuracy_pct, loss);
+        cli::Command::Check => return cli::run_check(DATA_PATH, csv_format),
+        cli::Command::Stats { path } => return cli::run_stats(&path, csv_format),
+        cli::Command::Serve { addr, model } => return cli::run_serve(&addr, &model),
+        cli::Command::Compare { model_a, model_b, data } => {
+            return cli::run_compare(&model_a, &model_b, data.as_deref().unwrap_or(DATA_PATH), csv_format);
         }
-        
-        // Send accuracy and loss update to the UI
-        sender.send((accuracy_pct, loss)).unwrap();
-    }
-
-    // Send a completion signal to the UI
-    println!("Training completed");
+    };
     
-    Ok(())
-}
-
-fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([800.0, 600.0]),
+            .with_inner_size([1000.0, 600.0]),
         ..Default::default()
     };
     
-    let (window, accuracy_sender, params_receiver) = TrainingWindow::new();
+    // Pick up where the last session left off, on its data if that is still there
+    let session = LastSession::load();
+    let data_path = session.data_path.filter(|path| Path::new(path).is_file()).unwrap_or_else(|| DATA_PATH.to_string());
+    
+    // A replay shows the recorded run's parameters
+    let params = replay.as_ref().map_or(session.params, |(recording, _)| recording.params.clone());
+    
+    let shared_model: SharedModel = Arc::new(Snapshot::new());
+    log::info!("Variance analysis: up to {} seeds at once", settings::worker_count(workers.or(Settings::load().workers)));
+    let (window, backend) = TrainingWindow::new(shared_model.clone(), params, data_path.clone(), workers, csv_format);
     
     let training_thread = thread::spawn(move || -> Result<(), Box<dyn Error + Send + Sync>> {
-        // Load and preprocess data
-        let (x_raw, y_true) = load_data("src/water_potability.csv")?;
-        let x = normalize(x_raw);
-        let (x, y_true) = shuffle_data(&x, &y_true);
+        if let Some((recording, epochs_per_second)) = replay {
+            recording::replay(recording, epochs_per_second, backend);
+            return Ok(());
+        }
         
-        // Initial training parameters
-        let current_params = Arc::new(Mutex::new(TrainingParams {
-            epochs: DEFAULT_EPOCHS,
-            hidden_layers: DEFAULT_HIDDEN_LAYERS,
-            neurons_per_layer: DEFAULT_HIDDEN,
-            learning_rate: DEFAULT_LR,
-            restart_training: false,
-        }));
+        let BackendHandles {
+            metrics: mut accuracy_sender,
+            params: params_receiver,
+            logger,
+            heartbeat,
+            activation_stats: activation_stats_sender,
+            summary: summary_sender,
+            data_summary: data_summary_sender,
+            gradient_norms: gradient_norm_sender,
+            seed: seed_sender,
+            pause,
+            busy,
+            finished: finished_sender,
+        } = backend;
         
-        let should_continue = true;
+        // Load and preprocess data; each run shuffles its own copy from its seed
+        let raw = data_loader::load_potability_data(&data_path, csv_format)?;
+        let feature_stats = data_loader::feature_stats(&raw);
+        if raw.weights.is_some() {
+            logger.log(format!("Weighting samples by the '{}' column; validation metrics stay unweighted", data_loader::WEIGHT_COLUMN));
+        }
+        
+        // Initial training parameters
+        let current_params = Arc::new(Mutex::new(TrainingParams::default()));
         
         // Main thread loop
-        while should_continue {
+        loop {
             // Wait for user to initiate/restart training
-            println!("Waiting for user to start/restart training...");
+            logger.log("Waiting for user to start/restart training...");
             
             // Wait for parameter updates from the UI
-            let train_params = loop {
+            let mut train_params = loop {
                 match params_receiver.recv_timeout(std::time::Duration::from_millis(100)) {
                     Ok(new_params) => {
                         // Update current parameters
                         let new_params_guard = new_params.lock().unwrap();
                         let mut current_guard = current_params.lock().unwrap();
+                        *current_guard = new_params_guard.clone();
                         
-                        current_guard.epochs = new_params_guard.epochs;
-                        current_guard.hidden_layers = new_params_guard.hidden_layers;
-                        current_guard.neurons_per_layer = new_params_guard.neurons_per_layer;
-                        current_guard.learning_rate = new_params_guard.learning_rate;
-                        current_guard.restart_training = new_params_guard.restart_training;
-                        
-                        println!("Received training parameters. Starting training...");
+                        logger.log("Received training parameters. Starting training...");
                         
                         // Create a copy for training
-                        let params = TrainingParams {
-                            epochs: current_guard.epochs,
-                            hidden_layers: current_guard.hidden_layers,
-                            neurons_per_layer: current_guard.neurons_per_layer,
-                            learning_rate: current_guard.learning_rate,
-                            restart_training: current_guard.restart_training,
-                        };
-                        
-                        break params;
+                        break current_guard.clone();
                     },
                     Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                         // Just a timeout, continue waiting
+                        heartbeat.beat();
                         continue;
                     },
                     Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
                         // Channel closed, application is terminating
-                        println!("Training thread shutting down while waiting to start");
+                        logger.log("Training thread shutting down while waiting to start");
                         return Ok(());
                     }
                 }
             };
             
             // Start training with received parameters
-            println!("Starting training with: {} epochs, {} hidden layers, {} neurons per layer, learning rate {}",
-                     train_params.epochs, train_params.hidden_layers, train_params.neurons_per_layer, train_params.learning_rate);
+            logger.log(format!("Starting training with: {} epochs, {} hidden layers, {} neurons per layer, learning rate {} ({} warmup epochs), scaling {:?}, balance {:?}, L1 {}, L2 {}, input noise {}, loss {:?}, early stop patience {} (min delta {})",
+                               train_params.epochs, train_params.hidden_layers, train_params.neurons_per_layer, train_params.learning_rate,
+                               train_params.warmup_epochs, train_params.scale_mode, train_params.balance, train_params.l1_lambda, train_params.l2_lambda,
+                               train_params.noise_std, train_params.loss, train_params.early_stopping_patience, train_params.min_delta));
+            
+            // Every random choice of the run derives from one seed, so it can be reproduced
+            let seed = train_params.resolve_seed();
+            logger.log(format!("Seed: {}", seed));
+            let _ = seed_sender.send(seed);
+            
+            // Duplicates go before splitting so no copy lands on both sides
+            let mut data = data_loader::shuffle_data_with(&raw, &mut StdRng::seed_from_u64(seed));
+            let duplicates_removed = train_params.dedup.then(|| data_loader::dedup(&mut data));
+            if let Some(removed) = duplicates_removed {
+                logger.log(format!("Removed {} duplicate rows, {} samples left", removed, data.n_samples()));
+            }
             
-            if let Err(e) = train_network(&x, &y_true, &train_params, &accuracy_sender) {
-                eprintln!("Error during training: {}", e);
+            let (fit_data, (calibration_data, validation_data, test_data)) = match split_held_out(&data, &train_params, seed) {
+                Ok(split) => split,
+                Err(e) => {
+                    logger.error(format!("Error during training: {}", e));
+                    busy.release();
+                    let _ = finished_sender.send(RunFinished::Failed(e.to_string()));
+                    continue;
+                }
+            };
+            
+            let held_out_sets: Vec<&Dataset> = calibration_data.iter().chain(validation_data.iter()).chain(test_data.iter()).collect();
+            let shared_with_held_out: usize = held_out_sets.iter().map(|held_out| data_loader::shared_rows(&fit_data, held_out)).sum();
+            if shared_with_held_out > 0 {
+                logger.log(format!(
+                    "Warning: {} held-out rows duplicate training rows, held-out scores will be optimistic",
+                    shared_with_held_out
+                ));
+            }
+            let _ = data_summary_sender.send(DataSummary {
+                samples: data.n_samples(),
+                duplicates_removed,
+                training: fit_data.n_samples(),
+                held_out: held_out_sets.iter().map(|held_out| held_out.n_samples()).sum(),
+                shared_with_held_out,
+            });
+            
+            // Fit the scaler on this run's mode; it travels with the trained network
+            let scaler = Scaler::fit(&fit_data.features, train_params.scale_mode);
+            let constant = scaler.constant_columns();
+            if !constant.is_empty() {
+                let names: Vec<String> = constant.iter().map(|&j| data_loader::feature_name(j)).collect();
+                logger.log(format!("Warning: constant features left unscaled: {}", names.join(", ")));
+            }
+            let mut dataset = Dataset {
+                features: scaler.transform(&fit_data.features),
+                labels: fit_data.labels.clone(),
+                weights: fit_data.weights.clone(),
+            };
+            
+            // Balance only the training copy; calibration and the PR curve below use unbalanced data
+            if train_params.balance != Balance::None {
+                dataset = data_loader::balance_classes(&dataset, train_params.balance, &mut StdRng::seed_from_u64(seed));
+                logger.log(format!("Balanced training data to {} samples", dataset.n_samples()));
             }
             
-            println!("Training completed. Waiting for user to restart with new parameters.");
+            let validation = validation_data.as_ref().map(|validation| Dataset {
+                features: scaler.transform(&validation.features),
+                labels: validation.labels.clone(),
+                weights: validation.weights.clone(),
+            });
+            
+            logger.log(format!("Initializing network with {} features, {} hidden layers, {} neurons per layer",
+                               dataset.features.ncols(), train_params.hidden_layers, train_params.neurons_per_layer));
+            
+            let mut best = None;
+            
+            // External metrics stream, written alongside the UI updates
+            let mut metrics_stream = metrics_jsonl.as_deref().map(cli::open_metrics_jsonl).transpose().unwrap_or_else(|e| {
+                logger.log(format!("Warning: cannot open the metrics stream: {}", e));
+                None
+            }).map(|stream| stream.with_run(format!("seed={}", seed)));
+            let mut recorder = record.as_deref().map(|path| Recorder::create(path, &train_params)).transpose().unwrap_or_else(|e| {
+                logger.log(format!("Warning: cannot start the recording: {}", e));
+                None
+            });
+            let result = train_params.check_data(&dataset).and_then(|()| neural_network::create_network(&train_params)).and_then(|mut network| {
+                let summary = network.summary();
+                logger.log(format!("Network: {}", summary));
+                let _ = summary_sender.send(summary);
+                network.set_scaler(scaler);
+                // WithBaseline reports the untrained baseline without an epoch, then every epoch;
+                // the scores are on the validation rows when there are any
+                let scored_on = if validation.is_some() { "Validation" } else { "Training" };
+                let on_epoch = |epoch: Option<usize>, accuracy: f64, loss: f64| {
+                    heartbeat.beat();
+                    let score = || match train_params.task {
+                        TaskType::BinaryClassification => format!("{} accuracy = {:.2}%", scored_on, accuracy),
+                        TaskType::Regression => format!("{} R² = {:.4}", scored_on, accuracy),
+                    };
+                    // The plot counts epochs completed, so the baseline sits at 0
+                    let Some(epoch) = epoch else {
+                        logger.log(format!("Before training: {}, Loss = {:.4}", score(), loss));
+                        accuracy_sender.send((0, accuracy, loss)).unwrap();
+                        return;
+                    };
+                    if epoch.is_multiple_of(LOG_INTERVAL) || epoch == train_params.epochs - 1 {
+                        logger.log(format!("Epoch {}/{}: {}, Loss = {:.4}", epoch, train_params.epochs, score(), loss));
+                    }
+                    
+                    // Send accuracy and loss update to the UI
+                    accuracy_sender.send((epoch + 1, accuracy, loss)).unwrap();
+                    
+                    // The UI refuses Start while busy, but a run is never queued behind this one either way
+                    while params_receiver.try_recv().is_ok() {
+                        logger.log(format!("Ignored new training parameters: {}", BUSY_MESSAGE));
+                    }
+                    
+                    // Hold here while paused, with this epoch on the plot and heartbeats going so it does not look stalled
+                    if pause.is_paused() {
+                        logger.log(format!("Paused after epoch {}", epoch));
+                        let _ = accuracy_sender.flush();
+                        while pause.wait(PAUSE_POLL_INTERVAL) {
+                            heartbeat.beat();
+                        }
+                        logger.log("Resumed");
+                    }
+                };
+                let on_event = |event| match event {
+                    TrainingEvent::ActivationStats { epoch, stats } => {
+                        let _ = activation_stats_sender.send((epoch, stats));
+                    }
+                    TrainingEvent::LearningRateReduced { epoch, learning_rate } => {
+                        logger.log(format!("Epoch {}: loss kept rising, learning rate reduced to {:.6}", epoch, learning_rate));
+                    }
+                    TrainingEvent::Diverged { epoch, batch, action, learning_rate } => {
+                        let taken = match action {
+                            DivergencePolicy::ReduceLr => format!("learning rate halved to {:.6}", learning_rate),
+                            _ => "update skipped".to_string(),
+                        };
+                        logger.log(format!("Epoch {}: batch {} gave a non-finite loss or update, {}", epoch, batch, taken));
+                    }
+                    TrainingEvent::GradientNorm { epoch, norm } => {
+                        let _ = gradient_norm_sender.send((epoch, norm));
+                    }
+                    TrainingEvent::ValidationLoss { epoch, loss } => {
+                        if epoch % LOG_INTERVAL == 0 {
+                            logger.log(format!("Epoch {}: validation loss = {:.4}", epoch, loss));
+                        }
+                    }
+                    TrainingEvent::EarlyStopped { epoch, best_epoch, best_loss } => {
+                        logger.log(format!(
+                            "Early stopping after epoch {}: no validation improvement since epoch {} (loss {:.4})",
+                            epoch, best_epoch, best_loss
+                        ));
+                    }
+                    TrainingEvent::OutputCollapsed { epoch, mean, std } => {
+                        logger.log(format!(
+                            "Warning: by epoch {} the network has given every sample about the same probability ({:.3}, std {:.1e}) for {} epochs. \
+                             It may be stuck predicting the majority class: try class balancing or a lower learning rate",
+                            epoch, mean, std, neural_network::COLLAPSE_EPOCHS
+                        ));
+                    }
+                    TrainingEvent::BestEpoch { epoch, loss, validation } => {
+                        let best_epoch = BestEpoch { epoch, loss, validation };
+                        logger.log(best_epoch.to_string());
+                        best = Some(best_epoch);
+                    }
+                };
+                // The plot and the recording follow the validation metrics; the metrics stream has both
+                let mut reported = (WithBaseline(on_epoch), &mut recorder as &mut dyn TrainingCallback);
+                let outcome = match &validation {
+                    Some(validation) => {
+                        let callback = (ValidationMetrics(&mut reported as &mut dyn TrainingCallback), &mut metrics_stream as &mut dyn TrainingCallback);
+                        neural_network::train_network_validated(&mut network, &dataset, validation, &train_params, callback, on_event)?
+                    }
+                    None => {
+                        let callback = (&mut reported as &mut dyn TrainingCallback, &mut metrics_stream as &mut dyn TrainingCallback);
+                        neural_network::train_network_with_events(&mut network, &dataset, &train_params, callback, on_event)?
+                    }
+                };
+                Ok((network, outcome))
+            });
+            if let Some(Err(e)) = metrics_stream.map(|stream| stream.finish()) {
+                logger.log(format!("Warning: writing the metrics stream failed: {}", e));
+            }
+            
+            // The final epoch must reach the plot even if the UI fell behind
+            if accuracy_sender.flush().is_err() {
+                return Ok(());
+            }
+            
+            // Released before the finish message, so a Start prompted by it is accepted
+            busy.release();
+            match result {
+                Ok((mut network, outcome)) => {
+                    match outcome.stopped_reason {
+                        StopReason::Completed | StopReason::EarlyStopped => {}
+                        StopReason::Cancelled => logger.log(format!("Training cancelled after {} epochs", outcome.epochs_run)),
+                        StopReason::TimedOut => logger.log(format!("Time limit reached after {} epochs", outcome.epochs_run)),
+                        StopReason::TargetReached => logger.log(format!("Accuracy target reached after {} epochs", outcome.epochs_run)),
+                    }
+                    if let Some(held_out) = &calibration_data {
+                        let brier_before = metrics::brier_score(&network, held_out);
+                        let calibration = network.calibrate(held_out);
+                        logger.log(format!(
+                            "Calibrated on {} held-out samples (a = {:.3}, b = {:.3}): Brier score {:.4} -> {:.4}",
+                            held_out.n_samples(), calibration.a, calibration.b, brier_before, metrics::brier_score(&network, held_out)
+                        ));
+                    }
+                    // Held-out rows show what generalizes: the untouched test set first, then
+                    // validation; without either, the training data stands in
+                    let held_out = test_data.as_ref().or(validation_data.as_ref()).unwrap_or(&data);
+                    
+                    // Precision and recall need classes; regression reports its fit instead
+                    let (pr_curve, average_precision) = match train_params.task {
+                        TaskType::BinaryClassification => {
+                            let report = metrics::evaluate_thresholds(&network, held_out, &[]);
+                            logger.log(format!(
+                                "Average precision: {:.4}, ROC AUC: {:.4} ({} samples)",
+                                report.average_precision, report.roc_auc, held_out.n_samples()
+                            ));
+                            (report.pr_curve, report.average_precision)
+                        }
+                        TaskType::Regression => {
+                            let fit = metrics::evaluate_regression(&network, held_out);
+                            logger.log(format!("R² = {:.4}, RMSE = {:.4} ({} samples)", fit.r_squared, fit.rmse, held_out.n_samples()));
+                            (Vec::new(), 0.0)
+                        }
+                    };
+                    
+                    if let Some(test) = &test_data {
+                        let scores: Vec<String> = recording::final_metrics(&network, test)
+                            .iter()
+                            .map(|(name, value)| format!("{} {:.4}", name, value))
+                            .collect();
+                        logger.log(format!("Test set ({} samples): {}", test.n_samples(), scores.join(", ")));
+                    }
+                    let importance = metrics::permutation_importance(&network, held_out).unwrap_or_else(|e| {
+                        logger.log(format!("Warning: feature importance skipped: {}", e));
+                        Vec::new()
+                    });
+                    if let Some(recorder) = &mut recorder {
+                        recorder.write_final_metrics(&recording::final_metrics(&network, held_out));
+                    }
+                    
+                    // Publish the trained model for the prediction panel
+                    shared_model.publish(TrainedModel {
+                        network,
+                        feature_stats: feature_stats.clone(),
+                        pr_curve,
+                        average_precision,
+                        importance,
+                    });
+                    let (accuracy, loss) = outcome.final_metrics.map_or((0.0, 0.0), |state| (state.accuracy, state.loss));
+                    let _ = finished_sender.send(RunFinished::Completed { epochs: outcome.epochs_run, accuracy, loss, best });
+                }
+                Err(e) => {
+                    let mut message = e.to_string();
+                    if matches!(e, NnError::Diverged { .. }) && train_params.learning_rate > neural_network::HIGH_LEARNING_RATE {
+                        message.push_str(&format!(
+                            ". The learning rate {} is above {}, where training usually diverges",
+                            train_params.learning_rate, neural_network::HIGH_LEARNING_RATE
+                        ));
+                    }
+                    logger.error(format!("Error during training: {}", message));
+                    let _ = finished_sender.send(RunFinished::Failed(message));
+                }
+            }
+            if let Some(Err(e)) = recorder.map(|recorder| recorder.finish()) {
+                logger.log(format!("Warning: writing the recording failed: {}", e));
+            }
+            
+            logger.log("Training completed. Waiting for user to restart with new parameters.");
         }
-        
-        Ok(())
     });
 
     // Let's handle potential errors from eframe more gracefully
@@ -314,13 +424,32 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         options,
         Box::new(|_cc| Box::new(window)),
     ) {
-        eprintln!("Error in UI: {}", e);
+        log::error!("Error in UI: {}", e);
     }
     
     if let Err(e) = training_thread.join().unwrap() {
-        eprintln!("Error in training: {}", e);
+        log::error!("Error in training: {}", e);
     }
 
     Ok(())
 }
 
+// Held-out parts of a run's data besides the training rows
+type HeldOut = (Option<Dataset>, Option<Dataset>, Option<Dataset>);
+
+// Splits `data` into (training, calibration, validation, test) rows as
+// `params` asks for, refusing data too small to leave a usable part on each side
+fn split_held_out(data: &Dataset, params: &TrainingParams, seed: u64) -> Result<(Dataset, HeldOut), NnError> {
+    // The plotted curves, early stopping and the accuracy target all watch the validation rows
+    let (fit_data, validation_data, test_data) =
+        data_loader::stratified_split(data, params.val_fraction, params.test_fraction, &mut StdRng::seed_from_u64(seed))?;
+    
+    // Calibration needs data the network has not seen
+    if params.calibrate {
+        data_loader::check_split(&fit_data, CALIBRATION_FRACTION)?;
+        let (train, held_out) = data_loader::train_validation_split(&fit_data, CALIBRATION_FRACTION);
+        Ok((train, (Some(held_out), validation_data, test_data)))
+    } else {
+        Ok((fit_data, (None, validation_data, test_data)))
+    }
+}
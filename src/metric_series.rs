@@ -0,0 +1,133 @@
+/// One received value of a metric.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetricPoint {
+    /// Index of the update among all received since the last clear.
+    pub update: f64,
+    /// Training epoch the value was reported for.
+    pub epoch: f64,
+    pub value: f64,
+}
+
+/// Plot buffer for one metric, such as the accuracy or loss curve. Once it
+/// holds more than its cap, the older half is downsampled into (min, max)
+/// pairs per bucket so memory stays bounded while the envelope of the curve is
+/// preserved. Recent points keep full resolution.
+///
+/// NaN and infinite values are never plotted: they would break the line and
+/// the plot bounds. They are kept apart in [`MetricSeries::dropped`] so the
+/// plot can mark where data is missing, and every other method only sees the
+/// finite points.
+///
+/// ```
+/// use water_potability_nn::metric_series::MetricSeries;
+///
+/// let mut losses = MetricSeries::default();
+/// losses.push(0, 0.7, 100);
+/// losses.push(1, f64::NAN, 100);
+/// losses.push(2, 0.5, 100);
+/// assert_eq!(losses.points().len(), 2);
+/// assert_eq!(losses.dropped()[0].epoch, 1.0);
+/// assert_eq!(losses.max_value(), 0.7);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MetricSeries {
+    points: Vec<MetricPoint>,
+    dropped: Vec<MetricPoint>,
+    received: usize,  // Values pushed since the last clear, before decimation
+}
+
+impl MetricSeries {
+    /// Appends the value reported for `epoch`, decimating the older points
+    /// beyond `max_points`. A non-finite value goes to the dropped points,
+    /// of which only the newest `max_points` are kept.
+    pub fn push(&mut self, epoch: usize, value: f64, max_points: usize) {
+        let point = MetricPoint { update: self.received as f64, epoch: epoch as f64, value };
+        self.received += 1;
+        if !value.is_finite() {
+            self.dropped.push(point);
+            if self.dropped.len() > max_points {
+                self.dropped.drain(..self.dropped.len() - max_points);
+            }
+            return;
+        }
+        self.points.push(point);
+        if self.points.len() > max_points {
+            self.decimate(max_points);
+        }
+    }
+
+    fn decimate(&mut self, max_points: usize) {
+        const BUCKET: usize = 4;
+        let split = self.points.len() - max_points / 2;
+        let mut reduced = Vec::with_capacity(split / 2 + max_points / 2 + 1);
+
+        for bucket in self.points[..split].chunks(BUCKET) {
+            let min = bucket.iter().min_by(|a, b| a.value.total_cmp(&b.value)).unwrap();
+            let max = bucket.iter().max_by(|a, b| a.value.total_cmp(&b.value)).unwrap();
+            // Keep the pair in epoch order so the line does not double back
+            let (first, second) = if min.update <= max.update { (min, max) } else { (max, min) };
+            reduced.push(*first);
+            if second.update != first.update {
+                reduced.push(*second);
+            }
+        }
+
+        reduced.extend_from_slice(&self.points[split..]);
+        self.points = reduced;
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.dropped.clear();
+        self.received = 0;
+    }
+
+    /// Whether no finite value is held.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The finite points, oldest first.
+    pub fn points(&self) -> &[MetricPoint] {
+        &self.points
+    }
+
+    /// The non-finite values received, oldest first.
+    pub fn dropped(&self) -> &[MetricPoint] {
+        &self.dropped
+    }
+
+    /// Newest finite value.
+    pub fn last(&self) -> Option<f64> {
+        self.points.last().map(|p| p.value)
+    }
+
+    /// Training epoch of the newest point, finite or not. Updates lost on
+    /// the way from training make it run ahead of the number received.
+    pub fn last_epoch(&self) -> Option<usize> {
+        self.newest().map(|p| p.epoch as usize)
+    }
+
+    /// Values of the last `n` finite points, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<f64> {
+        self.points[self.points.len().saturating_sub(n)..].iter().map(|p| p.value).collect()
+    }
+
+    /// Largest finite value, or 0 if it is larger.
+    pub fn max_value(&self) -> f64 {
+        self.points.iter().fold(0.0f64, |a, p| a.max(p.value))
+    }
+
+    /// Smallest finite value, or 0 if it is smaller.
+    pub fn min_value(&self) -> f64 {
+        self.points.iter().fold(0.0f64, |a, p| a.min(p.value))
+    }
+
+    /// Newest point received, finite or not.
+    pub fn newest(&self) -> Option<&MetricPoint> {
+        match (self.points.last(), self.dropped.last()) {
+            (Some(point), Some(dropped)) => Some(if dropped.update > point.update { dropped } else { point }),
+            (point, dropped) => point.or(dropped),
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use ndarray::Array2;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::data_loader::Dataset;
+use crate::error::NnError;
+use crate::neural_network::{binary_cross_entropy, is_potable, make_prediction, Network, TaskType, DECISION_THRESHOLD};
+
+// Shuffles of each feature averaged by permutation_importance
+const PERMUTATION_REPEATS: u64 = 5;
+// Fixed so repeated calls on the same network and data agree
+const PERMUTATION_SEED: u64 = 0x5eed;
+
+/// Scores of a network on a dataset, see [`evaluate`]. Potable is the
+/// positive class for precision, recall and F1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    /// Percentage of correctly classified samples.
+    pub accuracy: f64,
+    /// Mean binary cross-entropy, without any regularization penalty.
+    pub loss: f64,
+    /// Share of samples predicted potable that are potable, 0 when none are predicted potable.
+    pub precision: f64,
+    /// Share of potable samples predicted potable, 0 when there are none.
+    pub recall: f64,
+    /// Harmonic mean of precision and recall, 0 when both are 0.
+    pub f1: f64,
+}
+
+/// Scores `network` on `dataset`, whose features are in the units
+/// `make_prediction` expects (raw when the network carries a scaler).
+pub fn evaluate(network: &Network, dataset: &Dataset) -> Evaluation {
+    let probabilities = network.predict_proba(&network.scale_inputs(&dataset.features));
+    let (mut correct, mut true_positives, mut predicted_positives, mut positives) = (0, 0, 0, 0);
+    for (&p, &y) in probabilities.iter().zip(dataset.labels.iter()) {
+        let (predicted, actual) = (is_potable(p), y >= 0.5);
+        correct += usize::from(predicted == actual);
+        true_positives += usize::from(predicted && actual);
+        predicted_positives += usize::from(predicted);
+        positives += usize::from(actual);
+    }
+
+    let scores = ThresholdMetrics::from_counts(DECISION_THRESHOLD, correct, true_positives, predicted_positives, positives, dataset.n_samples());
+    Evaluation {
+        accuracy: scores.accuracy,
+        loss: binary_cross_entropy(&probabilities, &dataset.labels),
+        precision: scores.precision,
+        recall: scores.recall,
+        f1: scores.f1,
+    }
+}
+
+/// Classification scores at one decision threshold, see
+/// [`evaluate_thresholds`]. Defined as in [`Evaluation`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdMetrics {
+    /// Probability at or above which a sample is predicted potable.
+    pub threshold: f64,
+    pub accuracy: f64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+impl ThresholdMetrics {
+    fn from_counts(threshold: f64, correct: usize, true_positives: usize, predicted_positives: usize, positives: usize, n: usize) -> Self {
+        let ratio = |numerator: usize, denominator: usize| if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 };
+        let (precision, recall) = (ratio(true_positives, predicted_positives), ratio(true_positives, positives));
+        ThresholdMetrics {
+            threshold,
+            accuracy: correct as f64 / n.max(1) as f64 * 100.0,
+            precision,
+            recall,
+            f1: if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) },
+        }
+    }
+}
+
+/// Threshold scores and curves of a network on a dataset, all from one
+/// forward pass, see [`evaluate_thresholds`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdReport {
+    /// Scores at each requested threshold, in the order requested.
+    pub thresholds: Vec<ThresholdMetrics>,
+    /// ROC curve as `(false positive rate, true positive rate)` points from
+    /// `(0.0, 0.0)` to `(1.0, 1.0)`, empty when either class is missing.
+    pub roc_curve: Vec<(f64, f64)>,
+    /// As [`roc_auc`].
+    pub roc_auc: f64,
+    /// As [`pr_curve`].
+    pub pr_curve: Vec<(f64, f64)>,
+    /// [`average_precision`] of `pr_curve`.
+    pub average_precision: f64,
+}
+
+/// Scores `network` on `dataset` (raw features, as for [`evaluate`]) at every
+/// threshold in `thresholds`, along with the ROC and precision-recall curves
+/// and their summaries.
+///
+/// The probabilities are predicted once and sorted, after which each
+/// threshold costs a binary search rather than another pass over the data, so
+/// a fine grid of thresholds for a slider is cheap.
+///
+/// ```
+/// use ndarray::array;
+/// use water_potability_nn::data_loader::Dataset;
+/// use water_potability_nn::metrics::{evaluate, evaluate_thresholds};
+/// use water_potability_nn::neural_network::{NetworkBuilder, DECISION_THRESHOLD};
+///
+/// let network = NetworkBuilder::new(1).hidden_layers(&[4]).seed(1).build()?;
+/// let dataset = Dataset { features: array![[-1.0], [0.0], [1.0], [2.0]], labels: array![[0.0], [1.0], [0.0], [1.0]], weights: None };
+/// let report = evaluate_thresholds(&network, &dataset, &[0.0, DECISION_THRESHOLD]);
+/// assert_eq!(report.thresholds[0].recall, 1.0, "everything is predicted potable");
+/// assert_eq!(report.thresholds[1].f1, evaluate(&network, &dataset).f1);
+/// # Ok::<(), water_potability_nn::NnError>(())
+/// ```
+pub fn evaluate_thresholds(network: &Network, dataset: &Dataset, thresholds: &[f64]) -> ThresholdReport {
+    let probabilities = network.predict_proba(&network.scale_inputs(&dataset.features));
+    let scored = scored_samples(&probabilities, &dataset.labels);
+    let n = scored.len();
+    // A threshold predicts a prefix of the sorted samples potable
+    let positives_before = positives_before(&scored);
+    let positives = positives_before[n];
+    let thresholds = thresholds
+        .iter()
+        .map(|&threshold| {
+            let predicted_positives = scored.partition_point(|&(score, _)| score >= threshold);
+            let true_positives = positives_before[predicted_positives];
+            let true_negatives = (n - predicted_positives) - (positives - true_positives);
+            ThresholdMetrics::from_counts(threshold, true_positives + true_negatives, true_positives, predicted_positives, positives, n)
+        })
+        .collect();
+
+    let pr_curve = pr_curve_from_scores(&scored);
+    ThresholdReport {
+        thresholds,
+        roc_curve: roc_curve_from_scores(&scored),
+        roc_auc: roc_auc_from_scores(&scored),
+        average_precision: average_precision(&pr_curve),
+        pr_curve,
+    }
+}
+
+// (probability, is potable) of every sample, highest probability first
+fn scored_samples(probabilities: &Array2<f64>, labels: &Array2<f64>) -> Vec<(f64, bool)> {
+    let mut scored: Vec<(f64, bool)> = probabilities
+        .iter()
+        .zip(labels.iter())
+        .map(|(&p, &y)| (p, y >= 0.5))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+}
+
+// Number of positives among the first i sorted samples, for every i from 0 to n
+fn positives_before(scored: &[(f64, bool)]) -> Vec<usize> {
+    let mut counts = Vec::with_capacity(scored.len() + 1);
+    counts.push(0);
+    for &(_, is_positive) in scored {
+        counts.push(counts[counts.len() - 1] + usize::from(is_positive));
+    }
+    counts
+}
+
+// Samples sharing a score are on the same side of any threshold, so curves
+// take one point per group: the end index of each group of equal scores
+fn group_ends(scored: &[(f64, bool)]) -> impl Iterator<Item = usize> + '_ {
+    (1..=scored.len()).filter(|&end| scored.get(end).is_none_or(|next| next.0 != scored[end - 1].0))
+}
+
+/// Scores of a [`TaskType::Regression`](crate::neural_network::TaskType)
+/// network on a dataset, see [`evaluate_regression`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegressionEvaluation {
+    /// Share of the labels' variance the predictions explain: 1 is perfect,
+    /// 0 is no better than predicting the mean, and it can go negative.
+    pub r_squared: f64,
+    /// Root mean squared error, in the units of the labels.
+    pub rmse: f64,
+}
+
+/// Scores the predicted values of `network` on `dataset`, with features in
+/// the units `make_prediction` expects. R² is 0 when every label is the same.
+pub fn evaluate_regression(network: &Network, dataset: &Dataset) -> RegressionEvaluation {
+    let predictions = network.predict_proba(&network.scale_inputs(&dataset.features));
+    let n = dataset.n_samples().max(1) as f64;
+    let squared_error = (&predictions - &dataset.labels).mapv(|d| d * d).sum();
+    let mean = dataset.labels.sum() / n;
+    let total = dataset.labels.mapv(|y| (y - mean) * (y - mean)).sum();
+    RegressionEvaluation {
+        r_squared: if total > 0.0 { 1.0 - squared_error / total } else { 0.0 },
+        rmse: (squared_error / n).sqrt(),
+    }
+}
+
+/// Brier score: mean squared difference between the predicted probability
+/// and the 0/1 label, lower is better. Features are raw, as for [`evaluate`].
+pub fn brier_score(network: &Network, dataset: &Dataset) -> f64 {
+    let probabilities = network.predict_proba(&network.scale_inputs(&dataset.features));
+    (&probabilities - &dataset.labels).mapv(|d| d * d).mean().unwrap_or(0.0)
+}
+
+/// Precision-recall curve of `network` on `dataset`, as `(recall, precision)`
+/// points ordered by increasing recall. Features are in the same units
+/// `make_prediction` expects, i.e. raw when the network carries a scaler.
+///
+/// One point is produced per distinct predicted probability, used as the
+/// decision threshold. The curve starts at `(0.0, 1.0)` since precision is
+/// undefined before any sample is predicted positive. A dataset without
+/// positive samples yields an empty curve.
+pub fn pr_curve(network: &Network, dataset: &Dataset) -> Vec<(f64, f64)> {
+    let probabilities = network.predict_proba(&network.scale_inputs(&dataset.features));
+    pr_curve_from_scores(&scored_samples(&probabilities, &dataset.labels))
+}
+
+// Lowers the threshold one distinct score at a time, over samples sorted by scored_samples
+fn pr_curve_from_scores(scored: &[(f64, bool)]) -> Vec<(f64, f64)> {
+    let tp = positives_before(scored);
+    let positives = tp[scored.len()];
+    if positives == 0 {
+        return Vec::new();
+    }
+    let mut curve = vec![(0.0, 1.0)];
+    curve.extend(group_ends(scored).map(|end| (tp[end] as f64 / positives as f64, tp[end] as f64 / end as f64)));
+    curve
+}
+
+fn roc_curve_from_scores(scored: &[(f64, bool)]) -> Vec<(f64, f64)> {
+    let tp = positives_before(scored);
+    let positives = tp[scored.len()];
+    let negatives = scored.len() - positives;
+    if positives == 0 || negatives == 0 {
+        return Vec::new();
+    }
+    let mut curve = vec![(0.0, 0.0)];
+    curve.extend(group_ends(scored).map(|end| ((end - tp[end]) as f64 / negatives as f64, tp[end] as f64 / positives as f64)));
+    curve
+}
+
+/// Area under the ROC curve of `network` on `dataset` (raw features, as for
+/// [`evaluate`]): the chance that a random potable sample gets a higher
+/// probability than a random non-potable one, ties counting half. 1 is a
+/// perfect ranking, 0.5 is chance and also what a dataset missing either
+/// class gets.
+pub fn roc_auc(network: &Network, dataset: &Dataset) -> f64 {
+    let probabilities = network.predict_proba(&network.scale_inputs(&dataset.features));
+    roc_auc_from_scores(&scored_samples(&probabilities, &dataset.labels))
+}
+
+fn roc_auc_from_scores(scored: &[(f64, bool)]) -> f64 {
+    let n = scored.len();
+    let tp = positives_before(scored);
+    let positives = tp[n];
+    let negatives = n - positives;
+    if positives == 0 || negatives == 0 {
+        return 0.5;
+    }
+
+    // Mann-Whitney U: sum the ascending ranks of the positives, tied scores
+    // sharing their mean rank. Scores are sorted highest first, so the group
+    // ending at `end` holds ranks n - end + 1 through n - start
+    let mut positive_ranks = 0.0;
+    let mut start = 0;
+    for end in group_ends(scored) {
+        let mean_rank = (2 * n - start - end + 1) as f64 / 2.0;
+        positive_ranks += mean_rank * (tp[end] - tp[start]) as f64;
+        start = end;
+    }
+    (positive_ranks - (positives * (positives + 1)) as f64 / 2.0) / (positives * negatives) as f64
+}
+
+/// Average precision: the precision at each curve point weighted by the recall
+/// gained since the previous point.
+pub fn average_precision(curve: &[(f64, f64)]) -> f64 {
+    curve
+        .windows(2)
+        .map(|pair| (pair[1].0 - pair[0].0) * pair[1].1)
+        .sum()
+}
+
+/// Predicted potability probability as feature `feature_idx` moves across its
+/// observed training range in `n_points` even steps, with the other features
+/// held at `base_input`. Returns `(feature_value, probability)` pairs in
+/// original units; the network must carry the scaler it was trained with.
+pub fn sweep_feature(
+    network: &Network,
+    base_input: &[f64],
+    feature_idx: usize,
+    n_points: usize,
+) -> Result<Vec<(f64, f64)>, NnError> {
+    let scaler = network.scaler().ok_or(NnError::MissingScaler)?;
+    let stats = scaler
+        .feature_stats()
+        .get(feature_idx)
+        .ok_or_else(|| NnError::Config(format!("Feature index {} is out of range", feature_idx)))?;
+    if base_input.len() != scaler.feature_stats().len() {
+        return Err(NnError::InputDimension { expected: scaler.feature_stats().len(), found: base_input.len() });
+    }
+
+    let mut input = base_input.to_vec();
+    let steps = n_points.max(2) - 1;
+    (0..=steps)
+        .map(|i| {
+            let value = stats.min + (stats.max - stats.min) * i as f64 / steps as f64;
+            input[feature_idx] = value;
+            Ok((value, make_prediction(network, &input)?.probability))
+        })
+        .collect()
+}
+
+/// Contribution of each feature to one prediction of `network`, for showing
+/// which features pushed it toward potable (positive) or not potable
+/// (negative). `input` is raw, as for `make_prediction`, and the network must
+/// carry the scaler it was trained with. There is one contribution per input
+/// feature, in input order.
+///
+/// Each contribution is gradient × input: how far the feature sits from its
+/// training mean, in scaled units, times how steeply the network's logit
+/// rises with it at `input`. It is in log-odds, or in the predicted value for
+/// a regression network. This is a local, linear reading of the network: the
+/// contributions rank the features for this sample, but only add up to the
+/// change from the average sample when the network is close to linear.
+///
+/// ```
+/// use water_potability_nn::data_loader::{ScaleMode, Scaler};
+/// use water_potability_nn::metrics::explain_prediction;
+/// use water_potability_nn::neural_network::NetworkBuilder;
+///
+/// let mut network = NetworkBuilder::new(9).hidden_layers(&[8]).seed(3).build()?;
+/// let training = ndarray::Array2::from_shape_fn((20, 9), |(i, j)| (i * (j + 1)) as f64);
+/// network.set_scaler(Scaler::fit(&training, ScaleMode::Standardize));
+///
+/// // At the training mean every feature is average, so none contributes
+/// let means: Vec<f64> = network.scaler().unwrap().feature_stats().iter().map(|s| s.mean).collect();
+/// assert!(explain_prediction(&network, &means)?.iter().all(|c| c.abs() < 1e-6));
+/// # Ok::<(), water_potability_nn::NnError>(())
+/// ```
+pub fn explain_prediction(network: &Network, input: &[f64]) -> Result<Vec<f64>, NnError> {
+    network.check_input_dim(input.len())?;
+    let scaler = network.scaler().ok_or(NnError::MissingScaler)?;
+    let scaled = scaler.transform_row(input);
+    let means: Vec<f64> = scaler.feature_stats().iter().map(|stats| stats.mean).collect();
+    let baseline = scaler.transform_row(&means);
+    let gradient = network.input_gradient(&Array2::from_shape_vec((1, scaled.len()), scaled.clone())?);
+
+    Ok((0..scaled.len()).map(|feature| (scaled[feature] - baseline[feature]) * gradient[(0, feature)]).collect())
+}
+
+/// Permutation feature importance: how much the score of `network` on
+/// `dataset` drops when one feature's column is shuffled, breaking its link
+/// to the labels while keeping its distribution. The score is accuracy in
+/// percent, or R² for a regression network, so importance is in points of
+/// it; near 0 means the network ignores the feature, and a small negative
+/// value is noise.
+///
+/// Features are raw, as for [`evaluate`], and scaled with the network's
+/// scaler. Each feature is shuffled several times with fixed seeds and the
+/// drops averaged, so repeated calls give the same result. Score it on data
+/// the network did not train on to see what it relies on to generalize.
+///
+/// Returns one importance per feature column, or [`NnError::InputDimension`]
+/// when `dataset` does not have the network's input width.
+pub fn permutation_importance(network: &Network, dataset: &Dataset) -> Result<Vec<f64>, NnError> {
+    network.check_input_dim(dataset.features.ncols())?;
+    let score = |features: Array2<f64>| {
+        let dataset = Dataset { features, labels: dataset.labels.clone(), weights: None };
+        match network.task() {
+            TaskType::BinaryClassification => evaluate(network, &dataset).accuracy,
+            TaskType::Regression => evaluate_regression(network, &dataset).r_squared,
+        }
+    };
+    let baseline = score(dataset.features.clone());
+
+    let mut importance = vec![0.0; dataset.features.ncols()];
+    for (feature, drop) in importance.iter_mut().enumerate() {
+        let mut total = 0.0;
+        for repeat in 0..PERMUTATION_REPEATS {
+            let mut rng = StdRng::seed_from_u64(PERMUTATION_SEED + feature as u64 * PERMUTATION_REPEATS + repeat);
+            let mut column = dataset.features.column(feature).to_vec();
+            column.shuffle(&mut rng);
+            let mut shuffled = dataset.features.clone();
+            shuffled.column_mut(feature).assign(&ndarray::Array1::from(column));
+            total += baseline - score(shuffled);
+        }
+        *drop = total / PERMUTATION_REPEATS as f64;
+    }
+    Ok(importance)
+}
@@ -0,0 +1,2383 @@
+use ndarray::{Array2, Axis};
+use ndarray_rand::RandomExt;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+
+use crate::data_loader::{Balance, DataSource, Dataset, FeatureStats, ScaleMode, Scaler, NUM_FEATURES};
+use crate::error::NnError;
+
+// Default training configuration, also used to pre-fill the GUI inputs
+pub const DEFAULT_EPOCHS: usize = 2000;
+pub const DEFAULT_LR: f64 = 0.5;
+pub const DEFAULT_HIDDEN: usize = 32;
+pub const DEFAULT_HIDDEN_LAYERS: usize = 2;
+
+/// Learning rate above which training usually diverges within a few epochs.
+/// Higher rates are allowed, but the GUI warns about them.
+pub const HIGH_LEARNING_RATE: f64 = 2.0;
+
+/// Probability at or above which a sample is classified as potable.
+pub const DECISION_THRESHOLD: f64 = 0.5;
+
+/// Standard deviation of the predicted probabilities below which the output
+/// counts as constant.
+pub const COLLAPSE_STD: f64 = 1e-3;
+/// Consecutive constant-output epochs before [`TrainingEvent::OutputCollapsed`] is sent.
+pub const COLLAPSE_EPOCHS: usize = 5;
+/// Default [`TrainingParams::max_memory`]: 2 GiB.
+pub const DEFAULT_MAX_MEMORY: usize = 2 << 30;
+/// Fewest samples training accepts; one sample only teaches its own label.
+pub const MIN_TRAINING_SAMPLES: usize = 2;
+/// Times [`DivergencePolicy::ReduceLr`] halves the learning rate to retry one
+/// update before giving up on the run.
+pub const MAX_DIVERGENCE_RETRIES: usize = 10;
+
+// First bytes of every saved model file
+const MODEL_MAGIC: [u8; 4] = *b"WPNN";
+/// Version of the saved model layout. Bump it whenever the layout changes.
+pub const MODEL_FORMAT_VERSION: u32 = 3;
+
+/// Floating-point type of the network's weights and arithmetic: `f64`, or
+/// `f32` with the `f32` feature.
+///
+/// Single precision halves the memory of the weights and activations and
+/// speeds up the matrix multiplies of large models, but keeps about 7
+/// significant digits instead of 16. Losses and weights drift from an `f64`
+/// run by rounding, so a seeded run is reproducible within one precision only,
+/// and very small learning rates or gradients can vanish into rounding. Data,
+/// metrics, predictions and saved models are `f64` either way.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+/// Training parameters shared between the UI and the training thread.
+#[derive(Clone, Debug)]
+pub struct TrainingParams {
+    /// Features per sample, the width of the network's input layer built by
+    /// [`create_network`]. Defaults to the water dataset's [`NUM_FEATURES`].
+    pub input_dim: usize,
+    /// Whether the network classifies potability or predicts a continuous score.
+    pub task: TaskType,
+    pub epochs: usize,
+    pub hidden_layers: usize,
+    pub neurons_per_layer: usize,
+    pub learning_rate: f64,
+    /// Epochs over which the learning rate ramps linearly up to `learning_rate`.
+    pub warmup_epochs: usize,
+    pub scale_mode: ScaleMode,
+    /// Class balancing applied to the training data before fitting.
+    pub balance: Balance,
+    /// Capture hidden-layer activation statistics every this many epochs, 0 disables.
+    pub activation_stats_every: usize,
+    /// Seed for weight initialisation, `None` draws one from entropy.
+    pub seed: Option<u64>,
+    /// Halve the learning rate when the loss rises for `lr_patience` epochs in a row.
+    pub auto_reduce_lr: bool,
+    pub lr_patience: usize,
+    /// L1 penalty strength on the weights (biases are not penalised), 0 disables.
+    pub l1_lambda: f64,
+    /// L2 penalty strength on the weights, 0 disables. Combines with `l1_lambda`.
+    pub l2_lambda: f64,
+    /// Report the global gradient L2 norm every epoch as a [`TrainingEvent::GradientNorm`].
+    pub track_gradient_norm: bool,
+    /// Layer indices (0 is the first hidden layer, the output layer is last)
+    /// whose weights and biases are left untouched. Gradients still flow
+    /// through them to earlier layers.
+    pub frozen_layers: Vec<usize>,
+    /// Hold out part of the data after training to fit [`PlattScaling`] on.
+    pub calibrate: bool,
+    /// Drop exact-duplicate samples (see [`crate::data_loader::dedup`]) before
+    /// splitting off held-out data. Off by default since repeated samples can
+    /// be intentional.
+    pub dedup: bool,
+    /// Loss minimised by training and reported per epoch.
+    pub loss: LossFn,
+    /// Samples per gradient step, 0 trains on the full dataset each step.
+    pub batch_size: usize,
+    /// Reshuffle the samples into new batches every epoch, seeded from `seed`.
+    pub shuffle_batches: bool,
+    /// Shuffle once and reuse that batch order every epoch instead of
+    /// reshuffling. The order follows from `seed` alone, so every epoch of
+    /// every run with the same seed, across restarts too, steps through
+    /// identical batches. Meant for debugging numerical problems; a fixed
+    /// order can generalize slightly worse than a fresh one each epoch.
+    pub fixed_batch_order: bool,
+    /// Standard deviation of Gaussian noise added to every training batch's
+    /// inputs, in scaled units (standard deviations under
+    /// [`ScaleMode::Standardize`]), 0 disables. Mimics sensor measurement
+    /// error as a regularizer; drawn from `seed`, and never applied to
+    /// validation data or predictions.
+    pub noise_std: f64,
+    /// Share of the data the app holds out as a validation set: its training
+    /// curves are scored on it, and early stopping and the accuracy target
+    /// watch it.
+    pub val_fraction: f64,
+    /// Share of the data held out as a test set, 0 disables. The network
+    /// never trains or stops on it, so the final scores measured on it are
+    /// unbiased. Together with `val_fraction` it must stay below 1.
+    pub test_fraction: f64,
+    /// What to do when a weight update gives non-finite weights or loss.
+    pub divergence_policy: DivergencePolicy,
+    /// Stop once the monitored loss has not improved for this many epochs in a
+    /// row, 0 disables. The monitored loss is the validation loss when training
+    /// with [`train_network_validated`], the training loss otherwise.
+    pub early_stopping_patience: usize,
+    /// An epoch only counts as an improvement when the monitored loss drops more
+    /// than this below the best so far; smaller gains tick the patience counter
+    /// up as if the loss had stalled. Raise it to stop near-flat curves sooner.
+    pub min_delta: f64,
+    /// Stop after the first epoch that ends past this much training time.
+    pub time_limit: Option<std::time::Duration>,
+    /// Stop after the first epoch whose score reaches this: accuracy in
+    /// percent, or R² for regression. The score is measured on the validation
+    /// data when training with [`train_network_validated`], on the training
+    /// data otherwise.
+    pub target_accuracy: Option<f64>,
+    /// Refuse to train when [`TrainingParams::estimate_memory`] exceeds this
+    /// many bytes, 0 disables.
+    pub max_memory: usize,
+    /// Directory to write TensorBoard scalar summaries (loss, accuracy,
+    /// learning rate, validation loss) to, one new event file per run.
+    #[cfg(feature = "tensorboard")]
+    pub log_dir: Option<std::path::PathBuf>,
+    pub restart_training: bool,
+}
+
+impl TrainingParams {
+    /// Rejects settings training cannot work with. Network sizes are checked
+    /// by [`NetworkBuilder::build`].
+    pub fn validate(&self) -> Result<(), NnError> {
+        if !(self.learning_rate.is_finite() && self.learning_rate > 0.0) {
+            return Err(NnError::Config(format!("Learning rate must be a positive number, got {}", self.learning_rate)));
+        }
+        if self.l1_lambda < 0.0 || self.l2_lambda < 0.0 {
+            return Err(NnError::Config("L1 and L2 penalties cannot be negative".to_string()));
+        }
+        if !(self.noise_std.is_finite() && self.noise_std >= 0.0) {
+            return Err(NnError::Config(format!("Input noise must be a non-negative number, got {}", self.noise_std)));
+        }
+        let in_range = |fraction: f64| (0.0..1.0).contains(&fraction);
+        if !(in_range(self.val_fraction) && in_range(self.test_fraction)) || self.val_fraction + self.test_fraction >= 1.0 {
+            return Err(NnError::Config(format!(
+                "Validation and test fractions must each be at least 0 and together below 1, got {} and {}",
+                self.val_fraction, self.test_fraction
+            )));
+        }
+        if self.target_accuracy.is_some_and(f64::is_nan) {
+            return Err(NnError::Config("Accuracy target must be a number".to_string()));
+        }
+        if self.min_delta.is_nan() || self.min_delta < 0.0 {
+            return Err(NnError::Config(format!("Minimum improvement cannot be negative, got {}", self.min_delta)));
+        }
+        if let LossFn::Huber { delta } = self.loss
+            && (delta.is_nan() || delta <= 0.0)
+        {
+            return Err(NnError::Config(format!("Huber delta must be positive, got {}", delta)));
+        }
+        if self.task == TaskType::Regression {
+            if self.loss == LossFn::Bce {
+                return Err(NnError::Config("Regression needs the MSE or Huber loss, cross-entropy is for 0/1 labels".to_string()));
+            }
+            if self.calibrate || self.balance != Balance::None {
+                return Err(NnError::Config("Calibration and class balancing only apply to classification".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects data whose column count differs from `input_dim`, with fewer
+    /// than [`MIN_TRAINING_SAMPLES`] samples, with labels other than 0 and 1
+    /// for classification, or that is too large to train on within
+    /// `max_memory`.
+    pub fn check_data<D: DataSource>(&self, data: &D) -> Result<(), NnError> {
+        if data.n_features() != self.input_dim {
+            return Err(NnError::InputDimension { expected: self.input_dim, found: data.n_features() });
+        }
+        check_sample_count(data.n_samples())?;
+        if self.task == TaskType::BinaryClassification
+            && let Some(label) = data.all().labels.iter().find(|&&y| y != 0.0 && y != 1.0)
+        {
+            return Err(NnError::Config(format!(
+                "Classification needs labels of 0 or 1, found {}: check the label column, or train a regression", label
+            )));
+        }
+        check_memory(self.estimate_memory(data.n_samples()), self.max_memory)
+    }
+
+    /// Fixes `seed` to one drawn from entropy when it is unset and returns the
+    /// seed in effect, so the run can be reproduced later by setting it again.
+    ///
+    /// ```
+    /// use water_potability_nn::neural_network::TrainingParams;
+    ///
+    /// let mut params = TrainingParams::default();
+    /// let seed = params.resolve_seed();
+    /// assert_eq!(params.seed, Some(seed));
+    /// assert_eq!(params.resolve_seed(), seed);
+    /// ```
+    pub fn resolve_seed(&mut self) -> u64 {
+        *self.seed.get_or_insert_with(rand::random)
+    }
+
+    // Seed of the batch order in pass `pass` over the data, the same every
+    // pass with fixed_batch_order
+    pub(crate) fn batch_order_seed(&self, shuffle_seed: u64, pass: usize) -> u64 {
+        if self.fixed_batch_order { shuffle_seed } else { shuffle_seed.wrapping_add(pass as u64) }
+    }
+
+    /// Rough peak memory in bytes of training the network [`create_network`]
+    /// builds on `n_samples` samples: the data, parameters with their
+    /// gradients and optimizer state, and one batch's activations. Computed
+    /// from the sizes alone, so it is safe to call for networks far too large
+    /// to build.
+    ///
+    /// ```
+    /// use water_potability_nn::neural_network::TrainingParams;
+    ///
+    /// let small = TrainingParams::default();
+    /// let wide = TrainingParams { neurons_per_layer: 100_000, ..small.clone() };
+    /// assert!(small.estimate_memory(3000) < 10 << 20);
+    /// assert!(wide.estimate_memory(3000) > wide.max_memory);
+    /// ```
+    pub fn estimate_memory(&self, n_samples: usize) -> usize {
+        let mut layer_shapes = Vec::with_capacity(self.hidden_layers + 1);
+        let mut inputs = self.input_dim;
+        for _ in 0..self.hidden_layers {
+            layer_shapes.push((inputs, self.neurons_per_layer));
+            inputs = self.neurons_per_layer;
+        }
+        layer_shapes.push((inputs, 1));
+        memory_estimate(&layer_shapes, self.input_dim, n_samples, self.batch_size)
+    }
+}
+
+impl Default for TrainingParams {
+    fn default() -> Self {
+        Self {
+            input_dim: NUM_FEATURES,
+            task: TaskType::default(),
+            epochs: DEFAULT_EPOCHS,
+            hidden_layers: DEFAULT_HIDDEN_LAYERS,
+            neurons_per_layer: DEFAULT_HIDDEN,
+            learning_rate: DEFAULT_LR,
+            warmup_epochs: 0,
+            scale_mode: ScaleMode::default(),
+            balance: Balance::default(),
+            activation_stats_every: 0,
+            seed: None,
+            auto_reduce_lr: false,
+            lr_patience: 5,
+            l1_lambda: 0.0,
+            l2_lambda: 0.0,
+            track_gradient_norm: false,
+            frozen_layers: Vec::new(),
+            calibrate: false,
+            dedup: false,
+            loss: LossFn::default(),
+            batch_size: 0,
+            shuffle_batches: true,
+            fixed_batch_order: false,
+            divergence_policy: DivergencePolicy::Abort,
+            val_fraction: 0.2,
+            test_fraction: 0.0,
+            noise_std: 0.0,
+            early_stopping_patience: 0,
+            min_delta: 0.0,
+            time_limit: None,
+            target_accuracy: None,
+            max_memory: DEFAULT_MAX_MEMORY,
+            #[cfg(feature = "tensorboard")]
+            log_dir: None,
+            restart_training: false,
+        }
+    }
+}
+
+/// What the network's single output predicts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TaskType {
+    /// Potable or not: a sigmoid output giving the probability of label 1,
+    /// scored by accuracy.
+    #[default]
+    BinaryClassification,
+    /// A continuous target such as a quality score: a linear output trained
+    /// with [`LossFn::Mse`] or [`LossFn::Huber`], scored by R².
+    Regression,
+}
+
+impl TaskType {
+    /// Name of the per-epoch score, [`EpochState::accuracy`], for display.
+    pub fn score_name(self) -> &'static str {
+        match self {
+            TaskType::BinaryClassification => "Accuracy (%)",
+            TaskType::Regression => "R²",
+        }
+    }
+}
+
+/// Activation used by the hidden layers. The output layer is a sigmoid, or
+/// linear for [`TaskType::Regression`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Relu,
+    Sigmoid,
+    Tanh,
+}
+
+impl Activation {
+    fn apply(self, z: &Array2<Float>) -> Array2<Float> {
+        match self {
+            Activation::Relu => z.mapv(|v| v.max(0.0)),
+            Activation::Sigmoid => sigmoid(z),
+            Activation::Tanh => z.mapv(Float::tanh),
+        }
+    }
+
+    // `apply` overwriting z, sparing a copy of a large batch
+    fn apply_in_place(self, z: &mut Array2<Float>) {
+        match self {
+            Activation::Relu => z.mapv_inplace(|v| v.max(0.0)),
+            Activation::Sigmoid => z.mapv_inplace(logistic),
+            Activation::Tanh => z.mapv_inplace(Float::tanh),
+        }
+    }
+
+    // Derivative with respect to the pre-activation value z
+    fn derivative(self, z: &Array2<Float>) -> Array2<Float> {
+        match self {
+            Activation::Relu => z.mapv(|v| if v > 0.0 { 1.0 } else { 0.0 }),
+            Activation::Sigmoid => sigmoid(z).mapv(|s| s * (1.0 - s)),
+            Activation::Tanh => z.mapv(|v| 1.0 - v.tanh().powi(2)),
+        }
+    }
+}
+
+/// Loss between the network output and the label: the probability and a 0/1
+/// label for classification, the predicted and true value for regression.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LossFn {
+    /// Binary cross-entropy, the natural loss for a probability output.
+    #[default]
+    Bce,
+    /// Squared error of the output.
+    Mse,
+    /// Squared error up to `delta`, linear beyond it, so badly wrong samples
+    /// pull less than with [`LossFn::Mse`].
+    Huber { delta: f64 },
+}
+
+impl LossFn {
+    /// Mean loss of the outputs against the labels, both shaped (n, 1).
+    pub fn loss(self, y_pred: &Array2<f64>, y_true: &Array2<f64>) -> f64 {
+        match self {
+            LossFn::Bce => binary_cross_entropy(y_pred, y_true),
+            _ => self.sample_losses(y_pred, y_true).sum() / y_true.nrows().max(1) as f64,
+        }
+    }
+
+    /// Mean loss with each sample counted `weights` times, Σ wᵢ·lossᵢ / Σ wᵢ,
+    /// or the plain [`LossFn::loss`] without weights.
+    ///
+    /// ```
+    /// use ndarray::array;
+    /// use water_potability_nn::neural_network::LossFn;
+    ///
+    /// let (y_pred, y_true) = (array![[1.0], [3.0]], array![[0.0], [0.0]]);
+    /// assert_eq!(LossFn::Mse.weighted_loss(&y_pred, &y_true, None), 5.0);
+    /// assert_eq!(LossFn::Mse.weighted_loss(&y_pred, &y_true, Some(&array![[3.0], [1.0]])), 3.0);
+    /// ```
+    pub fn weighted_loss(self, y_pred: &Array2<f64>, y_true: &Array2<f64>, weights: Option<&Array2<f64>>) -> f64 {
+        match weights {
+            // All-zero weights have nothing to average, their loss is 0
+            Some(weights) => (self.sample_losses(y_pred, y_true) * weights).sum() / weights.sum().max(f64::MIN_POSITIVE),
+            None => self.loss(y_pred, y_true),
+        }
+    }
+
+    fn sample_losses(self, y_pred: &Array2<f64>, y_true: &Array2<f64>) -> Array2<f64> {
+        match self {
+            LossFn::Bce => cross_entropy_per_sample(y_pred, y_true),
+            LossFn::Mse => (y_pred - y_true).mapv(|r| r * r),
+            LossFn::Huber { delta } => (y_pred - y_true)
+                .mapv(|r| if r.abs() <= delta { 0.5 * r * r } else { delta * (r.abs() - 0.5 * delta) }),
+        }
+    }
+
+    // Per-sample derivative of the loss with respect to the output layer's
+    // pre-activation, through the sigmoid unless the output is linear
+    fn output_delta(self, y_pred: &Array2<Float>, y_true: &Array2<Float>, task: TaskType) -> Array2<Float> {
+        let residual = y_pred - y_true;
+        let slope = match task {
+            TaskType::BinaryClassification => y_pred.mapv(|p| p * (1.0 - p)),
+            TaskType::Regression => Array2::ones(y_pred.raw_dim()),
+        };
+        match self {
+            // The sigmoid slope cancels against the cross-entropy derivative
+            LossFn::Bce => residual,
+            LossFn::Mse => residual * 2.0 * slope,
+            LossFn::Huber { delta } => {
+                let delta = delta as Float;
+                residual.mapv(|r| r.clamp(-delta, delta)) * slope
+            }
+        }
+    }
+}
+
+/// Rule used to apply gradients to the weights.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Optimizer {
+    /// Plain gradient descent.
+    Sgd,
+    /// Gradient descent with a velocity term decayed by `beta` every step.
+    Momentum { beta: f64 },
+}
+
+/// How training responds to a weight update that diverges: one leaving
+/// weights that are no longer finite numbers (NaN or infinite), or a loss
+/// that is not finite on the next batch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DivergencePolicy {
+    /// Stop at once with [`NnError::Diverged`].
+    #[default]
+    Abort,
+    /// Discard the offending batch's update, keeping the weights from before
+    /// it, and carry on. A loss that is not finite even then still aborts.
+    SkipBatch,
+    /// Discard the update and retry it at half the learning rate, which stays
+    /// halved for the rest of the run. Gives up with [`NnError::Diverged`]
+    /// after [`MAX_DIVERGENCE_RETRIES`] halvings.
+    ReduceLr,
+}
+
+/// Weight initialisation scheme. Biases always start at zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Init {
+    /// Standard normal scaled by 0.1.
+    SmallNormal,
+    /// Normal with variance 2 / fan_in, suited to ReLU.
+    He,
+    /// Normal with variance 1 / fan_in, suited to sigmoid and tanh.
+    Xavier,
+}
+
+/// Fully connected network: hidden layers followed by one output, a sigmoid
+/// for binary classification or linear for regression.
+#[derive(Clone, Debug)]
+pub struct Network {
+    weights: Vec<Array2<Float>>,
+    biases: Vec<Array2<Float>>,
+    activation: Activation,
+    task: TaskType,
+    optimizer: Optimizer,
+    // Momentum buffers, same shapes as weights/biases
+    velocity_w: Vec<Array2<Float>>,
+    velocity_b: Vec<Array2<Float>>,
+    // Scaling fitted on the training data, applied to raw prediction inputs
+    scaler: Option<Scaler>,
+    // Post-hoc probability calibration, applied to the sigmoid output
+    calibration: Option<PlattScaling>,
+}
+
+impl Network {
+    /// Number of input features the network expects.
+    pub fn input_dim(&self) -> usize {
+        self.weights[0].nrows()
+    }
+
+    /// Fails with [`NnError::InputDimension`] unless samples with `n_features`
+    /// features fit this network.
+    pub fn check_input_dim(&self, n_features: usize) -> Result<(), NnError> {
+        if n_features != self.input_dim() {
+            return Err(NnError::InputDimension { expected: self.input_dim(), found: n_features });
+        }
+        Ok(())
+    }
+
+    /// Scaler that `make_prediction` applies to raw inputs, if any.
+    pub fn scaler(&self) -> Option<&Scaler> {
+        self.scaler.as_ref()
+    }
+
+    /// Stores the scaler fitted on the training data with the model.
+    pub fn set_scaler(&mut self, scaler: Scaler) {
+        self.scaler = Some(scaler);
+    }
+
+    /// Applies the stored scaler to raw features, or returns them unchanged.
+    pub fn scale_inputs(&self, x: &Array2<f64>) -> Array2<f64> {
+        match &self.scaler {
+            Some(scaler) => scaler.transform(x),
+            None => x.clone(),
+        }
+    }
+
+    /// Layer shapes and trainable parameter count.
+    ///
+    /// ```
+    /// use water_potability_nn::neural_network::NetworkBuilder;
+    ///
+    /// let network = NetworkBuilder::new(9).hidden_layers(&[32, 32]).build().unwrap();
+    /// assert_eq!(network.summary().to_string(), "2 hidden layers (32, 32 neurons), 1,409 parameters");
+    /// ```
+    pub fn summary(&self) -> NetworkSummary {
+        NetworkSummary {
+            layer_shapes: self.weights.iter().map(|w| w.dim()).collect(),
+            total_parameters: self.weights.iter().zip(&self.biases).map(|(w, b)| w.len() + b.len()).sum(),
+        }
+    }
+
+    /// Whether the output is a probability or a regression value.
+    pub fn task(&self) -> TaskType {
+        self.task
+    }
+
+    /// Number of hidden layers (the output layer is not counted).
+    pub fn hidden_layers(&self) -> usize {
+        self.weights.len() - 1
+    }
+
+    /// Weight matrix of layer `index` (0 is the first hidden layer), shaped
+    /// (inputs, outputs).
+    pub fn layer_weights(&self, index: usize) -> Option<&Array2<Float>> {
+        self.weights.get(index)
+    }
+
+    /// Platt scaling applied to the network's output, if it was calibrated.
+    pub fn calibration(&self) -> Option<PlattScaling> {
+        self.calibration
+    }
+
+    /// Fits [`PlattScaling`] on the uncalibrated outputs for `dataset` (raw
+    /// features, like `make_prediction`) and stores it with the model. Use data
+    /// the network was not trained on.
+    pub fn calibrate(&mut self, dataset: &Dataset) -> PlattScaling {
+        let probabilities = self.outputs(&to_float(&self.scale_inputs(&dataset.features)));
+        let calibration = PlattScaling::fit(&probabilities, &dataset.labels);
+        self.calibration = Some(calibration);
+        calibration
+    }
+
+    /// Potability probability for every row of `x`, as an (n, 1) array,
+    /// calibrated when the network carries a [`PlattScaling`]. For a
+    /// regression network, the predicted values.
+    pub fn predict_proba(&self, x: &Array2<f64>) -> Array2<f64> {
+        let probabilities = self.outputs(&to_float(x));
+        match self.calibration {
+            Some(calibration) => probabilities.mapv(|p| calibration.apply(p)),
+            None => probabilities,
+        }
+    }
+
+    /// Activations of every layer for one sample, for visualizations and
+    /// explainability tools: one `Vec` per hidden layer, as wide as the layer,
+    /// then the output as a one-element `Vec`. Like [`Network::predict_proba`],
+    /// `input` is the sample as the network sees it, so scale raw measurements
+    /// with [`Network::scaler`] first. The output is the network's own, before
+    /// any [`PlattScaling`]. This is the forward pass training runs, so the
+    /// values are exactly what the network computes.
+    ///
+    /// Unlike a prediction, which keeps one layer at a time, every layer is
+    /// kept and copied out: the memory is the sum of the layer widths (8 bytes
+    /// each) on top of the pre-activations the pass allocates on the way. That
+    /// is small for one sample, but calling it for every row of a large
+    /// dataset holds all of it at once if the results are kept.
+    ///
+    /// ```
+    /// use water_potability_nn::neural_network::NetworkBuilder;
+    ///
+    /// let network = NetworkBuilder::new(2).hidden_layers(&[4, 3]).seed(1).build()?;
+    /// let layers = network.forward_verbose(&[0.5, -1.0])?;
+    /// assert_eq!(layers.iter().map(Vec::len).collect::<Vec<_>>(), vec![4, 3, 1]);
+    /// # Ok::<(), water_potability_nn::NnError>(())
+    /// ```
+    pub fn forward_verbose(&self, input: &[f64]) -> Result<Vec<Vec<f64>>, NnError> {
+        self.check_input_dim(input.len())?;
+        let x = Array2::from_shape_vec((1, input.len()), input.to_vec())?;
+        let (_, activations, output) = self.forward(&x);
+        let mut layers: Vec<Vec<f64>> = activations.iter().map(|layer| layer.iter().map(|&v| widen(v)).collect()).collect();
+        layers.push(output.into_raw_vec());
+        Ok(layers)
+    }
+
+    // Returns the hidden pre-activations, hidden activations and output probabilities
+    pub(crate) fn forward(&self, x: &Array2<f64>) -> (Vec<Array2<Float>>, Vec<Array2<Float>>, Array2<f64>) {
+        let x = to_float(x);
+        let hidden_layers = self.hidden_layers();
+        let mut activations: Vec<Array2<Float>> = Vec::with_capacity(hidden_layers);
+        let mut z_values = Vec::with_capacity(hidden_layers);
+
+        // One matrix multiply per layer (a·W + b)
+        for i in 0..hidden_layers {
+            let input = if i == 0 { &*x } else { &activations[i-1] };
+            let z = affine(input, &self.weights[i], &self.biases[i]);
+            activations.push(self.activation.apply(&z));
+            z_values.push(z);
+        }
+
+        let y_pred = self.output_layer(&activations[hidden_layers - 1]);
+        (z_values, activations, y_pred)
+    }
+
+    // Outputs for `input`, like `forward` but without keeping the
+    // intermediate layers
+    fn outputs(&self, input: &Array2<Float>) -> Array2<f64> {
+        let mut h = Cow::Borrowed(input);
+        for i in 0..self.hidden_layers() {
+            let mut z = affine(&h, &self.weights[i], &self.biases[i]);
+            self.activation.apply_in_place(&mut z);
+            h = Cow::Owned(z);
+        }
+        self.output_layer(&h)
+    }
+
+    fn output_layer(&self, last_hidden: &Array2<Float>) -> Array2<f64> {
+        let output = self.hidden_layers();
+        let z = affine(last_hidden, &self.weights[output], &self.biases[output]);
+        to_f64(match self.task {
+            TaskType::BinaryClassification => sigmoid(&z),
+            TaskType::Regression => z,
+        })
+    }
+
+    // Backpropagation of one forward pass, gradients of the weights and biases
+    // of every layer including the regularization terms. Sample weights scale
+    // each sample's error, matching LossFn::weighted_loss
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn gradients(
+        &self,
+        x: &Array2<f64>,
+        y_true: &Array2<f64>,
+        weights: Option<&Array2<f64>>,
+        z_values: &[Array2<Float>],
+        activations: &[Array2<Float>],
+        y_pred: &Array2<f64>,
+        params: &TrainingParams,
+    ) -> (Vec<Array2<Float>>, Vec<Array2<Float>>) {
+        let (x, y_true, y_pred) = (to_float(x), to_float(y_true), to_float(y_pred));
+        let hidden_layers = self.hidden_layers();
+        // Gradients are collected from the output layer backwards
+        let mut dw = Vec::with_capacity(hidden_layers + 1);
+        let mut db = Vec::with_capacity(hidden_layers + 1);
+
+        // Output layer error
+        let mut delta = params.loss.output_delta(&y_pred, &y_true, self.task);
+        let batch_len = match weights {
+            Some(weights) => {
+                let weights = to_float(weights);
+                delta *= &*weights;
+                weights.sum().max(Float::MIN_POSITIVE)
+            }
+            None => x.nrows() as Float,
+        };
+
+        // Output layer gradients
+        dw.push(activations[hidden_layers - 1].t().dot(&delta) / batch_len);
+        db.push((delta.sum_axis(Axis(0)) / batch_len).insert_axis(Axis(0)));
+
+        // Hidden layers gradients
+        for i in (0..hidden_layers).rev() {
+            delta = delta.dot(&self.weights[i+1].t()) * self.activation.derivative(&z_values[i]);
+
+            let input = if i == 0 { &*x } else { &activations[i-1] };
+
+            dw.push(input.t().dot(&delta) / batch_len);
+            db.push((delta.sum_axis(Axis(0)) / batch_len).insert_axis(Axis(0)));
+        }
+        dw.reverse();
+        db.reverse();
+
+        // Regularization gradients, using sign(0) = 0 as the L1 subgradient
+        if params.l1_lambda != 0.0 || params.l2_lambda != 0.0 {
+            let (l1_lambda, l2_lambda) = (params.l1_lambda as Float, params.l2_lambda as Float);
+            for (grad, w) in dw.iter_mut().zip(&self.weights) {
+                grad.zip_mut_with(w, |g, &w| {
+                    let sign = if w == 0.0 { 0.0 } else { w.signum() };
+                    *g += l1_lambda * sign + l2_lambda * w;
+                });
+            }
+        }
+        (dw, db)
+    }
+
+    // Gradient of the output's pre-activation (the logit, or the value of a
+    // regression network) with respect to every input of every row of `x`
+    pub(crate) fn input_gradient(&self, x: &Array2<f64>) -> Array2<f64> {
+        let (z_values, _, _) = self.forward(x);
+        let mut delta = Array2::<Float>::ones((x.nrows(), 1));
+        for i in (0..=self.hidden_layers()).rev() {
+            delta = delta.dot(&self.weights[i].t());
+            if i > 0 {
+                delta *= &self.activation.derivative(&z_values[i - 1]);
+            }
+        }
+        to_f64(delta)
+    }
+
+    // Whether every weight and bias is a finite number
+    fn is_finite(&self) -> bool {
+        self.weights.iter().chain(&self.biases).all(|layer| layer.iter().all(|v| v.is_finite()))
+    }
+
+    pub(crate) fn apply_gradients(&mut self, dw: &[Array2<Float>], db: &[Array2<Float>], lr: f64, frozen: &[usize]) {
+        let lr = lr as Float;
+        for i in 0..self.weights.len() {
+            if frozen.contains(&i) {
+                continue;
+            }
+            match self.optimizer {
+                Optimizer::Sgd => {
+                    self.weights[i].scaled_add(-lr, &dw[i]);
+                    self.biases[i].scaled_add(-lr, &db[i]);
+                }
+                Optimizer::Momentum { beta } => {
+                    let beta = beta as Float;
+                    self.velocity_w[i] *= beta;
+                    self.velocity_w[i] += &dw[i];
+                    self.velocity_b[i] *= beta;
+                    self.velocity_b[i] += &db[i];
+                    self.weights[i].scaled_add(-lr, &self.velocity_w[i]);
+                    self.biases[i].scaled_add(-lr, &self.velocity_b[i]);
+                }
+            }
+        }
+    }
+}
+
+/// Platt scaling: maps an output probability `p` with logit `z` to
+/// `sigmoid(a * z + b)`, a logistic regression fitted on held-out data that
+/// tempers over- or under-confident outputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlattScaling {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl PlattScaling {
+    /// Fits `a` and `b` by Newton's method on uncalibrated probabilities and
+    /// 0/1 labels, both shaped (n, 1), using Platt's smoothed targets.
+    pub fn fit(probabilities: &Array2<f64>, labels: &Array2<f64>) -> Self {
+        let positives = labels.iter().filter(|&&y| y >= 0.5).count() as f64;
+        let negatives = labels.len() as f64 - positives;
+        let (target_pos, target_neg) = ((positives + 1.0) / (positives + 2.0), 1.0 / (negatives + 2.0));
+        let samples: Vec<(f64, f64)> = probabilities
+            .iter()
+            .zip(labels.iter())
+            .map(|(&p, &y)| (logit(p), if y >= 0.5 { target_pos } else { target_neg }))
+            .collect();
+
+        let (mut a, mut b) = (1.0, 0.0);
+        for _ in 0..100 {
+            // Gradient and Hessian of the cross-entropy in (a, b)
+            let (mut ga, mut gb, mut haa, mut hab, mut hbb) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for &(z, t) in &samples {
+                let p = 1.0 / (1.0 + (-(a * z + b)).exp());
+                let w = (p * (1.0 - p)).max(1e-12);
+                ga += (p - t) * z;
+                gb += p - t;
+                haa += w * z * z;
+                hab += w * z;
+                hbb += w;
+            }
+            let det = haa * hbb - hab * hab;
+            if det.abs() < 1e-12 {
+                break;
+            }
+            let (da, db) = ((hbb * ga - hab * gb) / det, (haa * gb - hab * ga) / det);
+            a -= da;
+            b -= db;
+            if da.abs() < 1e-10 && db.abs() < 1e-10 {
+                break;
+            }
+        }
+        Self { a, b }
+    }
+
+    pub fn apply(&self, probability: f64) -> f64 {
+        1.0 / (1.0 + (-(self.a * logit(probability) + self.b)).exp())
+    }
+}
+
+// Inverse sigmoid, clipped so saturated outputs stay finite
+fn logit(p: f64) -> f64 {
+    let p = p.clamp(1e-15, 1.0 - 1e-15);
+    (p / (1.0 - p)).ln()
+}
+
+/// Architecture overview returned by [`Network::summary`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkSummary {
+    /// `(inputs, outputs)` of every weight matrix, hidden layers first, output layer last.
+    pub layer_shapes: Vec<(usize, usize)>,
+    /// Weights plus biases.
+    pub total_parameters: usize,
+}
+
+impl NetworkSummary {
+    pub fn hidden_layer_sizes(&self) -> Vec<usize> {
+        self.layer_shapes[..self.layer_shapes.len() - 1].iter().map(|&(_, outputs)| outputs).collect()
+    }
+}
+
+impl fmt::Display for NetworkSummary {
+    /// e.g. "2 hidden layers (32, 32 neurons), 1,409 parameters"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sizes = self.hidden_layer_sizes();
+        let sizes_text = sizes.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ");
+        write!(
+            f,
+            "{} hidden layer{} ({} neurons), {} parameters",
+            sizes.len(),
+            if sizes.len() == 1 { "" } else { "s" },
+            sizes_text,
+            group_thousands(self.total_parameters),
+        )
+    }
+}
+
+fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Configures and builds a [`Network`].
+#[derive(Clone, Debug)]
+pub struct NetworkBuilder {
+    input_dim: usize,
+    hidden_layers: Vec<usize>,
+    activation: Activation,
+    task: TaskType,
+    optimizer: Optimizer,
+    init: Init,
+    seed: Option<u64>,
+}
+
+impl NetworkBuilder {
+    /// Starts from the default architecture (two ReLU layers of 32 neurons, plain SGD).
+    pub fn new(input_dim: usize) -> Self {
+        Self {
+            input_dim,
+            hidden_layers: vec![DEFAULT_HIDDEN; DEFAULT_HIDDEN_LAYERS],
+            activation: Activation::Relu,
+            task: TaskType::default(),
+            optimizer: Optimizer::Sgd,
+            init: Init::SmallNormal,
+            seed: None,
+        }
+    }
+
+    /// Sizes of the hidden layers, from input to output.
+    pub fn hidden_layers(mut self, sizes: &[usize]) -> Self {
+        self.hidden_layers = sizes.to_vec();
+        self
+    }
+
+    pub fn activation(mut self, activation: Activation) -> Self {
+        self.activation = activation;
+        self
+    }
+
+    /// Output head: a sigmoid for classification (the default), linear for regression.
+    pub fn task(mut self, task: TaskType) -> Self {
+        self.task = task;
+        self
+    }
+
+    pub fn optimizer(mut self, optimizer: Optimizer) -> Self {
+        self.optimizer = optimizer;
+        self
+    }
+
+    pub fn init(mut self, init: Init) -> Self {
+        self.init = init;
+        self
+    }
+
+    /// Seeds the weight initialisation so repeated builds are identical.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> Result<Network, NnError> {
+        // Guard against bad parameters
+        if self.input_dim == 0 {
+            return Err(NnError::Config("Input dimension must be at least 1".to_string()));
+        }
+        if self.hidden_layers.is_empty() {
+            return Err(NnError::Config("Number of hidden layers must be at least 1".to_string()));
+        }
+        if self.hidden_layers.contains(&0) {
+            return Err(NnError::Config("Number of neurons per layer must be at least 1".to_string()));
+        }
+
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let mut sizes = vec![self.input_dim];
+        sizes.extend(&self.hidden_layers);
+        sizes.push(1);
+
+        let mut weights = Vec::with_capacity(sizes.len() - 1);
+        let mut biases = Vec::with_capacity(sizes.len() - 1);
+        for pair in sizes.windows(2) {
+            let (fan_in, fan_out) = (pair[0], pair[1]);
+            let scale = match self.init {
+                Init::SmallNormal => 0.1,
+                Init::He => (2.0 / fan_in as Float).sqrt(),
+                Init::Xavier => (1.0 / fan_in as Float).sqrt(),
+            };
+            weights.push(Array2::random_using((fan_in, fan_out), StandardNormal, &mut rng) * scale);
+            biases.push(Array2::zeros((1, fan_out)));
+        }
+
+        let velocity_w = weights.iter().map(|w| Array2::zeros(w.raw_dim())).collect();
+        let velocity_b = biases.iter().map(|b| Array2::zeros(b.raw_dim())).collect();
+
+        Ok(Network {
+            weights,
+            biases,
+            activation: self.activation,
+            task: self.task,
+            optimizer: self.optimizer,
+            velocity_w,
+            velocity_b,
+            scaler: None,
+            calibration: None,
+        })
+    }
+}
+
+/// Distribution of one hidden layer's activations over a training epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerStats {
+    /// Fraction of activations that are exactly zero (dead ReLU units show up here).
+    pub zero_fraction: f64,
+    pub mean: f64,
+    pub std: f64,
+}
+
+impl LayerStats {
+    fn from_activations(a: &Array2<Float>) -> Self {
+        let n = a.len().max(1) as f64;
+        let mean = a.iter().map(|&v| widen(v)).sum::<f64>() / n;
+        Self {
+            zero_fraction: a.iter().filter(|&&v| v == 0.0).count() as f64 / n,
+            mean,
+            std: (a.iter().map(|&v| (widen(v) - mean).powi(2)).sum::<f64>() / n).sqrt(),
+        }
+    }
+}
+
+/// Out-of-band notifications from [`train_network_with_events`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrainingEvent {
+    /// One [`LayerStats`] per hidden layer, every `activation_stats_every` epochs.
+    ActivationStats { epoch: usize, stats: Vec<LayerStats> },
+    /// `auto_reduce_lr` halved the learning rate after `epoch`; `learning_rate`
+    /// is the effective rate for the next epoch.
+    LearningRateReduced { epoch: usize, learning_rate: f64 },
+    /// L2 norm of all weight and bias gradients of the epoch (root mean square
+    /// over its steps), when `track_gradient_norm` is set. Near zero at
+    /// convergence, growing when unstable.
+    GradientNorm { epoch: usize, norm: f64 },
+    /// The update of batch `batch` of `epoch` diverged and was handled by
+    /// `action` under [`TrainingParams::divergence_policy`]; `learning_rate`
+    /// is the rate in effect afterwards. [`DivergencePolicy::Abort`] returns
+    /// [`NnError::Diverged`] instead.
+    Diverged { epoch: usize, batch: usize, action: DivergencePolicy, learning_rate: f64 },
+    /// Loss on the validation set after `epoch`, from [`train_network_validated`].
+    ValidationLoss { epoch: usize, loss: f64 },
+    /// Early stopping ended training after `epoch`; the monitored loss was
+    /// lowest after `best_epoch`.
+    EarlyStopped { epoch: usize, best_epoch: usize, best_loss: f64 },
+    /// Sent once after the last epoch, with or without early stopping: the
+    /// epoch with the lowest monitored loss (drops under `min_delta` do not
+    /// count). `validation` tells whether that is the validation loss or, for
+    /// runs without a validation set, the training loss.
+    BestEpoch { epoch: usize, loss: f64, validation: bool },
+    /// The predicted probabilities on the validation set (the training data
+    /// without one) have had a standard deviation under [`COLLAPSE_STD`] for
+    /// [`COLLAPSE_EPOCHS`] epochs up to `epoch`: the network gives every
+    /// sample about the same answer, typically the majority class. `mean` is
+    /// that answer. Sent again only after the outputs spread out and collapse anew.
+    OutputCollapsed { epoch: usize, mean: f64, std: f64 },
+}
+
+/// Result of a single-sample prediction.
+///
+/// `is_potable` and `probability` keep their meaning for the binary and
+/// regression tasks. The class fields are reserved for networks choosing
+/// among more than two classes, such as tiers of water quality: for those
+/// `class_index` is set, `probability` is the predicted class's probability
+/// and `is_potable` is false. Every task this crate trains today is binary or
+/// regression, so the class fields are always `None` and nothing displays
+/// them yet.
+#[derive(Clone, Debug)]
+pub struct PredictionResult {
+    pub is_potable: bool,
+    /// Probability that the sample is potable, in [0, 1]. For a regression
+    /// network, the predicted value instead, and `is_potable` is false.
+    pub probability: f64,
+    /// Task of the network that made the prediction.
+    pub task: TaskType,
+    /// Index of the predicted class of a multi-class prediction.
+    pub class_index: Option<usize>,
+    /// Display name of `class_index`, if the classes are named.
+    pub class_name: Option<String>,
+}
+
+impl PredictionResult {
+    /// Probability of the not-potable class, the complement of `probability`.
+    pub fn not_potable_probability(&self) -> f64 {
+        1.0 - self.probability
+    }
+
+    /// Label of the predicted class of a multi-class prediction: its name,
+    /// or `class <index>` for an unnamed class. `None` for binary and
+    /// regression predictions.
+    pub fn class_label(&self) -> Option<String> {
+        let index = self.class_index?;
+        Some(self.class_name.clone().unwrap_or_else(|| format!("class {}", index)))
+    }
+
+    /// Short outcome for tables and exports: `potable` or `not potable`, or
+    /// `None` for a regression value.
+    pub fn verdict(&self) -> Option<String> {
+        match self.task {
+            TaskType::BinaryClassification => Some(if self.is_potable { "potable" } else { "not potable" }.to_string()),
+            TaskType::Regression => None,
+        }
+    }
+}
+
+/// Verdict followed by both class probabilities, as shown by the frontends,
+/// or the predicted value of a regression network.
+///
+/// ```
+/// use water_potability_nn::neural_network::{PredictionResult, TaskType};
+///
+/// let result = PredictionResult {
+///     is_potable: true,
+///     probability: 0.725,
+///     task: TaskType::BinaryClassification,
+///     class_index: None,
+///     class_name: None,
+/// };
+/// assert_eq!(result.to_string(), "POTABLE - P(potable) = 72.50%, P(not potable) = 27.50%");
+///
+/// let score = PredictionResult { probability: 6.25, task: TaskType::Regression, is_potable: false, ..result.clone() };
+/// assert_eq!(score.to_string(), "Predicted value = 6.2500");
+/// ```
+impl fmt::Display for PredictionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.task == TaskType::Regression {
+            return write!(f, "Predicted value = {:.4}", self.probability);
+        }
+        write!(
+            f,
+            "{} - P(potable) = {:.2}%, P(not potable) = {:.2}%",
+            if self.is_potable { "POTABLE" } else { "NOT POTABLE" },
+            self.probability * 100.0,
+            self.not_potable_probability() * 100.0
+        )
+    }
+}
+
+/// The single potable/not-potable decision used by both the training accuracy
+/// and `make_prediction`. Ties go to potable: exactly 0.5 counts as potable.
+///
+/// ```
+/// use water_potability_nn::neural_network::is_potable;
+///
+/// assert!(is_potable(0.5));
+/// assert!(!is_potable(0.5 - f64::EPSILON));
+/// ```
+pub fn is_potable(probability: f64) -> bool {
+    probability >= DECISION_THRESHOLD
+}
+
+/// A network output as text with the digits the network's [`Float`] holds:
+/// in full for `f64`, and as the shortest `f32` that rounds to it with the
+/// `f32` feature, rather than the widening noise of printing that as `f64`.
+///
+/// ```
+/// use water_potability_nn::neural_network::format_output;
+///
+/// assert_eq!(format_output(0.25), "0.25");
+/// ```
+pub fn format_output(value: f64) -> String {
+    (value as Float).to_string()
+}
+
+// One layer's a·W + b, for one row or a whole batch. ndarray's matrix
+// multiply picks AVX and FMA kernels at run time where the CPU has them.
+fn affine(input: &Array2<Float>, weights: &Array2<Float>, bias: &Array2<Float>) -> Array2<Float> {
+    input.dot(weights) + bias
+}
+
+fn sigmoid(x: &Array2<Float>) -> Array2<Float> {
+    x.mapv(logistic)
+}
+
+fn logistic(v: Float) -> Float {
+    1.0 / (1.0 + (-v).exp())
+}
+
+// f64 data as the network's Float, borrowed unless the `f32` feature needs a
+// converted copy
+#[cfg(not(feature = "f32"))]
+fn to_float(x: &Array2<f64>) -> Cow<'_, Array2<Float>> {
+    Cow::Borrowed(x)
+}
+
+#[cfg(feature = "f32")]
+fn to_float(x: &Array2<f64>) -> Cow<'_, Array2<Float>> {
+    Cow::Owned(x.mapv(|v| v as Float))
+}
+
+// The network's Float outputs as f64, a no-op without the `f32` feature
+#[cfg(not(feature = "f32"))]
+fn to_f64(x: Array2<Float>) -> Array2<f64> {
+    x
+}
+
+#[cfg(feature = "f32")]
+fn to_f64(x: Array2<Float>) -> Array2<f64> {
+    x.mapv(widen)
+}
+
+#[cfg(not(feature = "f32"))]
+fn widen(v: Float) -> f64 {
+    v
+}
+
+#[cfg(feature = "f32")]
+fn widen(v: Float) -> f64 {
+    f64::from(v)
+}
+
+/// Mean binary cross-entropy of predicted probabilities against 0/1 labels,
+/// both shaped (n, 1). Probabilities are clipped away from 0 and 1.
+pub fn binary_cross_entropy(y_pred: &Array2<f64>, y_true: &Array2<f64>) -> f64 {
+    cross_entropy_per_sample(y_pred, y_true).sum() / y_true.nrows().max(1) as f64
+}
+
+fn cross_entropy_per_sample(y_pred: &Array2<f64>, y_true: &Array2<f64>) -> Array2<f64> {
+    let epsilon = 1e-15; // To prevent log(0)
+    let y_pred_clipped = y_pred.mapv(|v| v.max(epsilon).min(1.0 - epsilon));
+    -y_true * &y_pred_clipped.mapv(|v| v.ln()) - (1.0 - y_true) * &(1.0 - &y_pred_clipped).mapv(|v| v.ln())
+}
+
+/// Builds the default ReLU network with `params.input_dim` inputs and the hidden
+/// layer sizes in `params`.
+pub fn create_network(params: &TrainingParams) -> Result<Network, NnError> {
+    let mut builder = NetworkBuilder::new(params.input_dim)
+        .hidden_layers(&vec![params.neurons_per_layer; params.hidden_layers])
+        .task(params.task);
+    if let Some(seed) = params.seed {
+        builder = builder.seed(seed);
+    }
+    builder.build()
+}
+
+/// Learning rate used for `epoch` (counted from 0): a linear warm-up over
+/// `warmup_epochs`, then the step schedule that decays `learning_rate`.
+pub fn learning_rate_at(params: &TrainingParams, epoch: usize) -> f64 {
+    if epoch < params.warmup_epochs {
+        // Ramp from lr / warmup_epochs up to lr
+        params.learning_rate * (epoch + 1) as f64 / params.warmup_epochs as f64
+    } else if epoch < params.epochs / 10 {
+        params.learning_rate  // High learning rate for first 10%
+    } else if epoch < params.epochs / 2 {
+        params.learning_rate * 0.5  // Medium learning rate until halfway
+    } else {
+        params.learning_rate * 0.1  // Low learning rate for final half
+    }
+}
+
+/// Where training stands after an epoch, passed to [`TrainingCallback::on_epoch_end`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochState {
+    /// The epoch that just finished, counting from 0.
+    pub epoch: usize,
+    /// Epochs the run is configured for; early stopping may end it sooner.
+    pub epochs: usize,
+    /// Training accuracy in percent, or R² for [`TaskType::Regression`],
+    /// over the epoch's batches. Each
+    /// batch is scored before its own step, and with shuffled mini-batches on
+    /// different rows every epoch; see `validation_accuracy` for a measure on
+    /// fixed data.
+    pub accuracy: f64,
+    /// Mean `params.loss` over the training data plus any L1/L2 weight
+    /// penalty, measured like `accuracy`.
+    pub loss: f64,
+    /// Learning rate of the epoch's steps, after warmup, the schedule and any
+    /// `auto_reduce_lr` reductions.
+    pub learning_rate: f64,
+    /// Loss on the validation set after the epoch, when training with
+    /// [`train_network_validated`]. The same rows are scored every epoch,
+    /// without any weight penalty.
+    pub validation_loss: Option<f64>,
+    /// Accuracy in percent (R² for regression) on the validation set,
+    /// measured with `validation_loss`.
+    pub validation_accuracy: Option<f64>,
+}
+
+/// Hook into the training loop, called once per epoch. Implement it for
+/// logging, exporting metrics or custom stopping rules; plain closures taking
+/// `(epoch, accuracy, loss)` implement it too.
+pub trait TrainingCallback {
+    /// Whether to call [`on_train_start`](Self::on_train_start). Scoring the
+    /// initial weights takes an extra pass over the data, so it only happens
+    /// when a callback asks for it.
+    fn wants_baseline(&self) -> bool {
+        false
+    }
+
+    /// Called once before the first update with the scores of the initial
+    /// weights, the origin of the learning curve, when
+    /// [`wants_baseline`](Self::wants_baseline) says so. They are measured on
+    /// all of the training data at once, without input noise, and on the
+    /// validation set when there is one; no epoch has run yet, and `epoch` is
+    /// 0. With one full batch and no noise the first epoch's training scores
+    /// repeat these, as each batch is scored before its step.
+    fn on_train_start(&mut self, _baseline: &EpochState) {}
+
+    fn on_epoch_end(&mut self, state: &EpochState);
+
+    /// Asked after [`on_epoch_end`](Self::on_epoch_end); returning true ends
+    /// training after this epoch.
+    fn should_stop(&mut self, _state: &EpochState) -> bool {
+        false
+    }
+
+    /// Called once when the training loop ends, with its outcome or the error
+    /// that ended it.
+    fn on_train_end(&mut self, _result: Result<&TrainingOutcome, &NnError>) {}
+}
+
+impl<F: FnMut(usize, f64, f64)> TrainingCallback for F {
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        self(state.epoch, state.accuracy, state.loss)
+    }
+}
+
+/// Both callbacks see every epoch; training stops when either asks to.
+impl<A: TrainingCallback, B: TrainingCallback> TrainingCallback for (A, B) {
+    fn wants_baseline(&self) -> bool {
+        self.0.wants_baseline() || self.1.wants_baseline()
+    }
+
+    fn on_train_start(&mut self, baseline: &EpochState) {
+        self.0.on_train_start(baseline);
+        self.1.on_train_start(baseline);
+    }
+
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        self.0.on_epoch_end(state);
+        self.1.on_epoch_end(state);
+    }
+
+    fn should_stop(&mut self, state: &EpochState) -> bool {
+        // Both are asked, so neither misses an epoch
+        let first = self.0.should_stop(state);
+        self.1.should_stop(state) || first
+    }
+
+    fn on_train_end(&mut self, result: Result<&TrainingOutcome, &NnError>) {
+        self.0.on_train_end(result);
+        self.1.on_train_end(result);
+    }
+}
+
+/// Adapts a callback to receive the validation metrics in place of the
+/// training ones, so the reported curve comes from the same held-out rows
+/// every epoch, the baseline included. Only called when there is a validation
+/// set, i.e. with [`train_network_validated`].
+///
+/// ```
+/// use water_potability_nn::neural_network::{EpochState, TrainingCallback, ValidationMetrics};
+///
+/// let mut reported = Vec::new();
+/// let mut callback = ValidationMetrics(|epoch, accuracy, loss| reported.push((epoch, accuracy, loss)));
+/// let state = EpochState {
+///     epoch: 3, epochs: 10, accuracy: 90.0, loss: 0.2, learning_rate: 0.1,
+///     validation_loss: Some(0.4), validation_accuracy: Some(75.0),
+/// };
+/// callback.on_epoch_end(&state);
+/// callback.on_epoch_end(&EpochState { validation_loss: None, validation_accuracy: None, ..state });
+/// assert_eq!(reported, [(3, 75.0, 0.4)]);
+/// ```
+pub struct ValidationMetrics<F>(pub F);
+
+/// Adapts an `(epoch, accuracy, loss)` closure to also receive the baseline
+/// of [`TrainingCallback::on_train_start`], so a plotted curve starts at the
+/// untrained network. The baseline comes first with `None` as its epoch, then
+/// every epoch with `Some(epoch)`.
+///
+/// ```
+/// use ndarray::array;
+/// use water_potability_nn::data_loader::Dataset;
+/// use water_potability_nn::neural_network::{train_network, NetworkBuilder, TrainingParams, WithBaseline};
+///
+/// let dataset = Dataset { features: array![[0.0], [1.0]], labels: array![[0.0], [1.0]], weights: None };
+/// let mut network = NetworkBuilder::new(1).hidden_layers(&[4]).seed(1).build().unwrap();
+/// let params = TrainingParams { epochs: 3, ..TrainingParams::default() };
+/// let mut epochs = Vec::new();
+/// train_network(&mut network, &dataset, &params, WithBaseline(|epoch, _, _| epochs.push(epoch))).unwrap();
+/// assert_eq!(epochs, [None, Some(0), Some(1), Some(2)]);
+/// ```
+pub struct WithBaseline<F>(pub F);
+
+impl<F: FnMut(Option<usize>, f64, f64)> TrainingCallback for WithBaseline<F> {
+    fn wants_baseline(&self) -> bool {
+        true
+    }
+
+    fn on_train_start(&mut self, baseline: &EpochState) {
+        (self.0)(None, baseline.accuracy, baseline.loss)
+    }
+
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        (self.0)(Some(state.epoch), state.accuracy, state.loss)
+    }
+}
+
+// The state with its validation metrics in place of the training ones, if it has them
+fn validation_view(state: &EpochState) -> Option<EpochState> {
+    let (accuracy, loss) = (state.validation_accuracy?, state.validation_loss?);
+    Some(EpochState { accuracy, loss, ..state.clone() })
+}
+
+impl<C: TrainingCallback> TrainingCallback for ValidationMetrics<C> {
+    fn wants_baseline(&self) -> bool {
+        self.0.wants_baseline()
+    }
+
+    fn on_train_start(&mut self, baseline: &EpochState) {
+        if let Some(baseline) = validation_view(baseline) {
+            self.0.on_train_start(&baseline);
+        }
+    }
+
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        if let Some(state) = validation_view(state) {
+            self.0.on_epoch_end(&state);
+        }
+    }
+
+    fn should_stop(&mut self, state: &EpochState) -> bool {
+        validation_view(state).is_some_and(|state| self.0.should_stop(&state))
+    }
+
+    fn on_train_end(&mut self, result: Result<&TrainingOutcome, &NnError>) {
+        self.0.on_train_end(result);
+    }
+}
+
+/// An absent callback does nothing.
+impl<C: TrainingCallback> TrainingCallback for Option<C> {
+    fn wants_baseline(&self) -> bool {
+        self.as_ref().is_some_and(|callback| callback.wants_baseline())
+    }
+
+    fn on_train_start(&mut self, baseline: &EpochState) {
+        if let Some(callback) = self {
+            callback.on_train_start(baseline);
+        }
+    }
+
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        if let Some(callback) = self {
+            callback.on_epoch_end(state);
+        }
+    }
+
+    fn should_stop(&mut self, state: &EpochState) -> bool {
+        self.as_mut().is_some_and(|callback| callback.should_stop(state))
+    }
+
+    fn on_train_end(&mut self, result: Result<&TrainingOutcome, &NnError>) {
+        if let Some(callback) = self {
+            callback.on_train_end(result);
+        }
+    }
+}
+
+impl TrainingCallback for &mut dyn TrainingCallback {
+    fn wants_baseline(&self) -> bool {
+        (**self).wants_baseline()
+    }
+
+    fn on_train_start(&mut self, baseline: &EpochState) {
+        (**self).on_train_start(baseline)
+    }
+
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        (**self).on_epoch_end(state)
+    }
+
+    fn should_stop(&mut self, state: &EpochState) -> bool {
+        (**self).should_stop(state)
+    }
+
+    fn on_train_end(&mut self, result: Result<&TrainingOutcome, &NnError>) {
+        (**self).on_train_end(result)
+    }
+}
+
+/// Why training ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// All `params.epochs` epochs ran.
+    Completed,
+    /// The monitored loss stopped improving for `early_stopping_patience` epochs.
+    EarlyStopped,
+    /// The callback's [`TrainingCallback::should_stop`] asked to stop.
+    Cancelled,
+    /// Training ran past `params.time_limit`.
+    TimedOut,
+    /// The score reached `params.target_accuracy`.
+    TargetReached,
+}
+
+/// Summary of a finished run, returned by the training functions. A run
+/// whose loss diverges returns [`NnError::Diverged`] instead, as its weights
+/// are no longer usable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainingOutcome {
+    pub epochs_run: usize,
+    /// Epoch with the lowest monitored loss, as in [`TrainingEvent::BestEpoch`].
+    /// `None` when no epoch ran.
+    pub best_epoch: Option<usize>,
+    /// State after the last epoch that ran, `None` when no epoch ran.
+    pub final_metrics: Option<EpochState>,
+    pub stopped_reason: StopReason,
+}
+
+/// Trains `network` in place with gradient descent and reports how the run
+/// went.
+///
+/// `callback` sees every finished epoch; see [`EpochState`] for what it
+/// reports. A closure is called with `(epoch, accuracy, loss)`, where `epoch`
+/// counts from 0, `accuracy` is the training accuracy in percent and `loss` is
+/// the mean `params.loss` over `data` plus any L1/L2 weight penalty. For
+/// metrics measured on fixed held-out data instead, train with
+/// [`train_network_validated`] and wrap the closure in [`ValidationMetrics`].
+/// A callback that asks for it receives the scores of the initial weights in
+/// [`TrainingCallback::on_train_start`]; wrap a closure in [`WithBaseline`] to
+/// get them as its first call.
+pub fn train_network<D, C>(
+    network: &mut Network,
+    data: &D,
+    params: &TrainingParams,
+    callback: C,
+) -> Result<TrainingOutcome, NnError>
+where
+    D: DataSource,
+    C: TrainingCallback,
+{
+    train_network_with_events(network, data, params, callback, |_| {})
+}
+
+/// Like [`train_network`], additionally reporting [`TrainingEvent`]s to `on_event`.
+/// Activation statistics come from the forward pass training already performs.
+///
+/// Each epoch takes one gradient step per batch from [`DataSource::batches`]
+/// (`params.batch_size`, reshuffled per epoch when `params.shuffle_batches`).
+/// The reported accuracy and loss are averaged over the epoch's batches, each
+/// measured before its own step; with the default full batch that is exactly
+/// the state at the start of the epoch.
+pub fn train_network_with_events<D, C, E>(
+    network: &mut Network,
+    data: &D,
+    params: &TrainingParams,
+    callback: C,
+    on_event: E,
+) -> Result<TrainingOutcome, NnError>
+where
+    D: DataSource,
+    C: TrainingCallback,
+    E: FnMut(TrainingEvent),
+{
+    run_training(network, data, None, params, callback, on_event)
+}
+
+/// Like [`train_network_with_events`], additionally measuring the loss and
+/// accuracy on `validation` after every epoch, in
+/// [`EpochState::validation_loss`] and [`EpochState::validation_accuracy`].
+/// The loss is also reported as [`TrainingEvent::ValidationLoss`] and is what
+/// early stopping monitors. `validation` must be scaled like `data`.
+pub fn train_network_validated<D, C, E>(
+    network: &mut Network,
+    data: &D,
+    validation: &Dataset,
+    params: &TrainingParams,
+    callback: C,
+    on_event: E,
+) -> Result<TrainingOutcome, NnError>
+where
+    D: DataSource,
+    C: TrainingCallback,
+    E: FnMut(TrainingEvent),
+{
+    network.check_input_dim(validation.features.ncols())?;
+    run_training(network, data, Some(validation), params, callback, on_event)
+}
+
+fn run_training<D, C, E>(
+    network: &mut Network,
+    data: &D,
+    validation: Option<&Dataset>,
+    params: &TrainingParams,
+    mut callback: C,
+    on_event: E,
+) -> Result<TrainingOutcome, NnError>
+where
+    D: DataSource,
+    C: TrainingCallback,
+    E: FnMut(TrainingEvent),
+{
+    let result = train_epochs(network, data, validation, params, &mut callback, on_event);
+    callback.on_train_end(result.as_ref());
+    result
+}
+
+// A weight update with what is needed to take it back or redo it
+struct UndoableStep {
+    epoch: usize,
+    batch: usize,
+    before: Network,
+    dw: Vec<Array2<Float>>,
+    db: Vec<Array2<Float>>,
+    retries: usize,
+}
+
+// Takes back `step` after it led to a non-finite `loss` or weights, as
+// `params.divergence_policy` says: SkipBatch restores the weights from before
+// it, ReduceLr redoes it at half the learning rate and returns it again in
+// case that diverges too. The caller handles Abort.
+fn undo_step<E: FnMut(TrainingEvent)>(
+    network: &mut Network,
+    mut step: UndoableStep,
+    params: &TrainingParams,
+    lr_factor: &mut f64,
+    learning_rate: &mut f64,
+    loss: f64,
+    on_event: &mut E,
+) -> Result<Option<UndoableStep>, NnError> {
+    let action = params.divergence_policy;
+    network.clone_from(&step.before);
+    if action == DivergencePolicy::ReduceLr {
+        if step.retries == MAX_DIVERGENCE_RETRIES {
+            return Err(NnError::Diverged { epoch: step.epoch, loss });
+        }
+        step.retries += 1;
+        *lr_factor *= 0.5;
+        *learning_rate *= 0.5;
+    }
+    on_event(TrainingEvent::Diverged { epoch: step.epoch, batch: step.batch, action, learning_rate: *learning_rate });
+    if action == DivergencePolicy::SkipBatch {
+        return Ok(None);
+    }
+    network.apply_gradients(&step.dw, &step.db, *learning_rate, &params.frozen_layers);
+    Ok(Some(step))
+}
+
+fn train_epochs<D, C, E>(
+    network: &mut Network,
+    data: &D,
+    validation: Option<&Dataset>,
+    params: &TrainingParams,
+    callback: &mut C,
+    mut on_event: E,
+) -> Result<TrainingOutcome, NnError>
+where
+    D: DataSource,
+    C: TrainingCallback,
+    E: FnMut(TrainingEvent),
+{
+    let n_samples = data.n_samples();
+    check_sample_count(n_samples)?;
+    network.check_input_dim(data.n_features())?;
+    let summary = network.summary();
+    check_memory(memory_estimate(&summary.layer_shapes, data.n_features(), n_samples, params.batch_size), params.max_memory)?;
+
+    check_frozen_layers(network, params)?;
+    // Validation inputs as the network's Float, converted once per run
+    let validation = validation.map(|validation| (validation, to_float(&validation.features)));
+    let shuffle_seed = params.seed.unwrap_or_else(rand::random);
+    // Its own stream, so noise does not change which samples share a batch
+    let mut noise_rng = StdRng::seed_from_u64(!shuffle_seed);
+    
+    // Divergence tracking for auto_reduce_lr
+    let mut lr_factor = 1.0;
+    let mut previous_loss = f64::INFINITY;
+    let mut rising_epochs = 0;
+    
+    // Early stopping state: best monitored loss, where it occurred, epochs since
+    let mut best_loss = f64::INFINITY;
+    let mut best_epoch = 0;
+    let mut stalled_epochs = 0;
+    
+    // Epochs in a row with near-constant predictions
+    let mut collapsed_epochs = 0;
+    
+    // The last update, while divergence_policy may still take it back
+    let mut last_step = None;
+    
+    let started = std::time::Instant::now();
+    let mut final_metrics = None;
+    let mut stopped_reason = StopReason::Completed;
+    
+    #[cfg(feature = "tensorboard")]
+    let mut event_writer = params.log_dir.as_deref().map(crate::tensorboard::EventWriter::create).transpose()?;
+
+    if callback.wants_baseline() {
+        callback.on_train_start(&baseline_state(network, data, validation.as_ref(), params));
+    }
+
+    // Training loop
+    for epoch in 0..params.epochs {
+        let mut learning_rate = learning_rate_at(params, epoch) * lr_factor;
+        let penalty = weight_penalty(network, params);
+        let (mut total_loss, mut total_weight, mut squared_norms, mut n_batches) = (0.0, 0.0, 0.0, 0);
+        let mut score = ScoreSums::default();
+        let (mut output_sum, mut output_squares) = (0.0, 0.0);
+        
+        let batches = data.batches(params.batch_size, params.shuffle_batches, params.batch_order_seed(shuffle_seed, epoch));
+        for (batch_index, batch) in batches.enumerate() {
+            let (x, y_true) = (&batch.features, &batch.labels);
+            let noisy;
+            let x = if params.noise_std > 0.0 {
+                let noise: Array2<f64> = Array2::random_using(x.raw_dim(), StandardNormal, &mut noise_rng);
+                noisy = x + &(noise * params.noise_std);
+                &noisy
+            } else {
+                x
+            };
+            let batch_len = x.nrows();
+            let weights = batch.weights.as_ref();
+            let (z_values, activations, y_pred, batch_loss) = loop {
+                let (z_values, activations, y_pred) = network.forward(x);
+                let batch_loss = params.loss.weighted_loss(&y_pred, y_true, weights);
+                if (batch_loss + penalty).is_finite() {
+                    break (z_values, activations, y_pred, batch_loss);
+                }
+                // The last update left weights too large to score this batch: take it back
+                // and score again, unless there is none or the policy is to abort
+                match last_step.take() {
+                    Some(step) if params.divergence_policy != DivergencePolicy::Abort => {
+                        last_step = undo_step(network, step, params, &mut lr_factor, &mut learning_rate, batch_loss + penalty, &mut on_event)?;
+                    }
+                    _ => return Err(NnError::Diverged { epoch, loss: batch_loss + penalty }),
+                }
+            };
+            
+            if n_batches == 0 && params.activation_stats_every > 0 && epoch % params.activation_stats_every == 0 {
+                on_event(TrainingEvent::ActivationStats {
+                    epoch,
+                    stats: activations.iter().map(LayerStats::from_activations).collect(),
+                });
+            }
+
+            // The epoch loss is the weighted mean over every sample
+            let batch_weight = weights.map_or(batch_len as f64, |weights| weights.sum());
+            total_loss += batch_loss * batch_weight;
+            total_weight += batch_weight;
+            output_sum += y_pred.sum();
+            output_squares += y_pred.fold(0.0, |acc, p| acc + p * p);
+            
+            let (dw, db) = network.gradients(x, y_true, weights, &z_values, &activations, &y_pred, params);
+            
+            if params.track_gradient_norm {
+                squared_norms += dw.iter().chain(&db).map(|g| g.fold(0.0, |acc, &v| acc + widen(v) * widen(v))).sum::<f64>();
+            }
+            
+            // Update weights and biases with learning rate
+            let before = (params.divergence_policy != DivergencePolicy::Abort).then(|| network.clone());
+            network.apply_gradients(&dw, &db, learning_rate, &params.frozen_layers);
+            if let Some(before) = before {
+                last_step = Some(UndoableStep { epoch, batch: batch_index, before, dw, db, retries: 0 });
+                // Non-finite weights are taken back at once, others only once they break a later batch
+                while !network.is_finite()
+                    && let Some(step) = last_step.take()
+                {
+                    last_step = undo_step(network, step, params, &mut lr_factor, &mut learning_rate, batch_loss + penalty, &mut on_event)?;
+                }
+            }
+            
+            // Score the predictions for monitoring
+            score.add(&y_pred, y_true);
+            n_batches += 1;
+        }
+        
+        let loss = total_loss / total_weight.max(f64::MIN_POSITIVE) + penalty;
+        
+        // A rising loss lowers the rate from the next epoch on
+        if params.auto_reduce_lr {
+            rising_epochs = if loss > previous_loss { rising_epochs + 1 } else { 0 };
+            if rising_epochs >= params.lr_patience.max(1) {
+                lr_factor *= 0.5;
+                rising_epochs = 0;
+                on_event(TrainingEvent::LearningRateReduced {
+                    epoch,
+                    learning_rate: learning_rate_at(params, epoch + 1) * lr_factor,
+                });
+            }
+            previous_loss = loss;
+        }
+        
+        if params.track_gradient_norm {
+            // Root mean square over the epoch's steps, the plain norm for a full batch
+            on_event(TrainingEvent::GradientNorm { epoch, norm: (squared_norms / n_batches as f64).sqrt() });
+        }
+        
+        let mut output_spread = output_mean_std(output_sum, output_squares, n_samples);
+        let validation_metrics = validation.as_ref().map(|(validation, input)| {
+            let probabilities = network.outputs(input);
+            let validation_loss = params.loss.loss(&probabilities, &validation.labels);
+            on_event(TrainingEvent::ValidationLoss { epoch, loss: validation_loss });
+            output_spread = output_mean_std(probabilities.sum(), probabilities.fold(0.0, |acc, p| acc + p * p), probabilities.len());
+            let mut validation_score = ScoreSums::default();
+            validation_score.add(&probabilities, &validation.labels);
+            let validation_accuracy = validation_score.score(network.task);
+            (validation_loss, validation_accuracy)
+        });
+        let validation_loss = validation_metrics.map(|(loss, _)| loss);
+        
+        let (mean, std) = output_spread;
+        collapsed_epochs = if std < COLLAPSE_STD { collapsed_epochs + 1 } else { 0 };
+        if collapsed_epochs == COLLAPSE_EPOCHS {
+            on_event(TrainingEvent::OutputCollapsed { epoch, mean, std });
+        }
+        
+        // Report accuracy as a percentage
+        let state = EpochState {
+            epoch,
+            epochs: params.epochs,
+            accuracy: score.score(network.task),
+            loss,
+            learning_rate,
+            validation_loss,
+            validation_accuracy: validation_metrics.map(|(_, accuracy)| accuracy),
+        };
+        callback.on_epoch_end(&state);
+        #[cfg(feature = "tensorboard")]
+        if let Some(writer) = &mut event_writer {
+            writer.write_epoch(&state)?;
+        }
+        
+        let monitored = validation_loss.unwrap_or(loss);
+        if monitored < best_loss - params.min_delta {
+            best_loss = monitored;
+            best_epoch = epoch;
+            stalled_epochs = 0;
+        } else {
+            stalled_epochs += 1;
+        }
+        let cancelled = callback.should_stop(&state);
+        let reached_target = params
+            .target_accuracy
+            .is_some_and(|target| state.validation_accuracy.unwrap_or(state.accuracy) >= target);
+        final_metrics = Some(state);
+        if reached_target && epoch + 1 < params.epochs {
+            stopped_reason = StopReason::TargetReached;
+            break;
+        }
+        if params.early_stopping_patience > 0 && stalled_epochs >= params.early_stopping_patience {
+            on_event(TrainingEvent::EarlyStopped { epoch, best_epoch, best_loss });
+            stopped_reason = StopReason::EarlyStopped;
+            break;
+        }
+        if cancelled {
+            stopped_reason = StopReason::Cancelled;
+            break;
+        }
+        if params.time_limit.is_some_and(|limit| started.elapsed() >= limit) && epoch + 1 < params.epochs {
+            stopped_reason = StopReason::TimedOut;
+            break;
+        }
+    }
+
+    if best_loss.is_finite() {
+        on_event(TrainingEvent::BestEpoch { epoch: best_epoch, loss: best_loss, validation: validation.is_some() });
+    }
+    Ok(TrainingOutcome {
+        epochs_run: final_metrics.as_ref().map_or(0, |state: &EpochState| state.epoch + 1),
+        best_epoch: best_loss.is_finite().then_some(best_epoch),
+        final_metrics,
+        stopped_reason,
+    })
+}
+
+// Scores of the weights before any update, over every batch of `data` without
+// noise and on the validation set, for TrainingCallback::on_train_start
+fn baseline_state<D: DataSource>(
+    network: &Network,
+    data: &D,
+    validation: Option<&(&Dataset, Cow<Array2<Float>>)>,
+    params: &TrainingParams,
+) -> EpochState {
+    let (mut total_loss, mut total_weight) = (0.0, 0.0);
+    let mut score = ScoreSums::default();
+    for batch in data.batches(params.batch_size, false, 0) {
+        let (_, _, y_pred) = network.forward(&batch.features);
+        let weights = batch.weights.as_ref();
+        let batch_weight = weights.map_or(batch.features.nrows() as f64, |weights| weights.sum());
+        total_loss += params.loss.weighted_loss(&y_pred, &batch.labels, weights) * batch_weight;
+        total_weight += batch_weight;
+        score.add(&y_pred, &batch.labels);
+    }
+    let validation_metrics = validation.map(|(validation, input)| {
+        let probabilities = network.outputs(input);
+        let mut validation_score = ScoreSums::default();
+        validation_score.add(&probabilities, &validation.labels);
+        (params.loss.loss(&probabilities, &validation.labels), validation_score.score(network.task))
+    });
+    EpochState {
+        epoch: 0,
+        epochs: params.epochs,
+        accuracy: score.score(network.task),
+        loss: total_loss / total_weight.max(f64::MIN_POSITIVE) + weight_penalty(network, params),
+        learning_rate: learning_rate_at(params, 0),
+        validation_loss: validation_metrics.map(|(loss, _)| loss),
+        validation_accuracy: validation_metrics.map(|(_, accuracy)| accuracy),
+    }
+}
+
+// Mean and standard deviation of n predictions from their sum and sum of squares
+fn output_mean_std(sum: f64, squares: f64, n: usize) -> (f64, f64) {
+    let n = n.max(1) as f64;
+    let mean = sum / n;
+    (mean, (squares / n - mean * mean).max(0.0).sqrt())
+}
+
+fn check_frozen_layers(network: &Network, params: &TrainingParams) -> Result<(), NnError> {
+    let hidden_layers = network.hidden_layers();
+    if let Some(&layer) = params.frozen_layers.iter().find(|&&layer| layer > hidden_layers) {
+        return Err(NnError::Config(format!("Cannot freeze layer {}, the network has {} layers", layer, hidden_layers + 1)));
+    }
+    Ok(())
+}
+
+fn check_sample_count(n_samples: usize) -> Result<(), NnError> {
+    if n_samples < MIN_TRAINING_SAMPLES {
+        return Err(NnError::TooFewSamples { purpose: "train on".to_string(), needed: MIN_TRAINING_SAMPLES, found: n_samples });
+    }
+    Ok(())
+}
+
+/// What [`dry_run`] found out about a configuration.
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    pub summary: NetworkSummary,
+    pub n_samples: usize,
+    /// Samples in the batch the forward pass was run on.
+    pub batch_size: usize,
+    /// Rough peak memory of training in bytes: the data, parameters with their
+    /// gradients and optimizer state, and one batch's activations.
+    pub estimated_bytes: usize,
+}
+
+/// Checks that `params` can train on `data` without training: validates the
+/// settings, builds the network, verifies every feature is finite and runs one
+/// forward pass on the first batch. `data` must be scaled as for training.
+pub fn dry_run<D: DataSource>(data: &D, params: &TrainingParams) -> Result<DryRun, NnError> {
+    params.validate()?;
+    params.check_data(data)?;
+    let network = create_network(params)?;
+    check_frozen_layers(&network, params)?;
+
+    let mut first_batch = None;
+    let mut offset = 0;
+    for batch in data.batches(params.batch_size, false, 0) {
+        if let Some(((row, column), value)) = batch.features.indexed_iter().find(|(_, v)| !v.is_finite()) {
+            return Err(NnError::Config(format!("Sample {} has a non-finite value ({}) in feature {}", offset + row, value, column)));
+        }
+        offset += batch.features.nrows();
+        first_batch.get_or_insert(batch);
+    }
+    let batch = first_batch.ok_or_else(|| NnError::Config("Dataset is empty".to_string()))?;
+
+    let output = network.outputs(&to_float(&batch.features));
+    if output.iter().any(|p| !p.is_finite()) {
+        return Err(NnError::Config("The forward pass produced non-finite outputs".to_string()));
+    }
+
+    let summary = network.summary();
+    Ok(DryRun {
+        batch_size: batch.features.nrows(),
+        n_samples: data.n_samples(),
+        estimated_bytes: memory_estimate(&summary.layer_shapes, data.n_features(), data.n_samples(), params.batch_size),
+        summary,
+    })
+}
+
+// Bytes of training a network with these (inputs, outputs) layer shapes.
+// Saturates instead of overflowing on absurd sizes.
+fn memory_estimate(layer_shapes: &[(usize, usize)], n_features: usize, n_samples: usize, batch_size: usize) -> usize {
+    let float = std::mem::size_of::<f64>();
+    let batch = if batch_size == 0 { n_samples } else { batch_size.min(n_samples) };
+    let parameters = layer_shapes
+        .iter()
+        .fold(0usize, |total, &(inputs, outputs)| total.saturating_add(inputs.saturating_add(1).saturating_mul(outputs)));
+    let layer_widths = layer_shapes.iter().fold(0usize, |total, &(_, outputs)| total.saturating_add(outputs));
+
+    let data_bytes = n_samples.saturating_mul(n_features + 1).saturating_mul(float);
+    // Weights and biases, their gradients and momentum velocities
+    let parameter_bytes = parameters.saturating_mul(3 * float);
+    // Pre-activations, activations and backpropagated errors per layer
+    let activation_bytes = batch.saturating_mul(layer_widths).saturating_mul(3 * float);
+    data_bytes.saturating_add(parameter_bytes).saturating_add(activation_bytes)
+}
+
+fn check_memory(estimated: usize, limit: usize) -> Result<(), NnError> {
+    if limit > 0 && estimated > limit {
+        return Err(NnError::MemoryLimit { estimated, limit });
+    }
+    Ok(())
+}
+
+// Running sums behind an epoch's score: predictions on the right side of
+// DECISION_THRESHOLD for classification, squared errors and label moments
+// for the R² of regression
+#[derive(Default)]
+struct ScoreSums {
+    n: usize,
+    correct: usize,
+    squared_error: f64,
+    label_sum: f64,
+    label_squares: f64,
+}
+
+impl ScoreSums {
+    fn add(&mut self, outputs: &Array2<f64>, labels: &Array2<f64>) {
+        for (&output, &y) in outputs.iter().zip(labels.iter()) {
+            self.n += 1;
+            self.correct += usize::from((if is_potable(output) { 1.0 } else { 0.0 } - y).abs() < 1e-6);
+            self.squared_error += (output - y) * (output - y);
+            self.label_sum += y;
+            self.label_squares += y * y;
+        }
+    }
+
+    // Accuracy in percent, or R² (0 for constant labels)
+    fn score(&self, task: TaskType) -> f64 {
+        let n = self.n.max(1) as f64;
+        match task {
+            TaskType::BinaryClassification => self.correct as f64 / n * 100.0,
+            TaskType::Regression => {
+                let total = self.label_squares - self.label_sum * self.label_sum / n;
+                if total > 0.0 { 1.0 - self.squared_error / total } else { 0.0 }
+            }
+        }
+    }
+}
+
+// lambda1 * sum|w| + lambda2 / 2 * sum w^2 over all weight matrices
+pub(crate) fn weight_penalty(network: &Network, params: &TrainingParams) -> f64 {
+    if params.l1_lambda == 0.0 && params.l2_lambda == 0.0 {
+        return 0.0;
+    }
+    network.weights.iter().map(|w| {
+        params.l1_lambda * w.fold(0.0, |acc, &v| acc + widen(v).abs())
+            + 0.5 * params.l2_lambda * w.fold(0.0, |acc, &v| acc + widen(v) * widen(v))
+    }).sum()
+}
+
+/// Predicts potability for one sample. When the network carries a scaler,
+/// `water_params` are raw measurements and are scaled with the training-time
+/// statistics; otherwise they are fed to the network as they are. A sample
+/// with a different feature count than the network's input is rejected.
+pub fn make_prediction(network: &Network, water_params: &[f64]) -> Result<PredictionResult, NnError> {
+    network.check_input_dim(water_params.len())?;
+    let input = match network.scaler() {
+        Some(scaler) => scaler.transform_row(water_params),
+        None => water_params.to_vec(),
+    };
+    let x = Array2::from_shape_vec((1, input.len()), input)?;
+    let probability = network.predict_proba(&x)[(0, 0)];
+    Ok(prediction_result(network.task, probability))
+}
+
+/// Predicts potability for every row of `samples`, with the same input
+/// convention as [`make_prediction`].
+pub fn make_predictions(network: &Network, samples: &Array2<f64>) -> Result<Vec<PredictionResult>, NnError> {
+    network.check_input_dim(samples.ncols())?;
+    let probabilities = network.predict_proba(&network.scale_inputs(samples));
+
+    Ok(probabilities
+        .iter()
+        .map(|&probability| prediction_result(network.task, probability))
+        .collect())
+}
+
+fn prediction_result(task: TaskType, output: f64) -> PredictionResult {
+    PredictionResult {
+        is_potable: task == TaskType::BinaryClassification && is_potable(output),
+        probability: output,
+        task,
+        class_index: None,
+        class_name: None,
+    }
+}
+
+/// Compatibility information stored at the start of a saved model, readable
+/// without loading the weights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelHeader {
+    pub version: u32,
+    /// Input dimension, hidden layer sizes and the single output.
+    pub layer_sizes: Vec<usize>,
+    pub activation: Activation,
+    pub task: TaskType,
+    /// Scaling the model expects its inputs to be transformed with, `None`
+    /// when it was saved without a scaler.
+    pub scale_mode: Option<ScaleMode>,
+}
+
+impl ModelHeader {
+    /// Number of input features the saved network expects.
+    pub fn input_dim(&self) -> usize {
+        self.layer_sizes[0]
+    }
+}
+
+/// Writes `network`, including its scaler, to `path`.
+///
+/// The file starts with magic bytes, [`MODEL_FORMAT_VERSION`] and a
+/// [`ModelHeader`], followed by the weights, biases, calibration and scaler as
+/// little-endian values. Momentum buffers are not saved.
+pub fn save_model(network: &Network, path: &str) -> Result<(), NnError> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    out.write_all(&MODEL_MAGIC)?;
+    write_u32(&mut out, MODEL_FORMAT_VERSION)?;
+    let mut layer_sizes = vec![network.input_dim()];
+    layer_sizes.extend(network.weights.iter().map(|w| w.ncols()));
+    write_u32(&mut out, layer_sizes.len() as u32)?;
+    for &size in &layer_sizes {
+        write_u32(&mut out, size as u32)?;
+    }
+    out.write_all(&[
+        activation_code(network.activation),
+        scale_mode_code(network.scaler().map(Scaler::mode)),
+        task_code(network.task),
+    ])?;
+    match network.optimizer {
+        Optimizer::Sgd => write_f64s(&mut out, &[0.0, 0.0])?,
+        Optimizer::Momentum { beta } => write_f64s(&mut out, &[1.0, beta])?,
+    }
+
+    for (w, b) in network.weights.iter().zip(&network.biases) {
+        write_f64s(&mut out, &w.iter().map(|&v| widen(v)).collect::<Vec<_>>())?;
+        write_f64s(&mut out, &b.iter().map(|&v| widen(v)).collect::<Vec<_>>())?;
+    }
+    match network.calibration {
+        Some(PlattScaling { a, b }) => write_f64s(&mut out, &[1.0, a, b])?,
+        None => write_f64s(&mut out, &[0.0, 0.0, 0.0])?,
+    }
+    if let Some(scaler) = network.scaler() {
+        let (offset, scale) = scaler.parts();
+        write_f64s(&mut out, offset)?;
+        write_f64s(&mut out, scale)?;
+        for stats in scaler.feature_stats() {
+            write_f64s(&mut out, &[stats.min, stats.max, stats.mean, stats.std])?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `network` as a standalone Rust source file with no dependencies:
+/// the weights, biases, scaler and calibration as `const` arrays and
+/// `pub fn predict(features: &[f64; INPUT_DIM]) -> f64`, which returns the same
+/// probability (or regression value) as [`make_prediction`] for raw features,
+/// up to floating-point rounding. Include it in another project with `mod` or `include!`.
+pub fn export_rust(network: &Network, path: &str) -> Result<(), NnError> {
+    let mut values = network.weights.iter().chain(&network.biases).flat_map(|a| a.iter());
+    if let Some(value) = values.find(|v| !v.is_finite()) {
+        return Err(NnError::Config(format!("Cannot export a network with a non-finite parameter ({})", value)));
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "// Generated by water_potability_nn::neural_network::export_rust, do not edit.")?;
+    writeln!(out, "// {}, {:?} hidden activation.", network.summary(), network.activation)?;
+    writeln!(out)?;
+    writeln!(out, "/// Features `predict` takes, in the training data's column order.")?;
+    writeln!(out, "pub const INPUT_DIM: usize = {};", network.input_dim())?;
+    writeln!(out)?;
+
+    let list = |values: &[f64]| values.iter().map(|v| format!("{:?}", v)).collect::<Vec<_>>().join(", ");
+    if let Some(scaler) = network.scaler() {
+        let (offset, scale) = scaler.parts();
+        writeln!(out, "// {:?} scaling fitted on the training data", scaler.mode())?;
+        writeln!(out, "const SCALE_OFFSET: [f64; INPUT_DIM] = [{}];", list(offset))?;
+        writeln!(out, "const SCALE_FACTOR: [f64; INPUT_DIM] = [{}];", list(scale))?;
+        writeln!(out)?;
+    }
+    for (i, (w, b)) in network.weights.iter().zip(&network.biases).enumerate() {
+        let rows: Vec<String> = w.rows().into_iter().map(|row| format!("    [{}],", list(&row.iter().map(|&v| widen(v)).collect::<Vec<_>>()))).collect();
+        writeln!(out, "const W{}: [[f64; {}]; {}] = [\n{}\n];", i, w.ncols(), w.nrows(), rows.join("\n"))?;
+        writeln!(out, "const B{}: [f64; {}] = [{}];", i, b.ncols(), list(&b.iter().map(|&v| widen(v)).collect::<Vec<_>>()))?;
+    }
+    writeln!(out)?;
+
+    match network.task {
+        TaskType::BinaryClassification => writeln!(out, "/// Probability in [0, 1] that the sample with these raw features is potable.")?,
+        TaskType::Regression => writeln!(out, "/// Predicted value for the sample with these raw features.")?,
+    }
+    writeln!(out, "pub fn predict(features: &[f64; INPUT_DIM]) -> f64 {{")?;
+    if network.scaler().is_some() {
+        writeln!(out, "    let h: [f64; INPUT_DIM] = std::array::from_fn(|j| (features[j] - SCALE_OFFSET[j]) / SCALE_FACTOR[j]);")?;
+    } else {
+        writeln!(out, "    let h = *features;")?;
+    }
+    let output_layer = network.weights.len() - 1;
+    for i in 0..output_layer {
+        writeln!(out, "    let h = layer(&h, &W{}, &B{}).map(activation);", i, i)?;
+    }
+    let output = match network.task {
+        TaskType::BinaryClassification => format!("sigmoid(layer(&h, &W{}, &B{})[0])", output_layer, output_layer),
+        TaskType::Regression => format!("layer(&h, &W{}, &B{})[0]", output_layer, output_layer),
+    };
+    match network.calibration {
+        Some(PlattScaling { a, b }) => {
+            writeln!(out, "    let p = {}.clamp(1e-15, 1.0 - 1e-15);", output)?;
+            writeln!(out, "    // Platt calibration")?;
+            writeln!(out, "    sigmoid({:?} * (p / (1.0 - p)).ln() + {:?})", a, b)?;
+        }
+        None => writeln!(out, "    {}", output)?,
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(out, "fn layer<const I: usize, const O: usize>(input: &[f64; I], weights: &[[f64; O]; I], bias: &[f64; O]) -> [f64; O] {{")?;
+    writeln!(out, "    let mut output = [0.0; O];")?;
+    writeln!(out, "    for (x, row) in input.iter().zip(weights) {{")?;
+    writeln!(out, "        for (o, w) in output.iter_mut().zip(row) {{")?;
+    writeln!(out, "            *o += x * w;")?;
+    writeln!(out, "        }}")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "    for (o, b) in output.iter_mut().zip(bias) {{")?;
+    writeln!(out, "        *o += b;")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "    output")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "fn activation(v: f64) -> f64 {{")?;
+    match network.activation {
+        Activation::Relu => writeln!(out, "    v.max(0.0)")?,
+        Activation::Sigmoid => writeln!(out, "    sigmoid(v)")?,
+        Activation::Tanh => writeln!(out, "    v.tanh()")?,
+    }
+    writeln!(out, "}}")?;
+    // A linear output with no sigmoid hidden layers leaves it unused
+    if network.task == TaskType::BinaryClassification || network.activation == Activation::Sigmoid {
+        writeln!(out)?;
+        writeln!(out, "fn sigmoid(v: f64) -> f64 {{")?;
+        writeln!(out, "    1.0 / (1.0 + (-v).exp())")?;
+        writeln!(out, "}}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads only the header of the model saved at `path`, e.g. to check its
+/// architecture or scaling before loading it.
+pub fn read_model_header(path: &str) -> Result<ModelHeader, NnError> {
+    read_header(&mut BufReader::new(File::open(path)?), path)
+}
+
+/// Loads a model written by [`save_model`]. Files from another format version
+/// or that are not model files at all are rejected with a descriptive error.
+pub fn load_model(path: &str) -> Result<Network, NnError> {
+    let mut input = BufReader::new(File::open(path)?);
+    let header = read_header(&mut input, path)?;
+    let truncated = |e: std::io::Error| {
+        if e.kind() == ErrorKind::UnexpectedEof {
+            format_error(path, "file is truncated")
+        } else {
+            NnError::Io(e)
+        }
+    };
+
+    // (kind, beta), kind 0 is plain SGD
+    let optimizer = match read_f64s(&mut input, 2).map_err(truncated)?[..] {
+        [kind, beta] if kind != 0.0 => Optimizer::Momentum { beta },
+        _ => Optimizer::Sgd,
+    };
+
+    let mut weights = Vec::with_capacity(header.layer_sizes.len() - 1);
+    let mut biases = Vec::with_capacity(header.layer_sizes.len() - 1);
+    for pair in header.layer_sizes.windows(2) {
+        let (fan_in, fan_out) = (pair[0], pair[1]);
+        let w = Array2::from_shape_vec((fan_in, fan_out), read_f64s(&mut input, fan_in * fan_out).map_err(truncated)?)?;
+        let b = Array2::from_shape_vec((1, fan_out), read_f64s(&mut input, fan_out).map_err(truncated)?)?;
+        weights.push(w.mapv(|v| v as Float));
+        biases.push(b.mapv(|v| v as Float));
+    }
+
+    // (present, a, b)
+    let calibration = match read_f64s(&mut input, 3).map_err(truncated)?[..] {
+        [present, a, b] if present != 0.0 => Some(PlattScaling { a, b }),
+        _ => None,
+    };
+
+    let input_dim = header.layer_sizes[0];
+    let scaler = match header.scale_mode {
+        Some(mode) => {
+            let offset = read_f64s(&mut input, input_dim).map_err(truncated)?;
+            let scale = read_f64s(&mut input, input_dim).map_err(truncated)?;
+            let stats = read_f64s(&mut input, input_dim * 4)
+                .map_err(truncated)?
+                .chunks(4)
+                .map(|s| FeatureStats { min: s[0], max: s[1], mean: s[2], std: s[3] })
+                .collect();
+            Some(Scaler::from_parts(mode, offset, scale, stats))
+        }
+        None => None,
+    };
+    if input.read(&mut [0u8])? != 0 {
+        return Err(format_error(path, "unexpected data after the model"));
+    }
+
+    Ok(Network {
+        velocity_w: weights.iter().map(|w| Array2::zeros(w.raw_dim())).collect(),
+        velocity_b: biases.iter().map(|b| Array2::zeros(b.raw_dim())).collect(),
+        weights,
+        biases,
+        activation: header.activation,
+        task: header.task,
+        optimizer,
+        scaler,
+        calibration,
+    })
+}
+
+/// Loads a saved model that will be fed samples with `n_features` features,
+/// failing before the weights are read if the model expects a different count.
+pub fn load_model_for(path: &str, n_features: usize) -> Result<Network, NnError> {
+    let header = read_model_header(path)?;
+    if header.input_dim() != n_features {
+        return Err(NnError::InputDimension { expected: header.input_dim(), found: n_features });
+    }
+    load_model(path)
+}
+
+fn read_header(input: &mut impl Read, path: &str) -> Result<ModelHeader, NnError> {
+    let mut magic = [0u8; 4];
+    if input.read_exact(&mut magic).is_err() || magic != MODEL_MAGIC {
+        return Err(format_error(path, "not a saved water potability model"));
+    }
+    let truncated = |_| format_error(path, "file is truncated");
+
+    let version = read_u32(input).map_err(truncated)?;
+    if version != MODEL_FORMAT_VERSION {
+        return Err(NnError::UnsupportedVersion { path: path.to_string(), found: version, expected: MODEL_FORMAT_VERSION });
+    }
+
+    let n_sizes = read_u32(input).map_err(truncated)? as usize;
+    if !(3..=1024).contains(&n_sizes) {
+        return Err(format_error(path, &format!("invalid architecture ({} layer sizes)", n_sizes)));
+    }
+    let layer_sizes = (0..n_sizes)
+        .map(|_| read_u32(input).map(|size| size as usize))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(truncated)?;
+    if layer_sizes.contains(&0) || layer_sizes.last() != Some(&1) {
+        return Err(format_error(path, &format!("invalid architecture {:?}", layer_sizes)));
+    }
+
+    let mut codes = [0u8; 3];
+    input.read_exact(&mut codes).map_err(truncated)?;
+    let activation = match codes[0] {
+        0 => Activation::Relu,
+        1 => Activation::Sigmoid,
+        2 => Activation::Tanh,
+        code => return Err(format_error(path, &format!("unknown activation code {}", code))),
+    };
+    let scale_mode = match codes[1] {
+        0 => None,
+        1 => Some(ScaleMode::Standardize),
+        2 => Some(ScaleMode::MinMax),
+        3 => Some(ScaleMode::None),
+        code => return Err(format_error(path, &format!("unknown scale mode code {}", code))),
+    };
+    let task = match codes[2] {
+        0 => TaskType::BinaryClassification,
+        1 => TaskType::Regression,
+        code => return Err(format_error(path, &format!("unknown task code {}", code))),
+    };
+
+    Ok(ModelHeader { version, layer_sizes, activation, task, scale_mode })
+}
+
+fn format_error(path: &str, reason: &str) -> NnError {
+    NnError::ModelFormat { path: path.to_string(), reason: reason.to_string() }
+}
+
+fn activation_code(activation: Activation) -> u8 {
+    match activation {
+        Activation::Relu => 0,
+        Activation::Sigmoid => 1,
+        Activation::Tanh => 2,
+    }
+}
+
+fn task_code(task: TaskType) -> u8 {
+    match task {
+        TaskType::BinaryClassification => 0,
+        TaskType::Regression => 1,
+    }
+}
+
+// 0 means the model has no scaler
+fn scale_mode_code(mode: Option<ScaleMode>) -> u8 {
+    match mode {
+        None => 0,
+        Some(ScaleMode::Standardize) => 1,
+        Some(ScaleMode::MinMax) => 2,
+        Some(ScaleMode::None) => 3,
+    }
+}
+
+fn write_u32(out: &mut impl Write, value: u32) -> std::io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_f64s(out: &mut impl Write, values: &[f64]) -> std::io::Result<()> {
+    values.iter().try_for_each(|v| out.write_all(&v.to_le_bytes()))
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f64s(input: &mut impl Read, n: usize) -> std::io::Result<Vec<f64>> {
+    let mut bytes = [0u8; 8];
+    (0..n)
+        .map(|_| {
+            input.read_exact(&mut bytes)?;
+            Ok(f64::from_le_bytes(bytes))
+        })
+        .collect()
+}
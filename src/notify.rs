@@ -0,0 +1,131 @@
+use std::io;
+#[cfg(not(target_os = "windows"))]
+use std::process::Command;
+use std::thread;
+
+// Shows a desktop notification through the platform's own command line tool
+// (notify-send on Linux and the BSDs, osascript on macOS). Returns once the
+// tool is started; it is reaped on a helper thread so the UI never waits, and
+// a tool that fails after starting is reported to the log from there.
+#[cfg(not(target_os = "windows"))]
+pub fn desktop_notification(title: &str, body: &str) -> io::Result<()> {
+    let mut command = notification_command(title, body)?;
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command.spawn().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(e.kind(), format!("{} is not installed or not on the PATH", program)),
+        _ => e,
+    })?;
+    thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => log::warn!("{} could not show the notification ({})", program, status),
+        Ok(_) => {}
+        Err(e) => log::warn!("{} could not be waited on: {}", program, e),
+    });
+    Ok(())
+}
+
+// Shows a desktop notification as a notification area balloon, which Windows
+// 10 and later present as a toast. Returns once the balloon is up; its icon is
+// removed again on a helper thread so the UI never waits.
+#[cfg(target_os = "windows")]
+pub fn desktop_notification(title: &str, body: &str) -> io::Result<()> {
+    use std::sync::mpsc::channel;
+
+    let (title, body) = (title.to_string(), body.to_string());
+    let (sender, receiver) = channel();
+    // The window and the icon belong to this thread, which also removes them
+    thread::spawn(move || {
+        let Some(window) = balloon::message_window() else {
+            let _ = sender.send(Err(io::Error::last_os_error()));
+            return;
+        };
+        let mut data = balloon::icon_data(window, &title, &body);
+        let shown = balloon::add(&mut data);
+        let _ = sender.send(if shown { Ok(()) } else { Err(io::Error::other("the notification area did not accept the balloon")) });
+        if shown {
+            thread::sleep(balloon::SHOWN_FOR);
+            balloon::remove(&mut data);
+        }
+        balloon::destroy(window);
+    });
+    receiver.recv().unwrap_or_else(|_| Err(io::Error::other("the notification thread stopped")))
+}
+
+#[cfg(target_os = "windows")]
+mod balloon {
+    use std::mem;
+    use std::ptr;
+    use std::time::Duration;
+    use winapi::shared::windef::HWND;
+    use winapi::um::shellapi::{Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_TIP, NIIF_INFO, NIM_ADD, NIM_DELETE, NOTIFYICONDATAW};
+    use winapi::um::winuser::{CreateWindowExW, DestroyWindow, LoadIconW, HWND_MESSAGE, IDI_INFORMATION};
+
+    // How long the tray icon stays, long enough to read the balloon
+    pub const SHOWN_FOR: Duration = Duration::from_secs(10);
+
+    // Invisible message-only window that owns the tray icon
+    pub fn message_window() -> Option<HWND> {
+        let class = wide::<8>("STATIC");
+        let window = unsafe {
+            CreateWindowExW(0, class.as_ptr(), ptr::null(), 0, 0, 0, 0, 0, HWND_MESSAGE, ptr::null_mut(), ptr::null_mut(), ptr::null_mut())
+        };
+        (!window.is_null()).then_some(window)
+    }
+
+    pub fn icon_data(window: HWND, title: &str, body: &str) -> NOTIFYICONDATAW {
+        // All-zero is a valid NOTIFYICONDATAW: null handles and empty strings
+        let mut data: NOTIFYICONDATAW = unsafe { mem::zeroed() };
+        data.cbSize = mem::size_of::<NOTIFYICONDATAW>() as u32;
+        data.hWnd = window;
+        data.uID = 1;
+        data.uFlags = NIF_ICON | NIF_TIP | NIF_INFO;
+        data.hIcon = unsafe { LoadIconW(ptr::null_mut(), IDI_INFORMATION) };
+        data.szTip = wide("Water Potability NN");
+        data.szInfoTitle = wide(title);
+        data.szInfo = wide(body);
+        data.dwInfoFlags = NIIF_INFO;
+        data
+    }
+
+    pub fn add(data: &mut NOTIFYICONDATAW) -> bool {
+        unsafe { Shell_NotifyIconW(NIM_ADD, data) != 0 }
+    }
+
+    pub fn remove(data: &mut NOTIFYICONDATAW) {
+        unsafe { Shell_NotifyIconW(NIM_DELETE, data) };
+    }
+
+    pub fn destroy(window: HWND) {
+        unsafe { DestroyWindow(window) };
+    }
+
+    // `text` as a NUL-terminated UTF-16 field of N units, cut short if it
+    // does not fit
+    fn wide<const N: usize>(text: &str) -> [u16; N] {
+        let mut field = [0; N];
+        for (slot, unit) in field.iter_mut().zip(text.encode_utf16().take(N - 1)) {
+            *slot = unit;
+        }
+        field
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(title: &str, body: &str) -> io::Result<Command> {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!("display notification {} with title {}", applescript_string(body), applescript_string(title)));
+    Ok(command)
+}
+
+// Quotes `text` as an AppleScript string literal, where only the backslash
+// and the double quote need escaping
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn notification_command(title: &str, body: &str) -> io::Result<Command> {
+    let mut command = Command::new("notify-send");
+    command.arg("--app-name=Water Potability NN").arg(title).arg(body);
+    Ok(command)
+}
@@ -0,0 +1,78 @@
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::neural_network::{EpochState, TrainingCallback};
+
+/// Shared switch to pause training between epochs and resume it where it
+/// left off. Unlike stopping, the training function never returns while
+/// paused, so the weights, momentum and epoch count are kept as they are.
+///
+/// Clones share the switch: pass one to training as a callback, which blocks
+/// at the end of an epoch while paused, and keep another to flip it. Nothing
+/// is spent while waiting, the thread sleeps on a condition variable.
+///
+/// ```
+/// use water_potability_nn::pause::PauseSwitch;
+/// use std::time::Duration;
+///
+/// let switch = PauseSwitch::new();
+/// switch.pause();
+/// let waiter = switch.clone();
+/// let worker = std::thread::spawn(move || waiter.wait_while_paused());
+/// assert!(switch.wait(Duration::from_millis(10)), "still paused");
+/// switch.resume();
+/// worker.join().unwrap();
+/// assert!(!switch.is_paused());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PauseSwitch {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl PauseSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses training at the end of the current epoch.
+    pub fn pause(&self) {
+        *self.paused() = true;
+    }
+
+    /// Lets a paused run continue, waking every waiter.
+    pub fn resume(&self) {
+        *self.paused() = false;
+        self.state.1.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused()
+    }
+
+    /// Blocks until the switch is resumed, returning at once when it is not
+    /// paused.
+    pub fn wait_while_paused(&self) {
+        let paused = self.paused();
+        drop(self.state.1.wait_while(paused, |paused| *paused).unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Blocks while paused for at most `timeout`, returning whether it is
+    /// still paused. Loop on it to do periodic work, such as reporting that
+    /// the thread is alive, during a long pause.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let paused = self.paused();
+        let (paused, _) = self.state.1.wait_timeout_while(paused, timeout, |paused| *paused).unwrap_or_else(PoisonError::into_inner);
+        *paused
+    }
+
+    // Only a bool is behind the lock, so a poisoned one is still consistent
+    fn paused(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.state.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl TrainingCallback for PauseSwitch {
+    fn on_epoch_end(&mut self, _state: &EpochState) {
+        self.wait_while_paused();
+    }
+}
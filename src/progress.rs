@@ -0,0 +1,60 @@
+use std::sync::mpsc::{sync_channel, Receiver, SendError, SyncSender, TrySendError};
+
+/// Creates a bounded channel for progress updates holding at most `capacity`
+/// unread updates.
+pub fn progress_channel<T>(capacity: usize) -> (ProgressSender<T>, Receiver<T>) {
+    let (sender, receiver) = sync_channel(capacity);
+    (ProgressSender { sender, pending: None }, receiver)
+}
+
+/// Sending half of a [`progress_channel`], for a producer that must never wait
+/// on a slow consumer, such as training feeding a plot.
+///
+/// [`send`](Self::send) drops an update when the channel is full instead of
+/// blocking, which only thins out a plotted curve. The newest dropped update
+/// is kept, and [`flush`](Self::flush) blocks until it is delivered, so the
+/// final point of a run is never lost.
+///
+/// ```
+/// use water_potability_nn::progress::progress_channel;
+///
+/// let (mut sender, receiver) = progress_channel(2);
+/// for epoch in 0..10 {
+///     sender.send(epoch).unwrap();
+/// }
+/// let reader = std::thread::spawn(move || receiver.iter().collect::<Vec<_>>());
+/// sender.flush().unwrap();
+/// drop(sender);
+/// assert_eq!(reader.join().unwrap(), [0, 1, 9]);
+/// ```
+pub struct ProgressSender<T> {
+    sender: SyncSender<T>,
+    pending: Option<T>,
+}
+
+impl<T> ProgressSender<T> {
+    /// Queues `update` without blocking, dropping it when the channel is full.
+    /// Fails only once the receiver is gone.
+    pub fn send(&mut self, update: T) -> Result<(), SendError<T>> {
+        match self.sender.try_send(update) {
+            Ok(()) => {
+                self.pending = None;
+                Ok(())
+            }
+            Err(TrySendError::Full(update)) => {
+                self.pending = Some(update);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(update)) => Err(SendError(update)),
+        }
+    }
+
+    /// Blocks until the last update, if [`send`](Self::send) dropped it, is
+    /// queued. Call it at the end of a run, before reporting completion.
+    pub fn flush(&mut self) -> Result<(), SendError<T>> {
+        match self.pending.take() {
+            Some(update) => self.sender.send(update),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::thread;
+use std::time::Duration;
+use crate::frontend_qt::{BackendHandles, RunFinished};
+use crate::settings;
+use water_potability_nn::data_loader::Dataset;
+use water_potability_nn::metrics;
+use water_potability_nn::neural_network::{EpochState, Network, TaskType, TrainingCallback, TrainingParams};
+
+// Separates the parameter lines from the metric rows
+const METRICS_HEADER: &str = "epoch,accuracy,loss";
+// Starts the optional section of scores of the trained network after the metric rows
+const FINAL_METRICS_HEADER: &str = "metric,value";
+
+// A training run saved for replay: its parameters as `key = value` lines,
+// then METRICS_HEADER and one CSV row of metrics per epoch, then, for a run
+// that completed, FINAL_METRICS_HEADER and one `name,value` row per score
+pub struct Recording {
+    pub params: TrainingParams,
+    pub metrics: Vec<(usize, f64, f64)>,  // (epoch, accuracy, loss)
+    pub final_metrics: Vec<(String, f64)>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read recording {}: {}", path, e))?;
+        let (header, rows) = contents
+            .split_once(&format!("{}\n", METRICS_HEADER))
+            .ok_or_else(|| format!("{} is not a training recording", path))?;
+        let (rows, final_rows) = rows.split_once(&format!("{}\n", FINAL_METRICS_HEADER)).unwrap_or((rows, ""));
+
+        let metrics = rows
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                match fields[..] {
+                    [epoch, accuracy, loss] => match (epoch.parse(), accuracy.parse(), loss.parse()) {
+                        (Ok(epoch), Ok(accuracy), Ok(loss)) => Ok((epoch, accuracy, loss)),
+                        _ => Err(format!("Invalid metrics on row {} of {}", i + 1, path)),
+                    },
+                    _ => Err(format!("Expected 3 fields on row {} of {}", i + 1, path)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let final_metrics = final_rows
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| match line.split_once(',').map(|(name, value)| (name.trim(), value.trim().parse())) {
+                Some((name, Ok(value))) => Ok((name.to_string(), value)),
+                _ => Err(format!("Invalid final metric '{}' in {}", line, path)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Recording { params: settings::read_params(header), metrics, final_metrics })
+    }
+}
+
+// Scores of a trained network on `dataset` (raw features) for the final
+// metrics section: the classification metrics, or the fit of a regression
+pub fn final_metrics(network: &Network, dataset: &Dataset) -> Vec<(&'static str, f64)> {
+    match network.task() {
+        TaskType::BinaryClassification => {
+            let evaluation = metrics::evaluate(network, dataset);
+            vec![
+                ("accuracy", evaluation.accuracy),
+                ("loss", evaluation.loss),
+                ("precision", evaluation.precision),
+                ("recall", evaluation.recall),
+                ("f1", evaluation.f1),
+                ("auc", metrics::roc_auc(network, dataset)),
+                ("brier", metrics::brier_score(network, dataset)),
+            ]
+        }
+        TaskType::Regression => {
+            let fit = metrics::evaluate_regression(network, dataset);
+            vec![("r_squared", fit.r_squared), ("rmse", fit.rmse)]
+        }
+    }
+}
+
+// Training callback writing a run to a recording file as it trains, so a
+// run that is cut short still leaves its epochs so far
+pub struct Recorder {
+    file: BufWriter<File>,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    // Starts a recording at `path`, replacing any earlier one
+    pub fn create(path: &str, params: &TrainingParams) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for line in settings::param_lines(params) {
+            writeln!(file, "{}", line)?;
+        }
+        writeln!(file, "{}", METRICS_HEADER)?;
+        Ok(Recorder { file, error: None })
+    }
+
+    // Appends the final metrics section; call it once, after the last epoch
+    pub fn write_final_metrics(&mut self, metrics: &[(&str, f64)]) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = writeln!(self.file, "{}", FINAL_METRICS_HEADER)
+            .and_then(|()| metrics.iter().try_for_each(|(name, value)| writeln!(self.file, "{},{}", name, value)));
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    // Flushes the file, or returns the first write error
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.file.flush(),
+        }
+    }
+}
+
+impl TrainingCallback for Recorder {
+    fn on_epoch_end(&mut self, state: &EpochState) {
+        if self.error.is_none()
+            && let Err(e) = writeln!(self.file, "{},{},{}", state.epoch, state.accuracy, state.loss)
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+// Stands in for the training thread: streams the recorded metrics into the UI
+// at `epochs_per_second`, then replays again whenever Start is pressed
+pub fn replay(recording: Recording, epochs_per_second: f64, backend: BackendHandles) {
+    let BackendHandles { mut metrics, params, logger, heartbeat, busy, finished, .. } = backend;
+    let interval = Duration::from_secs_f64(1.0 / epochs_per_second);
+
+    loop {
+        busy.claim();
+        logger.log(format!("Replaying {} recorded epochs at {} epochs/s", recording.metrics.len(), epochs_per_second));
+        for &(epoch, accuracy, loss) in &recording.metrics {
+            // The plot counts epochs completed
+            if metrics.send((epoch + 1, accuracy, loss)).is_err() {
+                return;
+            }
+            heartbeat.beat();
+            thread::sleep(interval);
+        }
+        if metrics.flush().is_err() {
+            return;
+        }
+        let (accuracy, loss) = recording.metrics.last().map_or((0.0, 0.0), |&(_, accuracy, loss)| (accuracy, loss));
+        busy.release();
+        let _ = finished.send(RunFinished::Completed { epochs: recording.metrics.len(), accuracy, loss, best: None });
+        if !recording.final_metrics.is_empty() {
+            let scores: Vec<String> = recording.final_metrics.iter().map(|(name, value)| format!("{} {:.4}", name, value)).collect();
+            logger.log(format!("Recorded final metrics: {}", scores.join(", ")));
+        }
+        logger.log("Replay finished. Press Start to replay it again.");
+
+        // Any submitted parameters restart the replay; training is not available here
+        loop {
+            match params.recv_timeout(Duration::from_millis(100)) {
+                Ok(_) => break,
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => heartbeat.beat(),
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::data_loader::{check_feature, FEATURE_NAMES, NUM_FEATURES};
+use crate::jsonl::json_string;
+use crate::neural_network::{format_output, make_prediction, Network, TaskType};
+
+/// Largest request body accepted, far more than nine numbers need.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+// A client that stops sending mid-request is dropped after this long
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Status code and JSON body of a response.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Self {
+        Response { status, body: format!("{{\"error\":{}}}", json_string(message)) }
+    }
+}
+
+/// Answers predictions from `network` on `listener`, one connection at a
+/// time, until accepting fails. Errors on a single connection are ignored.
+pub fn serve(network: &Network, listener: TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let _ = handle_connection(network, stream?);
+    }
+    Ok(())
+}
+
+/// Reads one HTTP/1.1 request from `stream`, writes the response and closes
+/// the connection.
+pub fn handle_connection(network: &Network, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let response = match read_request(&mut BufReader::new(&stream))? {
+        Ok((method, path, body)) => handle(network, &method, &path, &body),
+        Err(response) => response,
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// Routes a request. `POST /predict` takes the nine features as a JSON
+/// object keyed by feature name (case and spaces or underscores do not
+/// matter) or as an array in [`FEATURE_NAMES`] order, and answers
+/// `{"is_potable":..,"probability":..}`, or `{"value":..}` for a regression
+/// network. Malformed bodies and values outside
+/// [`FEATURE_BOUNDS`](crate::data_loader::FEATURE_BOUNDS) get a 400 with an
+/// `error` message.
+///
+/// ```
+/// use water_potability_nn::neural_network::NetworkBuilder;
+/// use water_potability_nn::serve::handle;
+///
+/// let network = NetworkBuilder::new(9).hidden_layers(&[4]).seed(1).build().unwrap();
+/// let body = br#"[7.0, 200, 20000, 7, 330, 420, 14, 66, 4]"#;
+/// let response = handle(&network, "POST", "/predict", body);
+/// assert_eq!(response.status, 200);
+/// assert!(response.body.starts_with("{\"is_potable\":"));
+///
+/// assert_eq!(handle(&network, "POST", "/predict", b"{\"pH\": 7}").status, 400);
+/// assert_eq!(handle(&network, "GET", "/predict", b"").status, 405);
+/// ```
+pub fn handle(network: &Network, method: &str, path: &str, body: &[u8]) -> Response {
+    if path != "/predict" {
+        return Response::error(404, "not found, predictions are served at POST /predict");
+    }
+    if method != "POST" {
+        return Response::error(405, "use POST");
+    }
+    let features = std::str::from_utf8(body)
+        .map_err(|_| "body is not UTF-8".to_string())
+        .and_then(parse_features);
+    let features = match features {
+        Ok(features) => features,
+        Err(message) => return Response::error(400, &message),
+    };
+    match make_prediction(network, &features) {
+        Ok(result) if result.task == TaskType::Regression => {
+            Response { status: 200, body: format!("{{\"value\":{}}}", format_output(result.probability)) }
+        }
+        Ok(result) => Response {
+            status: 200,
+            body: format!("{{\"is_potable\":{},\"probability\":{}}}", result.is_potable, format_output(result.probability)),
+        },
+        Err(e) => Response::error(500, &e.to_string()),
+    }
+}
+
+/// The features of a `/predict` body, in [`FEATURE_NAMES`] order and checked
+/// against their bounds.
+pub fn parse_features(body: &str) -> Result<Vec<f64>, String> {
+    let mut parser = Parser { text: body.as_bytes(), pos: 0 };
+    let features = match parser.peek() {
+        Some(b'[') => parser.array()?,
+        Some(b'{') => {
+            let mut features = [None; NUM_FEATURES];
+            for (key, value) in parser.object()? {
+                let index = FEATURE_NAMES
+                    .iter()
+                    .position(|name| normalize(name) == normalize(&key))
+                    .ok_or_else(|| format!("unknown feature '{}'", key))?;
+                features[index] = Some(value);
+            }
+            features
+                .iter()
+                .zip(FEATURE_NAMES)
+                .map(|(value, name)| value.ok_or_else(|| format!("missing feature '{}'", name)))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        _ => return Err("expected a JSON object or array of the nine features".to_string()),
+    };
+    if parser.peek().is_some() {
+        return Err("unexpected data after the JSON value".to_string());
+    }
+    if features.len() != NUM_FEATURES {
+        return Err(format!("expected {} features, got {}", NUM_FEATURES, features.len()));
+    }
+    features
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| check_feature(i, value).map_err(|e| e.to_string()))
+        .collect()
+}
+
+// "Organic Carbon", "organic_carbon" and "ORGANIC CARBON" name the same feature
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace(' ', "_")
+}
+
+// Outer Err is a broken connection, inner Err a malformed request to answer
+type Request = (String, String, Vec<u8>);
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<Request, Response>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(Err(Response::error(400, "malformed request line")));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            match value.trim().parse() {
+                Ok(length) => content_length = length,
+                Err(_) => return Ok(Err(Response::error(400, "invalid Content-Length"))),
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Ok(Err(Response::error(413, "request body too large")));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok((method, path, body)))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+// Just enough JSON for a flat object of numbers or an array of numbers
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    // Next non-whitespace byte, left unconsumed
+    fn peek(&mut self) -> Option<u8> {
+        while self.text.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at byte {}", byte as char, self.pos))
+        }
+    }
+
+    // Items of a comma-separated list up to `close`, the opening bracket already consumed
+    fn list<T>(&mut self, close: u8, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        if self.peek() == Some(close) {
+            self.pos += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+            } else {
+                self.expect(close)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Vec<f64>, String> {
+        self.expect(b'[')?;
+        self.list(b']', Self::number)
+    }
+
+    fn object(&mut self) -> Result<Vec<(String, f64)>, String> {
+        self.expect(b'{')?;
+        self.list(b'}', |parser| {
+            let key = parser.string()?;
+            parser.expect(b':')?;
+            Ok((key, parser.number()?))
+        })
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        self.peek();
+        let start = self.pos;
+        while self.text.get(self.pos).is_some_and(|b| b.is_ascii_digit() || b"+-.eE".contains(b)) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.pos])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .filter(|number: &f64| number.is_finite())
+            .ok_or_else(|| format!("expected a number at byte {}", start))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.text.get(self.pos).copied() {
+                None => return Err("unterminated string".to_string()),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let escaped = self.text.get(self.pos + 1).copied().ok_or("unterminated string")?;
+                    bytes.push(match escaped {
+                        b'"' | b'\\' | b'/' => escaped,
+                        _ => return Err(format!("unsupported escape at byte {}", self.pos)),
+                    });
+                    self.pos += 2;
+                    continue;
+                }
+                Some(byte) => bytes.push(byte),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        String::from_utf8(bytes).map_err(|_| "string is not UTF-8".to_string())
+    }
+}
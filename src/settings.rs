@@ -0,0 +1,264 @@
+use eframe::egui::Color32;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use water_potability_nn::data_loader::{Balance, ScaleMode};
+use water_potability_nn::neural_network::{DivergencePolicy, LossFn, TaskType, TrainingParams};
+
+// Default of Settings::idle_refresh
+pub const DEFAULT_IDLE_REFRESH: Duration = Duration::from_millis(500);
+
+// UI preferences kept between sessions, stored as `key = value` lines.
+// Missing or unreadable entries silently fall back to their defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub palette: Palette,
+    pub notify_on_finish: bool,  // Desktop notification when a run ends
+    pub idle_refresh: Duration,  // Time between repaints while nothing is running
+    pub workers: Option<usize>,  // Folds or seeds trained at once, one per core when None
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { palette: Palette::default(), notify_on_finish: false, idle_refresh: DEFAULT_IDLE_REFRESH, workers: None }
+    }
+}
+
+// Colors for the accuracy and loss lines
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Palette {
+    #[default]
+    Standard,    // Green accuracy, red loss
+    Colorblind,  // Blue accuracy, orange loss
+}
+
+impl Palette {
+    // (accuracy, loss) line colors
+    pub fn colors(self) -> (Color32, Color32) {
+        match self {
+            Palette::Standard => (Color32::from_rgb(50, 205, 50), Color32::from_rgb(220, 50, 50)),
+            Palette::Colorblind => (Color32::from_rgb(0, 114, 178), Color32::from_rgb(230, 159, 0)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Palette::Standard => "standard",
+            Palette::Colorblind => "colorblind",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "standard" => Some(Palette::Standard),
+            "colorblind" => Some(Palette::Colorblind),
+            _ => None,
+        }
+    }
+}
+
+fn config_file(name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("water_potability_nn").join(name))
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config_file("settings.cfg")
+}
+
+fn session_path() -> Option<PathBuf> {
+    config_file("last_session.cfg")
+}
+
+// `key = value` pairs of a config file, skipping lines without '='
+fn entries(contents: &str) -> impl Iterator<Item = (&str, &str)> {
+    contents.lines().filter_map(|line| line.split_once('=')).map(|(key, value)| (key.trim(), value.trim()))
+}
+
+fn write_config(path: Option<PathBuf>, contents: String) -> io::Result<()> {
+    let path = path.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
+
+// Overwrites `target` only when `value` parses
+fn set<T: FromStr>(target: &mut T, value: &str) {
+    if let Ok(parsed) = value.parse() {
+        *target = parsed;
+    }
+}
+
+// Enum values are stored by their Debug name
+fn set_variant<T: Copy + std::fmt::Debug>(target: &mut T, variants: &[T], value: &str) {
+    if let Some(&variant) = variants.iter().find(|variant| format!("{:?}", variant) == value) {
+        *target = variant;
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+        let Some(contents) = settings_path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return settings;
+        };
+
+        for (key, value) in entries(&contents) {
+            match key {
+                "palette" => {
+                    if let Some(palette) = Palette::parse(value) {
+                        settings.palette = palette;
+                    }
+                }
+                "notify_on_finish" => set(&mut settings.notify_on_finish, value),
+                "idle_refresh_ms" => {
+                    if let Ok(ms) = value.parse() {
+                        settings.idle_refresh = Duration::from_millis(ms);
+                    }
+                }
+                "workers" => settings.workers = value.parse().ok().filter(|&n| n > 0),
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        write_config(settings_path(), format!(
+            "palette = {}\nnotify_on_finish = {}\nidle_refresh_ms = {}\nworkers = {}\n",
+            self.palette.as_str(), self.notify_on_finish, self.idle_refresh.as_millis(),
+            self.workers.map_or("all".to_string(), |n| n.to_string())
+        ))
+    }
+}
+
+// Number of cross-validation folds or seed variance runs trained at once,
+// each on its own thread: `requested`, or one per available core. A single
+// training run always stays on one thread.
+pub fn worker_count(requested: Option<usize>) -> usize {
+    requested.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+// Training parameters read from `key = value` lines as written by
+// `param_lines`. Unknown keys are ignored and unparsable values keep their
+// defaults.
+pub fn read_params(contents: &str) -> TrainingParams {
+    let mut params = TrainingParams::default();
+    let mut huber_delta = None;
+    for (key, value) in entries(contents) {
+        match key {
+            "task" => set_variant(&mut params.task, &[TaskType::BinaryClassification, TaskType::Regression], value),
+            "epochs" => set(&mut params.epochs, value),
+            "hidden_layers" => set(&mut params.hidden_layers, value),
+            "neurons_per_layer" => set(&mut params.neurons_per_layer, value),
+            "learning_rate" => set(&mut params.learning_rate, value),
+            "warmup_epochs" => set(&mut params.warmup_epochs, value),
+            "scale_mode" => set_variant(&mut params.scale_mode, &[ScaleMode::Standardize, ScaleMode::MinMax, ScaleMode::None], value),
+            "balance" => set_variant(&mut params.balance, &[Balance::None, Balance::Oversample, Balance::Undersample], value),
+            "seed" => params.seed = value.parse().ok(),
+            "auto_reduce_lr" => set(&mut params.auto_reduce_lr, value),
+            "lr_patience" => set(&mut params.lr_patience, value),
+            "l1_lambda" => set(&mut params.l1_lambda, value),
+            "l2_lambda" => set(&mut params.l2_lambda, value),
+            "activation_stats_every" => set(&mut params.activation_stats_every, value),
+            "track_gradient_norm" => set(&mut params.track_gradient_norm, value),
+            "calibrate" => set(&mut params.calibrate, value),
+            "dedup" => set(&mut params.dedup, value),
+            "loss" => set_variant(&mut params.loss, &[LossFn::Bce, LossFn::Mse], value),
+            "huber_delta" => huber_delta = value.parse().ok(),
+            "batch_size" => set(&mut params.batch_size, value),
+            "shuffle_batches" => set(&mut params.shuffle_batches, value),
+            "fixed_batch_order" => set(&mut params.fixed_batch_order, value),
+            "noise_std" => set(&mut params.noise_std, value),
+            "divergence_policy" => set_variant(
+                &mut params.divergence_policy,
+                &[DivergencePolicy::Abort, DivergencePolicy::SkipBatch, DivergencePolicy::ReduceLr],
+                value,
+            ),
+            "val_fraction" => set(&mut params.val_fraction, value),
+            "test_fraction" => set(&mut params.test_fraction, value),
+            "early_stopping_patience" => set(&mut params.early_stopping_patience, value),
+            "min_delta" => set(&mut params.min_delta, value),
+            "target_accuracy" => params.target_accuracy = value.parse().ok(),
+            _ => {}
+        }
+    }
+    // Huber is the one loss with a parameter, saved as its own key
+    if let Some(delta) = huber_delta {
+        params.loss = LossFn::Huber { delta };
+    }
+    params
+}
+
+// The parameters the UI can set, one `key = value` line each
+pub fn param_lines(p: &TrainingParams) -> Vec<String> {
+    let mut lines = vec![
+        format!("task = {:?}", p.task),
+        format!("epochs = {}", p.epochs),
+        format!("hidden_layers = {}", p.hidden_layers),
+        format!("neurons_per_layer = {}", p.neurons_per_layer),
+        format!("learning_rate = {}", p.learning_rate),
+        format!("warmup_epochs = {}", p.warmup_epochs),
+        format!("scale_mode = {:?}", p.scale_mode),
+        format!("balance = {:?}", p.balance),
+        format!("seed = {}", p.seed.map_or("none".to_string(), |seed| seed.to_string())),
+        format!("auto_reduce_lr = {}", p.auto_reduce_lr),
+        format!("lr_patience = {}", p.lr_patience),
+        format!("l1_lambda = {}", p.l1_lambda),
+        format!("l2_lambda = {}", p.l2_lambda),
+        format!("activation_stats_every = {}", p.activation_stats_every),
+        format!("track_gradient_norm = {}", p.track_gradient_norm),
+        format!("calibrate = {}", p.calibrate),
+        format!("dedup = {}", p.dedup),
+        format!("batch_size = {}", p.batch_size),
+        format!("shuffle_batches = {}", p.shuffle_batches),
+        format!("fixed_batch_order = {}", p.fixed_batch_order),
+        format!("noise_std = {}", p.noise_std),
+        format!("divergence_policy = {:?}", p.divergence_policy),
+        format!("val_fraction = {}", p.val_fraction),
+        format!("test_fraction = {}", p.test_fraction),
+        format!("early_stopping_patience = {}", p.early_stopping_patience),
+        format!("min_delta = {}", p.min_delta),
+        format!("target_accuracy = {}", p.target_accuracy.map_or("none".to_string(), |target| target.to_string())),
+    ];
+    match p.loss {
+        LossFn::Huber { delta } => lines.push(format!("huber_delta = {}", delta)),
+        loss => lines.push(format!("loss = {:?}", loss)),
+    }
+    lines
+}
+
+// Parameters of the last started run and the data it trained on, restored
+// into the input fields on the next start. A missing or corrupt file, or
+// parameters that fail validation, silently give the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct LastSession {
+    pub params: TrainingParams,
+    pub data_path: Option<String>,
+}
+
+impl LastSession {
+    pub fn load() -> Self {
+        let Some(contents) = session_path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return LastSession::default();
+        };
+        let session = LastSession {
+            params: read_params(&contents),
+            data_path: entries(&contents).find(|&(key, value)| key == "data_path" && !value.is_empty()).map(|(_, value)| value.to_string()),
+        };
+
+        if session.params.validate().is_err() || session.params.hidden_layers == 0 || session.params.neurons_per_layer == 0 {
+            return LastSession::default();
+        }
+        session
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut lines = vec![format!("data_path = {}", self.data_path.as_deref().unwrap_or(""))];
+        lines.extend(param_lines(&self.params));
+        lines.push(String::new());
+        write_config(session_path(), lines.join("\n"))
+    }
+}
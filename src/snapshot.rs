@@ -0,0 +1,37 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Latest value published by one thread for others to read, e.g. the trained
+/// model shared between the training thread and the prediction panel.
+///
+/// Readers clone an `Arc` under a short read lock and use it after the lock is
+/// released, so a slow prediction never holds up the next publish and vice
+/// versa. A publish replaces the whole value, so a poisoned lock still holds a
+/// consistent snapshot and is read through.
+#[derive(Debug)]
+pub struct Snapshot<T> {
+    latest: RwLock<Option<Arc<T>>>,
+}
+
+impl<T> Snapshot<T> {
+    pub fn new() -> Self {
+        Snapshot { latest: RwLock::new(None) }
+    }
+
+    /// Replaces the current value and returns the shared handle to the new one.
+    pub fn publish(&self, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        *self.latest.write().unwrap_or_else(PoisonError::into_inner) = Some(value.clone());
+        value
+    }
+
+    /// The most recently published value, if any.
+    pub fn latest(&self) -> Option<Arc<T>> {
+        self.latest.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+impl<T> Default for Snapshot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::NnError;
+use crate::neural_network::EpochState;
+
+// CRC-32C (Castagnoli) polynomial, reversed, as used by TFRecord framing
+const CRC32C_POLY: u32 = 0x82f6_3b78;
+// Added to the rotated checksum so data containing its own CRC stays checkable
+const CRC_MASK_DELTA: u32 = 0xa282_ead8;
+
+/// Writes scalar summaries to a TensorBoard `.tfevents` file. Each record is
+/// an `Event` protobuf framed as a TFRecord; the few fields needed are
+/// encoded by hand.
+pub struct EventWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl EventWriter {
+    /// Creates `log_dir` if needed and starts a new event file in it, named
+    /// like TensorBoard's own writers so it is picked up as a run.
+    pub fn create(log_dir: &Path) -> Result<Self, NnError> {
+        fs::create_dir_all(log_dir)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let path = log_dir.join(format!("events.out.tfevents.{}.water_potability_nn", now.as_secs()));
+        let mut writer = EventWriter { file: BufWriter::new(File::create(&path)?), path };
+
+        // The first event of every file declares the format version
+        let mut event = event_header(wall_time(), 0);
+        put_bytes(&mut event, 3, b"brain.Event:2");
+        writer.write_record(&event)?;
+        Ok(writer)
+    }
+
+    /// The event file being written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one scalar under `tag` at `step`.
+    pub fn add_scalar(&mut self, tag: &str, value: f64, step: usize) -> io::Result<()> {
+        let mut summary_value = Vec::new();
+        put_bytes(&mut summary_value, 1, tag.as_bytes());
+        put_tag(&mut summary_value, 2, 5);
+        summary_value.extend_from_slice(&(value as f32).to_le_bytes());
+
+        let mut summary = Vec::new();
+        put_bytes(&mut summary, 1, &summary_value);
+
+        let mut event = event_header(wall_time(), step as i64);
+        put_bytes(&mut event, 5, &summary);
+        self.write_record(&event)
+    }
+
+    /// Writes the loss, accuracy, learning rate and, when measured, the
+    /// validation loss and accuracy of an epoch, then flushes so TensorBoard
+    /// sees it live.
+    pub fn write_epoch(&mut self, state: &EpochState) -> io::Result<()> {
+        self.add_scalar("train/loss", state.loss, state.epoch)?;
+        self.add_scalar("train/accuracy", state.accuracy, state.epoch)?;
+        self.add_scalar("train/learning_rate", state.learning_rate, state.epoch)?;
+        if let Some(loss) = state.validation_loss {
+            self.add_scalar("validation/loss", loss, state.epoch)?;
+        }
+        if let Some(accuracy) = state.validation_accuracy {
+            self.add_scalar("validation/accuracy", accuracy, state.epoch)?;
+        }
+        self.file.flush()
+    }
+
+    // TFRecord framing: length, masked CRC of the length, data, masked CRC of the data
+    fn write_record(&mut self, data: &[u8]) -> io::Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+        self.file.write_all(&length)?;
+        self.file.write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.file.write_all(data)?;
+        self.file.write_all(&masked_crc32c(data).to_le_bytes())
+    }
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+// Event fields 1 (wall_time, double) and 2 (step, int64)
+fn event_header(wall_time: f64, step: i64) -> Vec<u8> {
+    let mut event = Vec::new();
+    put_tag(&mut event, 1, 1);
+    event.extend_from_slice(&wall_time.to_le_bytes());
+    put_tag(&mut event, 2, 0);
+    put_varint(&mut event, step as u64);
+    event
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_tag(buf: &mut Vec<u8>, field: u32, wire_type: u32) {
+    put_varint(buf, u64::from(field << 3 | wire_type));
+}
+
+// Length-delimited field: strings and nested messages
+fn put_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_tag(buf, field, 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ CRC32C_POLY } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    crc.rotate_right(15).wrapping_add(CRC_MASK_DELTA)
+}
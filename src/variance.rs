@@ -0,0 +1,96 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::BTreeMap;
+
+use crate::cross_validation::{fold_seed, run_parallel};
+use crate::data_loader::{balance_classes, Balance, Dataset, Scaler};
+use crate::error::NnError;
+use crate::neural_network::{create_network, train_network, TrainingParams};
+
+/// Training accuracy across seeds at one epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochSpread {
+    pub epoch: usize,
+    pub mean: f64,
+    /// Population standard deviation over the seeds.
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Results of [`seed_variance`].
+#[derive(Clone, Debug)]
+pub struct SeedVariance {
+    pub seeds: Vec<u64>,
+    /// `(epoch, accuracy %)` reported by each seed's run, in `seeds` order.
+    pub curves: Vec<Vec<(usize, f64)>>,
+    /// Spread of the curves, for the epochs every run reported.
+    pub spread: Vec<EpochSpread>,
+}
+
+/// Trains `params` on `dataset` (raw features) once per seed and collects
+/// the accuracy curves. The `n_seeds` seeds are derived from `params.seed`
+/// (or a random base seed) like cross-validation fold seeds, and runs are
+/// spread over at most `max_threads` threads.
+pub fn seed_variance(
+    dataset: &Dataset,
+    params: &TrainingParams,
+    n_seeds: usize,
+    max_threads: usize,
+) -> Result<SeedVariance, NnError> {
+    if n_seeds < 2 {
+        return Err(NnError::Config(format!("Need at least 2 seeds to measure variance, got {}", n_seeds)));
+    }
+    params.check_data(dataset)?;
+    let base_seed = params.seed.unwrap_or_else(rand::random);
+    let seeds: Vec<u64> = (0..n_seeds).map(|i| fold_seed(base_seed, i)).collect();
+
+    let scaler = Scaler::fit(&dataset.features, params.scale_mode);
+    let scaled = Dataset { features: scaler.transform(&dataset.features), labels: dataset.labels.clone(), weights: dataset.weights.clone() };
+
+    let curves = run_parallel(n_seeds, max_threads, |i| accuracy_curve(&scaled, params, seeds[i]))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    let spread = accuracy_spread(&curves);
+    Ok(SeedVariance { seeds, curves, spread })
+}
+
+fn accuracy_curve(scaled: &Dataset, params: &TrainingParams, seed: u64) -> Result<Vec<(usize, f64)>, NnError> {
+    let balanced;
+    let training_data = if params.balance == Balance::None {
+        scaled
+    } else {
+        balanced = balance_classes(scaled, params.balance, &mut StdRng::seed_from_u64(seed));
+        &balanced
+    };
+
+    let seed_params = TrainingParams { seed: Some(seed), ..params.clone() };
+    let mut network = create_network(&seed_params)?;
+    let mut curve = Vec::with_capacity(params.epochs);
+    train_network(&mut network, training_data, &seed_params, |epoch, accuracy, _| curve.push((epoch, accuracy)))?;
+    Ok(curve)
+}
+
+/// Aligns `(epoch, value)` curves by epoch and summarises each epoch that
+/// all of them reported, in epoch order.
+pub fn accuracy_spread(curves: &[Vec<(usize, f64)>]) -> Vec<EpochSpread> {
+    let mut by_epoch: BTreeMap<usize, Vec<f64>> = BTreeMap::new();
+    for curve in curves {
+        for &(epoch, value) in curve {
+            by_epoch.entry(epoch).or_default().push(value);
+        }
+    }
+
+    by_epoch
+        .into_iter()
+        .filter(|(_, values)| values.len() == curves.len())
+        .map(|(epoch, values)| {
+            let n = values.len() as f64;
+            let mean = values.iter().sum::<f64>() / n;
+            let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            EpochSpread { epoch, mean, std, min, max }
+        })
+        .collect()
+}
@@ -0,0 +1,399 @@
+use std::fs;
+use std::path::PathBuf;
+
+use ndarray::Array2;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use water_potability_nn::data_loader::{
+    balance_classes, check_split, dedup, load_data_with_spec, load_water_data, load_water_data_with_spec, shared_rows, stratified_split, train_validation_split, Balance,
+    ColumnSpec, CsvFormat, load_potability_data, read_samples, LABEL_HEADER, DataSource, Dataset, ScaleMode, Scaler, DEFAULT_LABEL_COLUMN, MIN_SAMPLES_PER_CLASS,
+};
+use std::borrow::Cow;
+use water_potability_nn::NnError;
+
+fn write_temp_csv(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn loads_quoted_headers_with_bom() {
+    let mut contents = b"\xEF\xBB\xBF".to_vec();
+    contents.extend_from_slice(
+        b"\"ph\",\"Hardness\",\"Solids\",\"Chloramines\",\"Sulfate\",\"Conductivity\",\"Organic_carbon\",\"Trihalomethanes\",\"Turbidity\",\"Potability\"\n\
+          7.0,\"204.5\",20791.3,7.3,368.5,564.3,10.4,86.9,2.96,0\n\
+          3.7, 129.4 ,18630.1,6.6,,592.9,15.2,56.3,4.5,0\n\
+          8.1,224.2,19909.5,9.3,310.1,418.6,16.9,66.4,3.1,1\n",
+    );
+    let path = write_temp_csv("quoted_bom.csv", &contents);
+
+    let dataset = load_water_data(path.to_str().unwrap(), DEFAULT_LABEL_COLUMN).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // The row with an empty Sulfate value is skipped
+    assert_eq!(dataset.n_samples(), 2);
+    assert_eq!(dataset.features[(0, 0)], 7.0);
+    assert_eq!(dataset.features[(0, 1)], 204.5);
+    assert_eq!(dataset.labels.column(0).to_vec(), vec![0.0, 1.0]);
+}
+
+#[test]
+fn reports_line_of_malformed_value() {
+    let path = write_temp_csv(
+        "malformed.csv",
+        b"ph,Hardness,Solids,Chloramines,Sulfate,Conductivity,Organic_carbon,Trihalomethanes,Turbidity,Potability\n\
+          7.0,204.5,20791.3,7.3,368.5,564.3,10.4,86.9,2.96,0\n\
+          7.0,abc,20791.3,7.3,368.5,564.3,10.4,86.9,2.96,0\n",
+    );
+
+    let err = load_water_data(path.to_str().unwrap(), DEFAULT_LABEL_COLUMN).unwrap_err();
+    fs::remove_file(&path).unwrap();
+
+    assert!(matches!(&err, NnError::NotANumber { line: 3, column, .. } if column == "Hardness"));
+    assert_eq!(err.to_string(), "Line 3, column 'Hardness': 'abc' is not a number");
+}
+
+#[test]
+fn text_labels_are_read_through_the_label_mapping() {
+    let header = "ph,Hardness,Solids,Chloramines,Sulfate,Conductivity,Organic_carbon,Trihalomethanes,Turbidity,Potability\n";
+    let path = write_temp_csv(
+        "text_labels.csv",
+        format!("{header}7.0,204.5,20791.3,7.3,368.5,564.3,10.4,86.9,2.96,Yes\n\
+                 8.1,224.2,19909.5,9.3,310.1,418.6,16.9,66.4,3.1,no\n\
+                 7.5,210.0,20000.0,7.0,330.0,420.0,14.0,66.0,4.0,1\n").as_bytes(),
+    );
+    let spec = ColumnSpec::default().label("Yes", 1.0).label("No", 0.0);
+    let dataset = load_water_data_with_spec(path.to_str().unwrap(), DEFAULT_LABEL_COLUMN, &spec).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(dataset.labels.column(0).to_vec(), vec![1.0, 0.0, 1.0]);
+
+    let path = write_temp_csv(
+        "unmapped_label.csv",
+        format!("{header}7.0,204.5,20791.3,7.3,368.5,564.3,10.4,86.9,2.96,Maybe\n").as_bytes(),
+    );
+    let err = load_water_data_with_spec(path.to_str().unwrap(), DEFAULT_LABEL_COLUMN, &spec).unwrap_err();
+    // Without a mapping text labels are still rejected as non-numeric
+    let numeric_only = load_water_data(path.to_str().unwrap(), DEFAULT_LABEL_COLUMN);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(err.to_string(), "Line 2: unknown label 'Maybe', expected a number or one of Yes, No");
+    assert!(matches!(numeric_only, Err(NnError::NotANumber { line: 2, .. })));
+}
+
+#[test]
+fn categorical_columns_are_one_hot_encoded() {
+    let path = write_temp_csv(
+        "categorical.csv",
+        b"ph,Source,Potability,Treated\n\
+          7.0,river,1,true\n\
+          6.5,well,0,false\n\
+          8.0,,1,true\n\
+          7.5,lake,0,true\n",
+    );
+    let spec = ColumnSpec::default().categorical("Source").categorical("Treated");
+
+    let loaded = load_data_with_spec(path.to_str().unwrap(), 2, &spec).unwrap();
+    let as_numbers = load_data_with_spec(path.to_str().unwrap(), 2, &ColumnSpec::default());
+    fs::remove_file(&path).unwrap();
+
+    // The row without a source is skipped; categories are sorted
+    assert_eq!(
+        loaded.feature_names,
+        vec!["ph", "Source=lake", "Source=river", "Source=well", "Treated=false", "Treated=true"]
+    );
+    assert_eq!(loaded.one_hot_columns, vec![1, 2, 3, 4, 5]);
+    assert_eq!(loaded.dataset.features.row(0).to_vec(), vec![7.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(loaded.dataset.features.row(2).to_vec(), vec![7.5, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(loaded.dataset.labels.column(0).to_vec(), vec![1.0, 0.0, 0.0]);
+    assert!(matches!(as_numbers, Err(NnError::NotANumber { line: 2, .. })));
+
+    // One-hot features keep their 0/1 values through the scaler
+    let scaler = Scaler::fit(&loaded.dataset.features, ScaleMode::Standardize).pass_through(&loaded.one_hot_columns);
+    let scaled = scaler.transform(&loaded.dataset.features);
+    assert_eq!(scaled.column(2), loaded.dataset.features.column(2));
+    assert!(scaled.column(0).mean().unwrap().abs() < 1e-12);
+}
+
+#[test]
+fn missing_values_are_counted_per_column_and_skip_their_rows() {
+    let path = write_temp_csv(
+        "missing_counts.csv",
+        b"ph,Sulfate,Potability,Turbidity\n\
+          7.0,,1,3.1\n\
+          ,330.0,0,\n\
+          6.5,310.0,,4.0\n\
+          8.0,350.0,1,2.5\n",
+    );
+    let loaded = load_data_with_spec(path.to_str().unwrap(), 2, &ColumnSpec::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // A row with two gaps counts once as skipped but in both columns
+    assert_eq!(loaded.dataset.n_samples(), 1);
+    assert_eq!(loaded.skipped_rows, 3);
+    let missing: Vec<(&str, usize)> = loaded.missing_values.iter().map(|(name, count)| (name.as_str(), *count)).collect();
+    assert_eq!(missing, vec![("ph", 1), ("Sulfate", 1), ("Potability", 1), ("Turbidity", 1)]);
+}
+
+#[test]
+fn semicolon_and_tab_separated_files_with_decimal_commas_are_detected() {
+    let load = |name: &str, contents: &str, spec: &ColumnSpec| {
+        let path = write_temp_csv(name, contents.as_bytes());
+        let loaded = load_data_with_spec(path.to_str().unwrap(), 2, spec);
+        fs::remove_file(&path).unwrap();
+        loaded
+    };
+    let expected = load("comma.csv", "ph,Sulfate,Potability\n7.25,330.5,1\n6,310,0\n", &ColumnSpec::default()).unwrap().dataset;
+
+    for (name, contents) in [
+        ("semicolon.csv", "ph;Sulfate;Potability\n7,25;330,5;1\n6;310;0\n"),
+        ("quoted_semicolon.csv", "\"ph\";\"Sulfate\";\"Potability\"\n\"7,25\";\"330,5\";1\n6;310;0\n"),
+        ("tab.csv", "ph\tSulfate\tPotability\n7,25\t330,5\t1\n6\t310\t0\n"),
+        ("semicolon_points.csv", "ph;Sulfate;Potability\n7.25;330.5;1\n6;310;0\n"),
+    ] {
+        let dataset = load(name, contents, &ColumnSpec::default()).unwrap().dataset;
+        assert_eq!((dataset.features, dataset.labels), (expected.features.clone(), expected.labels.clone()), "{}", name);
+    }
+
+    // Decimal commas and points mixed, or two delimiters that both fit, need the format spelled out
+    let mixed = "ph;Sulfate;Potability\n7,25;330.5;1\n6;310;0\n";
+    let err = load("mixed.csv", mixed, &ColumnSpec::default()).unwrap_err();
+    assert!(matches!(err, NnError::AmbiguousFormat(_)), "{:?}", err);
+    assert!(err.to_string().contains("both decimal commas and decimal points"), "{}", err);
+    let two_delimiters = "ph,x;Sulfate,y;Potability\n";
+    assert!(matches!(CsvFormat::detect(two_delimiters.as_bytes()), Err(NnError::AmbiguousFormat(_))));
+
+    let explicit = ColumnSpec::default().format(CsvFormat { delimiter: b';', decimal: '.' });
+    let err = load("explicit.csv", mixed, &explicit).unwrap_err();
+    assert!(matches!(&err, NnError::NotANumber { line: 2, column, .. } if column == "ph"), "{:?}", err);
+    let dataset = load("explicit.csv", mixed, &ColumnSpec::default().format(CsvFormat::SEMICOLON)).unwrap().dataset;
+    assert_eq!(dataset.features.row(0).to_vec(), vec![7.25, 330.5]);
+}
+
+#[test]
+fn a_weight_column_is_read_as_sample_weights_and_not_as_a_feature() {
+    let header = "ph,Hardness,Solids,Chloramines,Weight,Sulfate,Conductivity,Organic_carbon,Trihalomethanes,Turbidity,Potability\n";
+    let row = |weight: &str, label: u8| format!("7.0,204.5,20791.3,7.3,{},368.5,564.3,10.4,86.9,2.96,{}\n", weight, label);
+    let path = write_temp_csv("weighted.csv", format!("{}{}{}", header, row("2.5", 1), row("0", 0)).as_bytes());
+    let dataset = load_water_data(path.to_str().unwrap(), 10).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(dataset.features.ncols(), 9);
+    assert_eq!(dataset.features[(0, 4)], 368.5, "Sulfate follows Chloramines");
+    assert_eq!(dataset.weights.unwrap().column(0).to_vec(), vec![2.5, 0.0]);
+    assert_eq!(dataset.labels.column(0).to_vec(), vec![1.0, 0.0]);
+
+    // The app finds the label by its header, so the weight column cannot shift Turbidity into its place
+    let path = write_temp_csv("weighted_by_header.csv", format!("{}{}{}", header, row("2.5", 1), row("0", 0)).as_bytes());
+    let dataset = load_potability_data(path.to_str().unwrap(), None).unwrap();
+    let loaded = load_data_with_spec(path.to_str().unwrap(), DEFAULT_LABEL_COLUMN, &ColumnSpec::default().label_header(LABEL_HEADER)).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(dataset.labels.column(0).to_vec(), vec![1.0, 0.0]);
+    assert_eq!(dataset.features[(0, 8)], 2.96, "Turbidity stays a feature");
+    assert_eq!((loaded.label_column, loaded.weight_column), (10, Some(4)));
+    let unnamed = header.replace("Potability", "Label");
+    let path = write_temp_csv("weighted_unnamed.csv", format!("{}{}", unnamed, row("1", 1)).as_bytes());
+    let err = load_potability_data(path.to_str().unwrap(), None).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(&err, NnError::Columns(message) if message.contains("Potability")), "{:?}", err);
+
+    let path = write_temp_csv("negative_weight.csv", format!("{}{}{}", header, row("1", 1), row("-1", 0)).as_bytes());
+    let err = load_water_data(path.to_str().unwrap(), 10).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(err, NnError::InvalidWeight { line: 3, value } if value == -1.0), "{:?}", err);
+
+    // Without the column every sample weighs the same
+    let dataset = Dataset { features: Array2::zeros((3, 1)), labels: Array2::zeros((3, 1)), weights: None };
+    assert!(dataset.select_rows(&[2, 0]).weights.is_none());
+}
+
+#[test]
+fn balancing_evens_out_classes_reproducibly() {
+    // 8 negatives, 2 positives, feature equals the row index
+    let dataset = Dataset {
+        features: Array2::from_shape_fn((10, 1), |(i, _)| i as f64),
+        labels: Array2::from_shape_fn((10, 1), |(i, _)| if i >= 8 { 1.0 } else { 0.0 }),
+        weights: None,
+    };
+    let positives = |d: &Dataset| d.labels.iter().filter(|&&y| y == 1.0).count();
+
+    let oversampled = balance_classes(&dataset, Balance::Oversample, &mut StdRng::seed_from_u64(1));
+    assert_eq!(oversampled.n_samples(), 16);
+    assert_eq!(positives(&oversampled), 8);
+    // Every row comes from the original data with its own label
+    assert!(oversampled.features.iter().zip(oversampled.labels.iter()).all(|(&x, &y)| (x >= 8.0) == (y == 1.0)));
+
+    let undersampled = balance_classes(&dataset, Balance::Undersample, &mut StdRng::seed_from_u64(1));
+    assert_eq!(undersampled.n_samples(), 4);
+    assert_eq!(positives(&undersampled), 2);
+
+    let again = balance_classes(&dataset, Balance::Oversample, &mut StdRng::seed_from_u64(1));
+    assert_eq!(again.features, oversampled.features);
+}
+
+#[test]
+fn batches_cover_every_sample_once_and_shuffle_reproducibly() {
+    // The feature is the row index, the label its parity
+    let dataset = Dataset {
+        features: Array2::from_shape_fn((10, 1), |(i, _)| i as f64),
+        labels: Array2::from_shape_fn((10, 1), |(i, _)| (i % 2) as f64),
+        weights: None,
+    };
+    let order = |batch_size, shuffle, seed| -> Vec<Vec<usize>> {
+        dataset
+            .batches(batch_size, shuffle, seed)
+            .map(|batch| {
+                assert!(batch.features.iter().zip(batch.labels.iter()).all(|(&x, &y)| (x as usize % 2) as f64 == y));
+                batch.features.iter().map(|&x| x as usize).collect()
+            })
+            .collect()
+    };
+
+    let shuffled = order(4, true, 7);
+    assert_eq!(shuffled.iter().map(Vec::len).collect::<Vec<_>>(), vec![4, 4, 2]);
+    let mut covered = shuffled.concat();
+    covered.sort();
+    assert_eq!(covered, (0..10).collect::<Vec<_>>());
+
+    assert_eq!(order(4, true, 7), shuffled);
+    assert_ne!(order(4, true, 8), shuffled);
+    assert_eq!(order(4, false, 7), vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+
+    // The default full batch is the dataset itself, not a copy
+    let full: Vec<_> = dataset.batches(0, false, 0).collect();
+    assert_eq!(full.len(), 1);
+    assert!(matches!(&full[0], Cow::Borrowed(batch) if std::ptr::eq(*batch, &dataset)));
+}
+
+#[test]
+fn dedup_removes_exact_repeats_and_counts_leaks_across_splits() {
+    // Rows 2 and 4 repeat row 0; row 3 has row 1's features but a different label
+    let mut dataset = Dataset {
+        features: Array2::from_shape_vec((5, 2), vec![1.0, 2.0, 3.0, 4.0, 1.0, 2.0, 3.0, 4.0, 1.0, 2.0]).unwrap(),
+        labels: Array2::from_shape_vec((5, 1), vec![0.0, 1.0, 0.0, 0.0, 0.0]).unwrap(),
+        weights: None,
+    };
+    let original = dataset.clone();
+
+    assert_eq!(dedup(&mut dataset), 2);
+    assert_eq!(dataset.features, Array2::from_shape_vec((3, 2), vec![1.0, 2.0, 3.0, 4.0, 3.0, 4.0]).unwrap());
+    assert_eq!(dataset.labels.iter().copied().collect::<Vec<_>>(), vec![0.0, 1.0, 0.0]);
+    assert_eq!(dedup(&mut dataset), 0);
+
+    // Splitting the raw rows leaves copies of the first two rows in the held-out part
+    let (train, held_out) = train_validation_split(&original, 0.6);
+    assert_eq!(shared_rows(&train, &held_out), 3);
+    let (train, held_out) = train_validation_split(&dataset, 1.0 / 3.0);
+    assert_eq!(shared_rows(&train, &held_out), 1);
+}
+
+#[test]
+fn tiny_datasets_split_only_when_each_side_and_class_keeps_rows() {
+    let dataset = |labels: &[f64]| Dataset {
+        features: Array2::from_shape_fn((labels.len(), 2), |(i, j)| (i * 2 + j) as f64),
+        labels: Array2::from_shape_vec((labels.len(), 1), labels.to_vec()).unwrap(),
+        weights: None,
+    };
+
+    let five = dataset(&[0.0, 1.0, 1.0, 0.0, 1.0]);
+    assert!(check_split(&five, 0.2).is_ok());
+    let (train, validation) = train_validation_split(&five, 0.2);
+    assert_eq!((train.n_samples(), validation.n_samples()), (4, 1));
+
+    // A batch larger than the data is the whole data, once
+    let batches: Vec<_> = five.batches(32, true, 1).collect();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].n_samples(), 5);
+
+    match check_split(&dataset(&[1.0]), 0.2) {
+        Err(NnError::TooFewSamples { needed, found: 1, .. }) => assert_eq!(needed, 3),
+        other => panic!("expected TooFewSamples, got {:?}", other),
+    }
+    match check_split(&dataset(&[0.0, 1.0, 1.0, 1.0, 1.0]), 0.2) {
+        Err(NnError::TooFewSamples { needed, found: 1, purpose }) => {
+            assert_eq!(needed, MIN_SAMPLES_PER_CLASS);
+            assert!(purpose.contains("not potable"), "{}", purpose);
+        }
+        other => panic!("expected TooFewSamples, got {:?}", other),
+    }
+    // Continuous labels have no classes to count
+    assert!(check_split(&dataset(&[0.5, 1.5, 2.5, 3.5, 4.5]), 0.2).is_ok());
+    assert!(check_split(&five, 0.0).is_err());
+}
+
+#[test]
+fn three_way_split_is_stratified_disjoint_and_seeded() {
+    // 30 positives among 100 rows, the feature is the row index
+    let dataset = Dataset {
+        features: Array2::from_shape_fn((100, 1), |(i, _)| i as f64),
+        labels: Array2::from_shape_fn((100, 1), |(i, _)| if i % 10 < 3 { 1.0 } else { 0.0 }),
+        weights: Some(Array2::from_shape_fn((100, 1), |(i, _)| i as f64)),
+    };
+    let split = |val, test, seed| stratified_split(&dataset, val, test, &mut StdRng::seed_from_u64(seed)).unwrap();
+    let positives = |d: &Dataset| d.labels.sum();
+
+    let (train, validation, test) = split(0.2, 0.1, 3);
+    let (validation, test) = (validation.unwrap(), test.unwrap());
+    assert_eq!((train.n_samples(), validation.n_samples(), test.n_samples()), (70, 20, 10));
+    assert_eq!((positives(&train), positives(&validation), positives(&test)), (21.0, 6.0, 3.0));
+    // Every row lands in exactly one part, with its own label and weight
+    let mut rows: Vec<f64> = [&train, &validation, &test].iter().flat_map(|d| d.features.iter().copied()).collect();
+    rows.sort_by(f64::total_cmp);
+    assert_eq!(rows, (0..100).map(|i| i as f64).collect::<Vec<_>>());
+    assert_eq!(test.weights.as_ref().unwrap().column(0), test.features.column(0));
+
+    let (_, _, same) = split(0.2, 0.1, 3);
+    assert_eq!(same.unwrap().features, test.features);
+    let (_, _, other) = split(0.2, 0.1, 4);
+    assert_ne!(other.unwrap().features, test.features);
+
+    let (train, validation, test) = split(0.2, 0.0, 3);
+    assert_eq!((train.n_samples(), validation.map(|d| d.n_samples()), test.is_none()), (80, Some(20), true));
+
+    for (val, test) in [(0.6, 0.4), (0.5, 0.7), (-0.1, 0.2)] {
+        assert!(matches!(stratified_split(&dataset, val, test, &mut StdRng::seed_from_u64(3)), Err(NnError::Config(_))));
+    }
+    let three = dataset.select_rows(&[0, 1, 2]);
+    assert!(matches!(
+        stratified_split(&three, 0.2, 0.1, &mut StdRng::seed_from_u64(3)),
+        Err(NnError::TooFewSamples { found: 3, .. })
+    ));
+}
+
+#[test]
+fn batch_samples_outside_physical_bounds_are_skipped() {
+    let path = write_temp_csv(
+        "out_of_range.csv",
+        b"ph,Hardness,Solids,Chloramines,Sulfate,Conductivity,Organic_carbon,Trihalomethanes,Turbidity\n\
+          7.0,204.5,20791.3,7.3,368.5,564.3,10.4,86.9,2.96\n\
+          15.0,204.5,20791.3,7.3,368.5,564.3,10.4,86.9,2.96\n\
+          7.0,204.5,20791.3,7.3,368.5,564.3,10.4,86.9,-1\n",
+    );
+
+    let file = read_samples(path.to_str().unwrap(), None).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(file.samples.nrows(), 1);
+    assert_eq!(file.skipped, vec![
+        (3, "pH = 15 is outside its possible range (0 to 14)".to_string()),
+        (4, "Turbidity = -1 is outside its possible range (at least 0)".to_string()),
+    ]);
+}
+
+#[test]
+fn batch_samples_are_read_in_the_detected_or_given_format() {
+    let path = write_temp_csv(
+        "semicolon_samples.csv",
+        b"ph;Hardness;Solids;Chloramines;Sulfate;Conductivity;Organic_carbon;Trihalomethanes;Turbidity\n\
+          7,5;204,5;20791,3;7,3;368,5;564,3;10,4;86,9;2,96\n",
+    );
+
+    let detected = read_samples(path.to_str().unwrap(), None).unwrap();
+    let given = read_samples(path.to_str().unwrap(), Some(CsvFormat::SEMICOLON)).unwrap();
+    let as_points = read_samples(path.to_str().unwrap(), Some(";.".parse().unwrap())).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(detected.samples.row(0).to_vec(), vec![7.5, 204.5, 20791.3, 7.3, 368.5, 564.3, 10.4, 86.9, 2.96]);
+    assert_eq!(given.samples, detected.samples);
+    assert_eq!((as_points.samples.nrows(), as_points.skipped.len()), (0, 1));
+}
@@ -0,0 +1,68 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use log::{Level, LevelFilter, Log, Record};
+use water_potability_nn::logging::Logger;
+
+// Writer the test can read back after handing it to the logger
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+fn log(logger: &Logger, level: Level, target: &str, message: &str) {
+    logger.log(&Record::builder().level(level).target(target).args(format_args!("{}", message)).build());
+}
+
+#[test]
+fn records_are_filtered_by_level_and_info_keeps_the_bare_message() {
+    let captured = Captured::default();
+    let logger = Logger::new(LevelFilter::Info, Box::new(captured.clone()));
+
+    log(&logger, Level::Info, "water_potability_nn::frontend_qt", "Epoch 100/2000: Accuracy = 61.25%, Loss = 0.6612");
+    log(&logger, Level::Warn, "water_potability_nn", "Failed to save the session");
+    log(&logger, Level::Debug, "water_potability_nn", "hidden at info");
+    // Other crates only get through from warn up
+    log(&logger, Level::Info, "winit::platform", "hidden dependency chatter");
+    log(&logger, Level::Error, "eframe", "Window creation failed");
+
+    assert_eq!(
+        captured.text(),
+        "Epoch 100/2000: Accuracy = 61.25%, Loss = 0.6612\n\
+         WARN: Failed to save the session\n\
+         ERROR: Window creation failed\n"
+    );
+}
+
+#[test]
+fn structured_lines_carry_a_timestamp_level_and_module() {
+    let captured = Captured::default();
+    let logger = Logger::new(LevelFilter::Debug, Box::new(captured.clone())).structured();
+
+    log(&logger, Level::Debug, "water_potability_nn::frontend_qt", "Seed: 42");
+    log(&logger, Level::Trace, "water_potability_nn", "hidden at debug");
+
+    let text = captured.text();
+    let line = text.strip_suffix('\n').unwrap();
+    assert!(!line.contains('\n'), "{}", text);
+    // "YYYY-MM-DD HH:MM:SS.mmm DEBUG water_potability_nn::frontend_qt: Seed: 42"
+    let (date, rest) = line.split_once(' ').unwrap();
+    let (time, rest) = rest.split_once(' ').unwrap();
+    assert_eq!((date.len(), time.len()), (10, 12), "{}", line);
+    assert_eq!(rest, "DEBUG water_potability_nn::frontend_qt: Seed: 42");
+}
@@ -0,0 +1,37 @@
+use water_potability_nn::metric_series::MetricSeries;
+
+#[test]
+fn non_finite_values_are_kept_off_the_curve_and_its_bounds() {
+    let mut losses = MetricSeries::default();
+    losses.push(0, 0.8, 8);
+    losses.push(1, f64::NAN, 8);
+    losses.push(2, f64::INFINITY, 8);
+    losses.push(3, 0.6, 8);
+    losses.push(4, f64::NEG_INFINITY, 8);
+
+    assert!(losses.points().iter().all(|p| p.value.is_finite()));
+    assert_eq!(losses.points().iter().map(|p| p.epoch).collect::<Vec<_>>(), vec![0.0, 3.0]);
+    assert_eq!(losses.dropped().iter().map(|p| p.epoch).collect::<Vec<_>>(), vec![1.0, 2.0, 4.0]);
+    assert_eq!((losses.min_value(), losses.max_value()), (0.0, 0.8));
+    assert_eq!((losses.last(), losses.last_epoch()), (Some(0.6), Some(4)));
+    assert_eq!(losses.tail(5), vec![0.8, 0.6]);
+
+    // Only NaN so far: nothing to draw, the bounds stay at their defaults
+    let mut diverged = MetricSeries::default();
+    diverged.push(0, f64::NAN, 8);
+    assert!(diverged.is_empty());
+    assert_eq!((diverged.min_value(), diverged.max_value()), (0.0, 0.0));
+    assert_eq!(diverged.newest().map(|p| p.update), Some(0.0));
+
+    // Decimation and the dropped markers stay within the cap
+    let mut long = MetricSeries::default();
+    for epoch in 0..100 {
+        long.push(epoch, if epoch % 3 == 0 { f64::NAN } else { epoch as f64 }, 10);
+    }
+    assert!(long.points().len() <= 20 && long.dropped().len() == 10);
+    assert!(long.max_value().is_finite() && long.max_value() == 98.0);
+    assert_eq!(long.dropped().last().unwrap().epoch, 99.0);
+
+    long.clear();
+    assert!(long.is_empty() && long.dropped().is_empty() && long.newest().is_none());
+}
@@ -0,0 +1,203 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use ndarray::Array2;
+
+use water_potability_nn::data_loader::{Dataset, ScaleMode, Scaler};
+use water_potability_nn::metrics::brier_score;
+use water_potability_nn::neural_network::{
+    export_rust, load_model, load_model_for, make_prediction, make_predictions, read_model_header, save_model, Activation, Float, Init, Network,
+    NetworkBuilder, TaskType, MODEL_FORMAT_VERSION,
+};
+use water_potability_nn::NnError;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn saved_model_round_trips_with_its_scaler() {
+    let mut network = NetworkBuilder::new(3).hidden_layers(&[4, 2]).activation(Activation::Tanh).seed(9).build().unwrap();
+    let data = Array2::from_shape_fn((5, 3), |(i, j)| (i * 3 + j) as f64);
+    network.set_scaler(Scaler::fit(&data, ScaleMode::MinMax));
+
+    let path = temp_path("round_trip.model");
+    save_model(&network, path.to_str().unwrap()).unwrap();
+    let header = read_model_header(path.to_str().unwrap()).unwrap();
+    let loaded = load_model(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(header.version, MODEL_FORMAT_VERSION);
+    assert_eq!(header.layer_sizes, vec![3, 4, 2, 1]);
+    assert_eq!(header.activation, Activation::Tanh);
+    assert_eq!(header.task, TaskType::BinaryClassification);
+    assert_eq!(header.scale_mode, Some(ScaleMode::MinMax));
+
+    let sample = [4.0, 7.5, 1.0];
+    assert_eq!(
+        make_prediction(&loaded, &sample).unwrap().probability,
+        make_prediction(&network, &sample).unwrap().probability
+    );
+    assert_eq!(loaded.scaler().unwrap().feature_stats(), network.scaler().unwrap().feature_stats());
+}
+
+#[test]
+fn rejects_other_versions_and_foreign_files() {
+    let network = NetworkBuilder::new(2).hidden_layers(&[3]).seed(1).build().unwrap();
+    let path = temp_path("versioned.model");
+    save_model(&network, path.to_str().unwrap()).unwrap();
+
+    // Bump the version field that follows the 4 magic bytes
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[4..8].copy_from_slice(&(MODEL_FORMAT_VERSION + 1).to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+    let error = load_model(path.to_str().unwrap()).unwrap_err();
+    assert!(matches!(error, NnError::UnsupportedVersion { found, expected, .. } if found == expected + 1), "{}", error);
+
+    fs::write(&path, b"ph,Hardness\n7.0,204.5\n").unwrap();
+    let error = load_model(path.to_str().unwrap()).unwrap_err().to_string();
+    assert!(error.contains("not a saved water potability model"), "{}", error);
+
+    // Truncated weights
+    bytes[4..8].copy_from_slice(&MODEL_FORMAT_VERSION.to_le_bytes());
+    fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+    let error = load_model(path.to_str().unwrap()).unwrap_err().to_string();
+    fs::remove_file(&path).unwrap();
+    assert!(error.contains("truncated"), "{}", error);
+}
+
+#[test]
+fn calibration_is_saved_with_the_model() {
+    let mut network = NetworkBuilder::new(1).hidden_layers(&[2]).seed(4).build().unwrap();
+    let held_out = Dataset {
+        features: Array2::from_shape_fn((40, 1), |(i, _)| i as f64 / 10.0),
+        labels: Array2::from_shape_fn((40, 1), |(i, _)| if i % 4 == 0 { 1.0 } else { 0.0 }),
+        weights: None,
+    };
+    let brier_before = brier_score(&network, &held_out);
+    let calibration = network.calibrate(&held_out);
+    // Fitting on the held-out data cannot make the fit to it worse
+    assert!(brier_score(&network, &held_out) <= brier_before);
+
+    let path = temp_path("calibrated.model");
+    save_model(&network, path.to_str().unwrap()).unwrap();
+    let loaded = load_model(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.calibration(), Some(calibration));
+    assert_eq!(
+        make_prediction(&loaded, &[1.5]).unwrap().probability,
+        make_prediction(&network, &[1.5]).unwrap().probability
+    );
+}
+
+#[test]
+fn input_dimension_is_checked_for_predictions_and_loading() {
+    let network = NetworkBuilder::new(3).hidden_layers(&[2]).seed(2).build().unwrap();
+    assert!(matches!(
+        make_prediction(&network, &[1.0, 2.0]),
+        Err(NnError::InputDimension { expected: 3, found: 2 })
+    ));
+
+    let path = temp_path("input_dim.model");
+    save_model(&network, path.to_str().unwrap()).unwrap();
+    let mismatch = load_model_for(path.to_str().unwrap(), 9);
+    let matching = load_model_for(path.to_str().unwrap(), 3);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(mismatch, Err(NnError::InputDimension { expected: 3, found: 9 })));
+    assert_eq!(matching.unwrap().input_dim(), 3);
+}
+
+#[test]
+fn single_predictions_match_batch_predictions() {
+    let network = NetworkBuilder::new(3).hidden_layers(&[8, 4]).seed(5).build().unwrap();
+    let samples = Array2::from_shape_fn((6, 3), |(i, j)| (i as f64 - 2.5) * (j as f64 + 0.5));
+
+    let batch = make_predictions(&network, &samples).unwrap();
+    for (row, expected) in samples.outer_iter().zip(&batch) {
+        let single = make_prediction(&network, &row.to_vec()).unwrap();
+        assert!((single.probability - expected.probability).abs() < 1e-12);
+        assert_eq!(single.is_potable, expected.is_potable);
+    }
+}
+
+#[test]
+fn verbose_forward_pass_returns_every_layer_and_the_prediction() {
+    let network = NetworkBuilder::new(3).hidden_layers(&[8, 4]).activation(Activation::Relu).seed(9).build().unwrap();
+    let sample = [0.4, -1.2, 2.0];
+
+    let layers = network.forward_verbose(&sample).unwrap();
+    assert_eq!(layers.iter().map(Vec::len).collect::<Vec<_>>(), vec![8, 4, 1]);
+    assert!(layers[..2].iter().flatten().all(|&a| a >= 0.0), "ReLU activations are never negative");
+    let expected = network.predict_proba(&Array2::from_shape_vec((1, 3), sample.to_vec()).unwrap())[(0, 0)];
+    assert_eq!(layers[2][0], expected);
+
+    // Biases start at zero, so the first hidden layer is the ReLU of the input times its weights
+    let weights = network.layer_weights(0).unwrap();
+    for (j, &activation) in layers[0].iter().enumerate() {
+        let z: Float = sample.iter().enumerate().map(|(i, &x)| x as Float * weights[(i, j)]).sum();
+        assert!((activation as Float - z.max(0.0)).abs() < 1e-5, "unit {}: {} vs {}", j, activation, z);
+    }
+
+    assert!(matches!(network.forward_verbose(&[1.0, 2.0]), Err(NnError::InputDimension { expected: 3, found: 2 })));
+}
+
+// Compiles the exported source with a main that prints `predict` for every
+// sample, and returns the printed probabilities
+fn run_exported(network: &Network, samples: &Array2<f64>, name: &str) -> Vec<f64> {
+    let dir = temp_path(name);
+    fs::create_dir_all(&dir).unwrap();
+    export_rust(network, dir.join("model.rs").to_str().unwrap()).unwrap();
+    let rows: Vec<String> = samples.rows().into_iter().map(|row| format!("{:?}", row.to_vec())).collect();
+    fs::write(
+        dir.join("main.rs"),
+        format!(
+            "include!(\"model.rs\");\nfn main() {{\n    for sample in [{}] {{\n        println!(\"{{:?}}\", predict(&sample));\n    }}\n}}\n",
+            rows.join(", ")
+        ),
+    )
+    .unwrap();
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let binary = dir.join("exported");
+    let build = Command::new(rustc)
+        .args(["--edition", "2021", "-D", "warnings", "-o"])
+        .arg(&binary)
+        .arg(dir.join("main.rs"))
+        .output()
+        .unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    let output = Command::new(&binary).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    String::from_utf8(output.stdout).unwrap().lines().map(|line| line.parse().unwrap()).collect()
+}
+
+#[test]
+fn exported_rust_source_predicts_like_the_network() {
+    let samples = Array2::from_shape_fn((12, 9), |(i, j)| ((i * 7 + j * 3) % 11) as f64 * (j + 1) as f64);
+
+    // Scaled, calibrated, two ReLU layers
+    let mut network = NetworkBuilder::new(9).hidden_layers(&[6, 4]).seed(5).build().unwrap();
+    network.set_scaler(Scaler::fit(&samples, ScaleMode::Standardize));
+    let held_out = Dataset {
+        features: samples.clone(),
+        labels: Array2::from_shape_fn((12, 1), |(i, _)| (i % 3 == 0) as u8 as f64),
+        weights: None,
+    };
+    network.calibrate(&held_out);
+    // Unscaled inputs through a tanh layer
+    let plain = NetworkBuilder::new(9).hidden_layers(&[5]).activation(Activation::Tanh).seed(6).build().unwrap();
+    // Linear output, values well outside [0, 1]
+    let regression = NetworkBuilder::new(9).hidden_layers(&[5]).task(TaskType::Regression).init(Init::He).seed(7).build().unwrap();
+
+    // The export computes in f64 whatever precision the network was built in
+    let tolerance = 10f64.powi(3 - Float::DIGITS as i32);
+    for (network, name) in [(&network, "export_scaled"), (&plain, "export_plain"), (&regression, "export_regression")] {
+        let exported = run_exported(network, &samples, name);
+        let expected = make_predictions(network, &samples).unwrap();
+        assert_eq!(exported.len(), expected.len());
+        for (got, want) in exported.iter().zip(&expected) {
+            assert!((got - want.probability).abs() < tolerance, "{} vs {}", got, want.probability);
+        }
+    }
+}
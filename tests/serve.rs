@@ -0,0 +1,68 @@
+#![cfg(feature = "serve")]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use ndarray::Array2;
+use water_potability_nn::data_loader::{ScaleMode, Scaler};
+use water_potability_nn::neural_network::{format_output, make_prediction, NetworkBuilder};
+use water_potability_nn::serve::{handle_connection, parse_features};
+
+const SAMPLE: [f64; 9] = [7.0, 200.0, 20000.0, 7.0, 330.0, 420.0, 14.0, 66.0, 4.0];
+
+// Sends one raw request to a server answering a single connection
+fn exchange(network: &water_potability_nn::neural_network::Network, request: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut client = TcpStream::connect(addr).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    thread::scope(|scope| {
+        scope.spawn(|| handle_connection(network, stream).unwrap());
+        client.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    })
+}
+
+fn post(body: &str) -> String {
+    format!("POST /predict HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+#[test]
+fn predict_endpoint_scales_inputs_like_make_prediction() {
+    let mut network = NetworkBuilder::new(9).hidden_layers(&[6]).seed(3).build().unwrap();
+    let training = Array2::from_shape_fn((20, 9), |(i, j)| SAMPLE[j] * (0.5 + i as f64 / 20.0));
+    network.set_scaler(Scaler::fit(&training, ScaleMode::Standardize));
+    let expected = make_prediction(&network, &SAMPLE).unwrap();
+
+    let body = "{\"ph\": 7, \"Hardness\": 200, \"solids\": 20000, \"Chloramines\": 7, \"Sulfate\": 330, \
+                \"Conductivity\": 420, \"organic_carbon\": 14, \"Trihalomethanes\": 66, \"Turbidity\": 4}";
+    let response = exchange(&network, &post(body));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with(&format!(
+        "\r\n\r\n{{\"is_potable\":{},\"probability\":{}}}",
+        expected.is_potable, format_output(expected.probability)
+    )));
+
+    // Malformed or out-of-range input is the client's fault
+    for bad in ["{\"pH\": 7", "[7, 200]", "{\"pH\": \"seven\"}", "[15, 200, 20000, 7, 330, 420, 14, 66, 4]"] {
+        let response = exchange(&network, &post(bad));
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}: {}", bad, response);
+        assert!(response.contains("{\"error\":"));
+    }
+    assert!(exchange(&network, "GET /health HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn feature_bodies_name_every_feature_or_list_them_in_order() {
+    assert_eq!(parse_features(" [7, 200, 20000, 7, 330, 420, 14, 66, 4] ").unwrap(), SAMPLE);
+    assert_eq!(parse_features("{\"pH\": 7}").unwrap_err(), "missing feature 'Hardness'");
+    assert_eq!(parse_features("{\"color\": 1}").unwrap_err(), "unknown feature 'color'");
+    assert_eq!(
+        parse_features("[-1, 200, 20000, 7, 330, 420, 14, 66, 4]").unwrap_err(),
+        "pH = -1 is outside its possible range (0 to 14)"
+    );
+    assert!(parse_features("[7, 200, 20000, 7, 330, 420, 14, 66, 4] extra").is_err());
+}
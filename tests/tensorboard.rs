@@ -0,0 +1,58 @@
+#![cfg(feature = "tensorboard")]
+
+use std::fs;
+
+use ndarray::array;
+use water_potability_nn::data_loader::Dataset;
+use water_potability_nn::neural_network::{train_network, NetworkBuilder, TrainingParams};
+
+// Reference CRC-32C, masked the way TFRecord stores it
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+        }
+    }
+    (!crc).rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+#[test]
+fn training_writes_checksummed_scalar_events() {
+    let log_dir = std::env::temp_dir().join(format!("{}_tensorboard", std::process::id()));
+    let dataset = Dataset {
+        features: array![[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]],
+        labels: array![[0.0], [1.0], [1.0], [1.0]],
+        weights: None,
+    };
+    let params = TrainingParams { epochs: 5, log_dir: Some(log_dir.clone()), ..TrainingParams::default() };
+    let mut network = NetworkBuilder::new(2).hidden_layers(&[4]).seed(3).build().unwrap();
+    train_network(&mut network, &dataset, &params, |_, _, _| {}).unwrap();
+
+    let files: Vec<_> = fs::read_dir(&log_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].file_name().unwrap().to_str().unwrap().starts_with("events.out.tfevents."));
+    let bytes = fs::read(&files[0]).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let mut records = Vec::new();
+    let mut rest = &bytes[..];
+    while !rest.is_empty() {
+        let (header, tail) = rest.split_at(12);
+        let length = u64::from_le_bytes(header[..8].try_into().unwrap()) as usize;
+        assert_eq!(u32::from_le_bytes(header[8..].try_into().unwrap()), masked_crc32c(&header[..8]));
+        let (data, tail) = tail.split_at(length);
+        assert_eq!(u32::from_le_bytes(tail[..4].try_into().unwrap()), masked_crc32c(data));
+        records.push(data.to_vec());
+        rest = &tail[4..];
+    }
+
+    // Version header, then loss, accuracy and learning rate per epoch
+    let contains = |record: &[u8], text: &str| record.windows(text.len()).any(|w| w == text.as_bytes());
+    assert_eq!(records.len(), 1 + 3 * params.epochs);
+    assert!(contains(&records[0], "brain.Event:2"));
+    for tag in ["train/loss", "train/accuracy", "train/learning_rate"] {
+        assert_eq!(records.iter().filter(|record| contains(record, tag)).count(), params.epochs);
+    }
+}
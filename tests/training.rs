@@ -0,0 +1,1181 @@
+use ndarray::Array2;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, StandardNormal};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use water_potability_nn::cross_validation::cross_validate;
+use water_potability_nn::data_loader::{Batch, DataSource, Dataset, ScaleMode, Scaler, NUM_FEATURES};
+use water_potability_nn::live_metrics::{SharedMetrics, TrainingStatus};
+use water_potability_nn::lr_finder::{lr_range_test, LrRangeTest};
+use water_potability_nn::metrics::{
+    average_precision, evaluate, evaluate_regression, evaluate_thresholds, explain_prediction, permutation_importance, pr_curve, roc_auc, sweep_feature, ThresholdMetrics,
+};
+use water_potability_nn::neural_network::{
+    create_network, dry_run, make_prediction, train_network, train_network_validated, train_network_with_events, Activation, DivergencePolicy, EpochState, Float,
+    LossFn, Network, NetworkBuilder, StopReason, TaskType, TrainingCallback, TrainingEvent, TrainingParams, ValidationMetrics, WithBaseline, COLLAPSE_EPOCHS, COLLAPSE_STD,
+    MAX_DIVERGENCE_RETRIES, MIN_TRAINING_SAMPLES,
+};
+use water_potability_nn::pause::PauseSwitch;
+use water_potability_nn::progress::progress_channel;
+use water_potability_nn::snapshot::Snapshot;
+use water_potability_nn::variance::{accuracy_spread, seed_variance};
+use water_potability_nn::NnError;
+
+// Direction separating the two classes: label is 1 when features · W > 0
+const W: [f64; NUM_FEATURES] = [1.0, -0.5, 0.8, 0.0, -1.2, 0.3, 0.6, -0.7, 0.9];
+
+fn separable_dataset(n_samples: usize, seed: u64) -> Dataset {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let features = Array2::from_shape_fn((n_samples, NUM_FEATURES), |_| StandardNormal.sample(&mut rng));
+    let labels = Array2::from_shape_fn((n_samples, 1), |(i, _)| {
+        let score: f64 = features.row(i).iter().zip(W).map(|(x, w)| x * w).sum();
+        if score > 0.0 { 1.0 } else { 0.0 }
+    });
+    Dataset { features, labels, weights: None }
+}
+
+fn params() -> TrainingParams {
+    TrainingParams {
+        epochs: 300,
+        hidden_layers: 1,
+        neurons_per_layer: 16,
+        seed: Some(42),
+        ..TrainingParams::default()
+    }
+}
+
+fn train(dataset: &Dataset) -> (Vec<f64>, Vec<f64>, Network) {
+    let params = params();
+    let mut network = create_network(&params).unwrap();
+    let (mut accuracies, mut losses) = (Vec::new(), Vec::new());
+    train_network(&mut network, dataset, &params, |_, accuracy, loss| {
+        accuracies.push(accuracy);
+        losses.push(loss);
+    })
+    .unwrap();
+    (accuracies, losses, network)
+}
+
+#[test]
+fn learns_linearly_separable_data() {
+    let dataset = separable_dataset(400, 7);
+    let (accuracies, _, network) = train(&dataset);
+
+    assert_eq!(accuracies.len(), params().epochs);
+    let final_accuracy = *accuracies.last().unwrap();
+    assert!(final_accuracy > 90.0, "final training accuracy {:.2}%", final_accuracy);
+
+    // Far along W on either side the verdict is unambiguous
+    let positive: Vec<f64> = W.iter().map(|w| w * 2.0).collect();
+    let negative: Vec<f64> = W.iter().map(|w| -w * 2.0).collect();
+    assert!(make_prediction(&network, &positive).unwrap().is_potable);
+    assert!(!make_prediction(&network, &negative).unwrap().is_potable);
+}
+
+#[test]
+fn seeded_training_is_deterministic() {
+    let dataset = separable_dataset(200, 11);
+    let (_, first, _) = train(&dataset);
+    let (_, second, _) = train(&dataset);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn a_sample_weight_of_two_trains_like_the_sample_appearing_twice() {
+    let dataset = separable_dataset(100, 13);
+    let doubled: Vec<usize> = (0..100).chain(0..10).collect();
+    let duplicated = dataset.select_rows(&doubled);
+    let weighted = Dataset {
+        weights: Some(Array2::from_shape_fn((100, 1), |(i, _)| if i < 10 { 2.0 } else { 1.0 })),
+        ..dataset.clone()
+    };
+    // Scaling every weight alike changes nothing
+    let uniform = Dataset { weights: Some(Array2::from_elem((100, 1), 3.0)), ..dataset.clone() };
+
+    let params = TrainingParams { epochs: 50, ..params() };
+    let losses = |data: &Dataset| {
+        let mut network = create_network(&params).unwrap();
+        let mut losses = Vec::new();
+        train_network(&mut network, data, &params, |_, _, loss| losses.push(loss)).unwrap();
+        losses
+    };
+    let tolerance = 10f64.powi(3 - Float::DIGITS as i32);
+    let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < tolerance);
+    assert!(close(&losses(&weighted), &losses(&duplicated)));
+    assert!(close(&losses(&uniform), &losses(&dataset)));
+    assert!(!close(&losses(&weighted), &losses(&dataset)));
+}
+
+// Dataset wrapper recording the rows of every batch training asks for
+struct RecordedBatches {
+    dataset: Dataset,
+    requested: RefCell<Vec<Vec<usize>>>,
+}
+
+impl DataSource for RecordedBatches {
+    fn n_samples(&self) -> usize {
+        self.dataset.n_samples()
+    }
+
+    fn n_features(&self) -> usize {
+        self.dataset.features.ncols()
+    }
+
+    fn rows(&self, indices: &[usize]) -> Batch<'_> {
+        self.requested.borrow_mut().push(indices.to_vec());
+        self.dataset.rows(indices)
+    }
+}
+
+#[test]
+fn fixed_batch_order_reuses_one_shuffle_every_epoch_and_run() {
+    let epoch_orders = |fixed_batch_order: bool| {
+        let data = RecordedBatches { dataset: separable_dataset(40, 151), requested: RefCell::new(Vec::new()) };
+        let params = TrainingParams { epochs: 3, batch_size: 10, fixed_batch_order, ..params() };
+        let mut network = create_network(&params).unwrap();
+        train_network(&mut network, &data, &params, |_, _, _| {}).unwrap();
+        let batches = data.requested.into_inner();
+        batches.chunks(4).map(|epoch| epoch.concat()).collect::<Vec<_>>()
+    };
+
+    let fixed = epoch_orders(true);
+    assert_eq!(fixed.len(), 3);
+    assert_ne!(fixed[0], (0..40).collect::<Vec<_>>(), "still shuffled");
+    assert!(fixed.iter().all(|order| *order == fixed[0]));
+    assert_eq!(epoch_orders(true), fixed);
+
+    let reshuffled = epoch_orders(false);
+    assert_eq!(reshuffled[0], fixed[0], "the first epoch draws the same order");
+    assert_ne!(reshuffled[1], reshuffled[0]);
+}
+
+#[test]
+fn auto_reduce_lr_halves_rate_when_loss_rises() {
+    let dataset = separable_dataset(200, 3);
+    let params = TrainingParams {
+        epochs: 100,
+        learning_rate: 50.0,  // Far too high, the loss oscillates
+        auto_reduce_lr: true,
+        lr_patience: 1,
+        ..params()
+    };
+    let mut network = create_network(&params).unwrap();
+    let mut reductions = Vec::new();
+    train_network_with_events(&mut network, &dataset, &params, |_, _, _| {}, |event| {
+        if let TrainingEvent::LearningRateReduced { learning_rate, .. } = event {
+            reductions.push(learning_rate);
+        }
+    })
+    .unwrap();
+
+    assert!(!reductions.is_empty());
+}
+
+#[test]
+fn sweep_feature_spans_training_range() {
+    let data = separable_dataset(200, 3);
+    let scaler = Scaler::fit(&data.features, ScaleMode::Standardize);
+    let stats = scaler.feature_stats().to_vec();
+    let mut network = create_network(&params()).unwrap();
+    network.set_scaler(scaler);
+
+    let base: Vec<f64> = stats.iter().map(|s| s.mean).collect();
+    let sweep = sweep_feature(&network, &base, 1, 11).unwrap();
+
+    assert_eq!(sweep.len(), 11);
+    assert_eq!(sweep[0].0, stats[1].min);
+    assert!((sweep[10].0 - stats[1].max).abs() < 1e-12);
+    assert!(sweep.iter().all(|&(_, p)| (0.0..=1.0).contains(&p)));
+    assert!(sweep_feature(&network, &base, stats.len(), 11).is_err());
+}
+
+#[test]
+fn permutation_importance_ranks_the_features_the_labels_depend_on() {
+    let dataset = separable_dataset(400, 31);
+    let scaler = Scaler::fit(&dataset.features, ScaleMode::Standardize);
+    let scaled = Dataset { features: scaler.transform(&dataset.features), labels: dataset.labels.clone(), weights: None };
+    let mut network = create_network(&params()).unwrap();
+    train_network(&mut network, &scaled, &params(), |_, _, _| {}).unwrap();
+    network.set_scaler(scaler);
+
+    let held_out = separable_dataset(300, 37);
+    let importance = permutation_importance(&network, &held_out).unwrap();
+    assert_eq!(importance, permutation_importance(&network, &held_out).unwrap(), "fixed seeds give stable results");
+    assert_eq!(importance.len(), NUM_FEATURES);
+
+    // W leaves feature 3 out entirely and weighs 4 and 0 most, 5 least
+    let least = (0..NUM_FEATURES).min_by(|&a, &b| importance[a].total_cmp(&importance[b])).unwrap();
+    assert_eq!(least, 3, "{:?}", importance);
+    assert!(importance[3].abs() < 3.0, "{:?}", importance);
+    assert!(importance[4] > 10.0 && importance[0] > 10.0, "{:?}", importance);
+    assert!(importance[5] < importance[4].min(importance[0]), "{:?}", importance);
+
+    let narrow = Dataset { features: held_out.features.slice(ndarray::s![.., ..3]).to_owned(), ..held_out };
+    assert!(matches!(permutation_importance(&network, &narrow), Err(NnError::InputDimension { expected: 9, found: 3 })));
+}
+
+#[test]
+fn explanations_follow_the_direction_each_feature_pushes() {
+    let dataset = separable_dataset(400, 31);
+    let scaler = Scaler::fit(&dataset.features, ScaleMode::Standardize);
+    let scaled = Dataset { features: scaler.transform(&dataset.features), labels: dataset.labels.clone(), weights: None };
+    let mut network = create_network(&params()).unwrap();
+    train_network(&mut network, &scaled, &params(), |_, _, _| {}).unwrap();
+    network.set_scaler(scaler);
+
+    // Far along W every feature W weighs pushes toward potable, most of all the heaviest
+    let sample: Vec<f64> = W.iter().map(|w| w * 2.0).collect();
+    let contributions = explain_prediction(&network, &sample).unwrap();
+    assert_eq!(contributions.len(), NUM_FEATURES);
+    for feature in [0, 2, 4, 8] {
+        assert!(contributions[feature] > 0.0, "{:?}", contributions);
+    }
+    let top = (0..NUM_FEATURES).max_by(|&a, &b| contributions[a].total_cmp(&contributions[b])).unwrap();
+    assert!([0, 4].contains(&top), "{:?}", contributions);
+    let opposite: Vec<f64> = W.iter().map(|w| -w * 2.0).collect();
+    assert!(explain_prediction(&network, &opposite).unwrap()[top] < 0.0);
+
+    // A regression output is its own logit: each contribution is the offset
+    // from the mean times the output's slope along that feature
+    let mut regression = NetworkBuilder::new(NUM_FEATURES)
+        .hidden_layers(&[6])
+        .activation(Activation::Tanh)
+        .task(TaskType::Regression)
+        .seed(5)
+        .build()
+        .unwrap();
+    regression.set_scaler(Scaler::fit(&dataset.features, ScaleMode::Standardize));
+    let stats = regression.scaler().unwrap().feature_stats().to_vec();
+    let contributions = explain_prediction(&regression, &sample).unwrap();
+    let h = 1e-3;
+    for (feature, stats) in stats.iter().enumerate() {
+        let output = |offset: f64| {
+            let mut moved = sample.clone();
+            moved[feature] += offset * stats.std;
+            make_prediction(&regression, &moved).unwrap().probability
+        };
+        let slope = (output(h) - output(-h)) / (2.0 * h);
+        let expected = (sample[feature] - stats.mean) / stats.std * slope;
+        assert!((contributions[feature] - expected).abs() < 1e-3, "feature {}: {} vs {}", feature, contributions[feature], expected);
+    }
+
+    assert!(explain_prediction(&regression, &sample[..3]).is_err());
+    assert!(matches!(explain_prediction(&create_network(&params()).unwrap(), &sample), Err(NnError::MissingScaler)));
+}
+
+#[test]
+fn roc_auc_is_the_share_of_correctly_ranked_pairs() {
+    let dataset = separable_dataset(200, 131);
+    let params = TrainingParams { epochs: 100, ..params() };
+    let mut network = create_network(&params).unwrap();
+    train_network(&mut network, &dataset, &params, |_, _, _| {}).unwrap();
+
+    let held_out = separable_dataset(60, 137);
+    let probabilities: Vec<f64> = (0..held_out.n_samples())
+        .map(|i| make_prediction(&network, held_out.features.row(i).as_slice().unwrap()).unwrap().probability)
+        .collect();
+    let class = |potable: bool| -> Vec<f64> {
+        probabilities.iter().zip(held_out.labels.iter()).filter(|&(_, &y)| (y == 1.0) == potable).map(|(&p, _)| p).collect()
+    };
+    let (mut pairs, mut ranked) = (0.0, 0.0);
+    for &positive in &class(true) {
+        for &negative in &class(false) {
+            pairs += 1.0;
+            ranked += if positive > negative { 1.0 } else if positive == negative { 0.5 } else { 0.0 };
+        }
+    }
+    let auc = roc_auc(&network, &held_out);
+    assert!((auc - ranked / pairs).abs() < 1e-12, "{} vs {}", auc, ranked / pairs);
+    assert!(auc > 0.9, "{}", auc);
+
+    let one_class = Dataset { labels: Array2::ones((60, 1)), ..held_out };
+    assert_eq!(roc_auc(&network, &one_class), 0.5);
+}
+
+#[test]
+fn threshold_report_matches_scoring_each_threshold_separately() {
+    let dataset = separable_dataset(200, 139);
+    let params = TrainingParams { epochs: 20, ..params() };
+    let mut network = create_network(&params).unwrap();
+    train_network(&mut network, &dataset, &params, |_, _, _| {}).unwrap();
+
+    let held_out = separable_dataset(80, 149);
+    let probabilities: Vec<f64> = (0..held_out.n_samples())
+        .map(|i| make_prediction(&network, held_out.features.row(i).as_slice().unwrap()).unwrap().probability)
+        .collect();
+    let thresholds = [0.9, 0.0, 0.3, 0.5, 0.7, 1.0];
+    let report = evaluate_thresholds(&network, &held_out, &thresholds);
+
+    for (scores, &threshold) in report.thresholds.iter().zip(&thresholds) {
+        let (mut tp, mut fp, mut false_negatives) = (0.0, 0.0, 0.0);
+        for (&p, &y) in probabilities.iter().zip(held_out.labels.iter()) {
+            match (p >= threshold, y == 1.0) {
+                (true, true) => tp += 1.0,
+                (true, false) => fp += 1.0,
+                (false, true) => false_negatives += 1.0,
+                (false, false) => {}
+            }
+        }
+        let correct = held_out.n_samples() as f64 - fp - false_negatives;
+        assert_eq!(scores.threshold, threshold);
+        assert!((scores.accuracy - correct / held_out.n_samples() as f64 * 100.0).abs() < 1e-9);
+        assert!((scores.precision - if tp + fp > 0.0 { tp / (tp + fp) } else { 0.0 }).abs() < 1e-12);
+        assert!((scores.recall - tp / (tp + false_negatives)).abs() < 1e-12);
+    }
+    assert_eq!(report.thresholds[1].recall, 1.0);
+    assert_eq!(report.thresholds[3], {
+        let evaluation = evaluate(&network, &held_out);
+        ThresholdMetrics { threshold: 0.5, accuracy: evaluation.accuracy, precision: evaluation.precision, recall: evaluation.recall, f1: evaluation.f1 }
+    });
+
+    assert_eq!(report.roc_auc, roc_auc(&network, &held_out));
+    assert_eq!(report.pr_curve, pr_curve(&network, &held_out));
+    assert_eq!(report.average_precision, average_precision(&report.pr_curve));
+    assert_eq!(report.roc_curve.first(), Some(&(0.0, 0.0)));
+    assert_eq!(report.roc_curve.last(), Some(&(1.0, 1.0)));
+    assert!(report.roc_curve.windows(2).all(|pair| pair[0].0 <= pair[1].0 && pair[0].1 <= pair[1].1));
+}
+
+#[test]
+fn l1_penalty_shrinks_weights() {
+    let dataset = separable_dataset(200, 11);
+    let positive: Vec<f64> = W.iter().map(|w| w * 2.0).collect();
+    let negative: Vec<f64> = W.iter().map(|w| -w * 2.0).collect();
+
+    let mut runs = Vec::new();
+    for l1_lambda in [0.0, 0.05] {
+        let params = TrainingParams { l1_lambda, ..params() };
+        let mut network = create_network(&params).unwrap();
+        let mut final_loss = 0.0;
+        train_network(&mut network, &dataset, &params, |_, _, loss| final_loss = loss).unwrap();
+        let spread = make_prediction(&network, &positive).unwrap().probability
+            - make_prediction(&network, &negative).unwrap().probability;
+        runs.push((final_loss, spread));
+    }
+
+    // The penalty is part of the reported loss, and smaller weights give less confident outputs
+    assert!(runs[1].0 > runs[0].0);
+    assert!(runs[1].1 < runs[0].1);
+}
+
+#[test]
+fn constant_outputs_are_reported_once_they_persist() {
+    let collapses = |dataset: &Dataset| {
+        let params = TrainingParams { epochs: 20, ..params() };
+        let mut network = create_network(&params).unwrap();
+        let mut collapses = Vec::new();
+        train_network_with_events(&mut network, dataset, &params, |_, _, _| {}, |event| {
+            if let TrainingEvent::OutputCollapsed { epoch, mean, std } = event {
+                collapses.push((epoch, mean, std));
+            }
+        })
+        .unwrap();
+        collapses
+    };
+
+    // Identical inputs can only ever get identical outputs
+    let mut identical = separable_dataset(100, 9);
+    identical.features.fill(0.0);
+    let reported = collapses(&identical);
+    assert_eq!(reported.len(), 1);
+    let (epoch, mean, std) = reported[0];
+    assert_eq!(epoch, COLLAPSE_EPOCHS - 1);
+    assert!(std < COLLAPSE_STD && (0.0..=1.0).contains(&mean));
+
+    assert!(collapses(&separable_dataset(100, 9)).is_empty());
+}
+
+#[test]
+fn gradient_norm_is_reported_every_epoch_when_enabled() {
+    let dataset = separable_dataset(200, 5);
+    let params = TrainingParams { track_gradient_norm: true, ..params() };
+    let mut network = create_network(&params).unwrap();
+    let mut norms = Vec::new();
+    train_network_with_events(&mut network, &dataset, &params, |_, _, _| {}, |event| {
+        if let TrainingEvent::GradientNorm { norm, .. } = event {
+            norms.push(norm);
+        }
+    })
+    .unwrap();
+
+    assert_eq!(norms.len(), params.epochs);
+    assert!(norms.iter().all(|n| n.is_finite() && *n >= 0.0));
+    // Gradients shrink as the separable data is fitted
+    assert!(norms.last().unwrap() < &norms[0]);
+}
+
+#[test]
+fn frozen_layers_keep_their_weights() {
+    let dataset = separable_dataset(200, 13);
+    let params = TrainingParams { hidden_layers: 2, frozen_layers: vec![0, 1], ..params() };
+    let mut network = create_network(&params).unwrap();
+    let before = network.clone();
+
+    train_network(&mut network, &dataset, &params, |_, _, _| {}).unwrap();
+
+    for layer in [0, 1] {
+        assert_eq!(
+            network.layer_weights(layer).unwrap().iter().map(|w| w.to_bits()).collect::<Vec<_>>(),
+            before.layer_weights(layer).unwrap().iter().map(|w| w.to_bits()).collect::<Vec<_>>(),
+        );
+    }
+    // Only the output layer was trained
+    assert_ne!(network.layer_weights(2), before.layer_weights(2));
+
+    let out_of_range = TrainingParams { frozen_layers: vec![5], ..params };
+    assert!(train_network(&mut network, &dataset, &out_of_range, |_, _, _| {}).is_err());
+}
+
+#[test]
+fn constant_feature_does_not_poison_training() {
+    let mut dataset = separable_dataset(200, 17);
+    dataset.features.column_mut(3).fill(7.0);  // W ignores feature 3 anyway
+    let scaler = Scaler::fit(&dataset.features, ScaleMode::Standardize);
+    assert_eq!(scaler.constant_columns(), vec![3]);
+
+    let scaled = Dataset { features: scaler.transform(&dataset.features), labels: dataset.labels.clone(), weights: None };
+    assert!(scaled.features.iter().all(|v| v.is_finite()));
+
+    let mut network = create_network(&params()).unwrap();
+    network.set_scaler(scaler);
+    let mut losses = Vec::new();
+    train_network(&mut network, &scaled, &params(), |_, _, loss| losses.push(loss)).unwrap();
+    assert!(losses.iter().all(|l| l.is_finite()));
+
+    // An unseen value in the constant column stays in a sane range
+    let mut sample = vec![0.0; NUM_FEATURES];
+    sample[3] = 8.0;
+    assert_eq!(network.scaler().unwrap().transform_row(&sample)[3], 1.0);
+    assert!(make_prediction(&network, &sample).unwrap().probability.is_finite());
+}
+
+#[test]
+fn cross_validation_is_independent_of_thread_count() {
+    let dataset = separable_dataset(300, 19);
+    let params = TrainingParams { epochs: 100, ..params() };
+
+    let sequential = cross_validate(&dataset, &params, 3, 1).unwrap();
+    let parallel = cross_validate(&dataset, &params, 3, 3).unwrap();
+
+    assert_eq!(sequential.folds.len(), 3);
+    for (a, b) in sequential.folds.iter().zip(&parallel.folds) {
+        assert_eq!((a.fold, a.seed), (b.fold, b.seed));
+        assert_eq!(a.evaluation, b.evaluation);
+    }
+    assert!(sequential.mean_accuracy() > 85.0, "mean accuracy {:.2}%", sequential.mean_accuracy());
+    assert!(cross_validate(&dataset, &params, 1, 1).is_err());
+}
+
+#[test]
+fn predicting_during_training_does_not_block_metric_updates() {
+    let dataset = separable_dataset(200, 23);
+    let params = TrainingParams { epochs: 200, ..params() };
+    let shared = Snapshot::new();
+    let training_done = AtomicBool::new(false);
+    let (sender, receiver) = channel();
+
+    let predictions = thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut network = create_network(&params).unwrap();
+            shared.publish(network.clone());
+            train_network(&mut network, &dataset, &params, |epoch, accuracy, loss| {
+                sender.send((epoch, accuracy, loss)).unwrap();
+                // Publish mid-run too so readers and the writer really overlap
+                if epoch % 20 == 0 {
+                    shared.publish(create_network(&params).unwrap());
+                }
+            })
+            .unwrap();
+            shared.publish(network);
+            training_done.store(true, Ordering::SeqCst);
+        });
+
+        let predictor = scope.spawn(|| {
+            let sample = [0.5; NUM_FEATURES];
+            let mut count = 0;
+            while !training_done.load(Ordering::SeqCst) {
+                if let Some(network) = shared.latest() {
+                    assert!(make_prediction(&network, &sample).unwrap().probability.is_finite());
+                    count += 1;
+                }
+            }
+            count
+        });
+
+        // Every epoch's update arrives, in order, while predictions are running
+        for expected in 0..params.epochs {
+            let (epoch, _, _) = receiver.recv_timeout(Duration::from_secs(30)).expect("training stalled");
+            assert_eq!(epoch, expected);
+        }
+        predictor.join().unwrap()
+    });
+
+    assert!(predictions > 0);
+    assert!(receiver.try_recv().is_err(), "no messages beyond one per epoch");
+}
+
+#[test]
+fn predictions_requested_over_a_channel_come_from_the_trained_model() {
+    let dataset = separable_dataset(400, 7);
+    let params = params();
+    let shared = Snapshot::new();
+    let (request_sender, request_receiver) = channel::<[f64; NUM_FEATURES]>();
+    let (answer_sender, answer_receiver) = channel();
+
+    // Far along W on either side, so the trained verdict is unambiguous
+    let potable: [f64; NUM_FEATURES] = W.map(|w| w * 2.0);
+    let not_potable: [f64; NUM_FEATURES] = W.map(|w| -w * 2.0);
+    let untrained = make_prediction(&create_network(&params).unwrap(), &potable).unwrap();
+
+    thread::scope(|scope| {
+        // Training thread: publishes the model once it is trained, as the backend does
+        scope.spawn(|| {
+            let mut network = create_network(&params).unwrap();
+            train_network(&mut network, &dataset, &params, |_, _, _| {}).unwrap();
+            shared.publish(network);
+        });
+        // Prediction thread: answers each request with the latest published model
+        scope.spawn(|| {
+            for features in request_receiver {
+                let answer = loop {
+                    if let Some(network) = shared.latest() {
+                        break make_prediction(&network, &features).unwrap();
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                };
+                answer_sender.send(answer).unwrap();
+            }
+        });
+
+        request_sender.send(potable).unwrap();
+        request_sender.send(not_potable).unwrap();
+        drop(request_sender);
+    });
+
+    let answers: Vec<_> = answer_receiver.try_iter().collect();
+    assert_eq!(answers.len(), 2);
+    assert!(answers[0].is_potable && answers[0].probability > 0.9, "{}", answers[0]);
+    assert!(!answers[1].is_potable && answers[1].probability < 0.1, "{}", answers[1]);
+    // A fresh network with the same seed would not be this confident
+    assert!((answers[0].probability - untrained.probability).abs() > 0.1, "{} vs untrained {}", answers[0], untrained);
+}
+
+#[test]
+fn paused_training_resumes_exactly_where_it_left_off() {
+    let dataset = separable_dataset(200, 113);
+    let params = TrainingParams { epochs: 30, batch_size: 32, ..params() };
+    let mut uninterrupted = create_network(&params).unwrap();
+    let expected = train_network(&mut uninterrupted, &dataset, &params, |_, _, _| {}).unwrap();
+
+    let switch = PauseSwitch::new();
+    switch.pause();
+    let live = SharedMetrics::new();
+    let (network, outcome) = thread::scope(|scope| {
+        let training = scope.spawn(|| {
+            let mut network = create_network(&params).unwrap();
+            let outcome = train_network(&mut network, &dataset, &params, (live.clone(), switch.clone())).unwrap();
+            (network, outcome)
+        });
+
+        // The first epoch ends and training holds there
+        while live.latest().is_none() {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(live.latest().unwrap().state.as_ref().unwrap().epoch, 0);
+        assert!(!training.is_finished());
+        switch.resume();
+        training.join().unwrap()
+    });
+
+    // Same epochs, metrics and weights as a run that never paused
+    assert_eq!(outcome, expected);
+    let sample = [0.3; NUM_FEATURES];
+    assert_eq!(make_prediction(&network, &sample).unwrap().probability, make_prediction(&uninterrupted, &sample).unwrap().probability);
+}
+
+#[test]
+fn live_metrics_can_be_polled_while_training_runs() {
+    let dataset = separable_dataset(200, 29);
+    let params = TrainingParams { epochs: 200, ..params() };
+    let live = SharedMetrics::new();
+
+    let polled = thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut network = create_network(&params).unwrap();
+            train_network(&mut network, &dataset, &params, live.clone()).unwrap();
+        });
+
+        // Epochs only move forward until the run reports its end
+        let mut polled = Vec::new();
+        loop {
+            let Some(metrics) = live.latest() else { continue };
+            let epoch = metrics.state.as_ref().unwrap().epoch;
+            assert!(polled.last().is_none_or(|&last| last <= epoch));
+            polled.push(epoch);
+            if metrics.status != TrainingStatus::Running {
+                break polled;
+            }
+        }
+    });
+    assert_eq!(polled.last(), Some(&199));
+    assert_eq!(live.latest().unwrap().status, TrainingStatus::Finished(StopReason::Completed));
+
+    // A diverging run is reported as failed
+    let diverging = TrainingParams { task: TaskType::Regression, loss: LossFn::Mse, learning_rate: 1e6, ..params };
+    let mut network = create_network(&diverging).unwrap();
+    let live = SharedMetrics::new();
+    assert!(train_network(&mut network, &dataset, &diverging, live.clone()).is_err());
+    let failed = live.latest().unwrap();
+    assert!(matches!(&failed.status, TrainingStatus::Failed(message) if message.contains("diverged")), "{:?}", failed.status);
+}
+
+#[test]
+fn divergence_policy_aborts_skips_or_retries_at_a_lower_rate() {
+    let dataset = separable_dataset(100, 43);
+    let diverging = TrainingParams { task: TaskType::Regression, loss: LossFn::Mse, learning_rate: 1e6, epochs: 50, ..params() };
+    let run = |divergence_policy| {
+        let params = TrainingParams { divergence_policy, ..diverging.clone() };
+        let mut network = create_network(&params).unwrap();
+        let mut taken = Vec::new();
+        let result = train_network_with_events(&mut network, &dataset, &params, |_, _, _| {}, |event| {
+            if let TrainingEvent::Diverged { action, learning_rate, .. } = event {
+                taken.push((action, learning_rate));
+            }
+        });
+        (result, taken)
+    };
+
+    let (result, taken) = run(DivergencePolicy::Abort);
+    assert!(matches!(result, Err(NnError::Diverged { .. })), "{:?}", result);
+    assert!(taken.is_empty());
+
+    // Every diverging update is thrown away, so training carries on to the end
+    let (result, taken) = run(DivergencePolicy::SkipBatch);
+    let outcome = result.unwrap();
+    assert_eq!(outcome.epochs_run, diverging.epochs);
+    assert!(outcome.final_metrics.unwrap().loss.is_finite());
+    assert!(!taken.is_empty() && taken.iter().all(|&(action, _)| action == DivergencePolicy::SkipBatch));
+
+    // Each retry halves the rate, until the retries of one update run out
+    let (result, taken) = run(DivergencePolicy::ReduceLr);
+    assert!(matches!(result, Err(NnError::Diverged { .. })), "{:?}", result);
+    assert!(taken.len() >= MAX_DIVERGENCE_RETRIES);
+    assert!(taken.iter().all(|&(action, _)| action == DivergencePolicy::ReduceLr));
+    let last_update = &taken[taken.len() - MAX_DIVERGENCE_RETRIES..];
+    assert!(last_update.windows(2).all(|pair| pair[1].1 == pair[0].1 / 2.0), "{:?}", taken);
+}
+
+#[test]
+fn seed_variance_bands_enclose_the_mean() {
+    let dataset = separable_dataset(150, 29);
+    let params = TrainingParams { epochs: 60, ..params() };
+
+    let sequential = seed_variance(&dataset, &params, 3, 1).unwrap();
+    let parallel = seed_variance(&dataset, &params, 3, 3).unwrap();
+
+    assert_eq!(sequential.seeds.len(), 3);
+    assert_eq!(sequential.spread.len(), params.epochs);
+    assert_eq!(sequential.spread, parallel.spread);
+    for band in &sequential.spread {
+        assert!(band.min <= band.mean && band.mean <= band.max, "{:?}", band);
+        assert!(band.std <= band.max - band.min);
+    }
+    assert!(sequential.spread.iter().any(|band| band.max > band.min), "different seeds should differ somewhere");
+
+    // Only epochs every curve reported are kept
+    let spread = accuracy_spread(&[vec![(0, 50.0), (10, 60.0)], vec![(10, 70.0), (20, 80.0)]]);
+    assert_eq!(spread.len(), 1);
+    assert_eq!((spread[0].epoch, spread[0].mean, spread[0].std), (10, 65.0, 5.0));
+    assert!(seed_variance(&dataset, &params, 1, 1).is_err());
+}
+
+#[test]
+fn lr_range_test_sweeps_rates_upward_until_the_loss_blows_up() {
+    let dataset = separable_dataset(200, 37);
+    let params = TrainingParams { batch_size: 20, ..params() };
+    let test = LrRangeTest { min_lr: 1e-4, max_lr: 1e3, steps: 80 };
+
+    let range = lr_range_test(&dataset, &params, &test).unwrap();
+    assert!(range.points.len() >= 2 && range.points.len() <= test.steps);
+    assert!((range.points[0].0 - 1e-4).abs() < 1e-12);
+    assert!(range.points.windows(2).all(|pair| pair[1].0 > pair[0].0));
+    let suggested = range.suggested.expect("the loss falls somewhere in the sweep");
+    assert!((1e-4..1e3).contains(&suggested));
+
+    // Seeded runs sweep identically
+    assert_eq!(lr_range_test(&dataset, &params, &test).unwrap().points, range.points);
+    assert!(lr_range_test(&dataset, &params, &LrRangeTest { min_lr: 1.0, max_lr: 0.1, steps: 10 }).is_err());
+    assert!(lr_range_test(&dataset, &params, &LrRangeTest { steps: 1, ..test }).is_err());
+}
+
+#[test]
+fn regression_predicts_a_continuous_target_and_reports_r_squared() {
+    // Quality score linear in the features, far outside [0, 1]
+    let mut dataset = separable_dataset(300, 41);
+    dataset.labels = Array2::from_shape_fn((300, 1), |(i, _)| {
+        5.0 + dataset.features.row(i).iter().zip(W).map(|(x, w)| x * w).sum::<f64>()
+    });
+    let params = TrainingParams { task: TaskType::Regression, loss: LossFn::Mse, learning_rate: 0.01, epochs: 400, ..params() };
+
+    let mut network = create_network(&params).unwrap();
+    let mut scores = Vec::new();
+    train_network(&mut network, &dataset, &params, |_, r_squared, _| scores.push(r_squared)).unwrap();
+    assert!(scores[0] < 0.5, "the untrained network explains little: {}", scores[0]);
+    assert!(*scores.last().unwrap() > 0.95, "final R² {}", scores.last().unwrap());
+
+    let fit = evaluate_regression(&network, &dataset);
+    assert!(fit.r_squared > 0.95 && fit.rmse < 0.5, "{:?}", fit);
+    let sample: Vec<f64> = dataset.features.row(0).to_vec();
+    let prediction = make_prediction(&network, &sample).unwrap();
+    assert_eq!(prediction.task, TaskType::Regression);
+    assert!(!prediction.is_potable);
+    assert!((prediction.probability - dataset.labels[(0, 0)]).abs() < 1.0);
+
+    // Cross-entropy and class-only options need 0/1 labels
+    assert!(TrainingParams { loss: LossFn::Bce, ..params.clone() }.validate().is_err());
+    assert!(TrainingParams { calibrate: true, ..params.clone() }.validate().is_err());
+    assert!(params.validate().is_ok());
+}
+
+#[test]
+fn min_delta_ignores_negligible_validation_improvements() {
+    let dataset = separable_dataset(300, 31);
+    let validation = separable_dataset(100, 37);
+    let run = |min_delta| {
+        let params = TrainingParams { epochs: 300, early_stopping_patience: 10, min_delta, ..params() };
+        let mut network = create_network(&params).unwrap();
+        let (mut epochs_run, mut validation_losses, mut stopped, mut best) = (0, Vec::new(), None, Vec::new());
+        train_network_validated(&mut network, &dataset, &validation, &params, |_, _, _| epochs_run += 1, |event| match event {
+            TrainingEvent::ValidationLoss { loss, .. } => validation_losses.push(loss),
+            TrainingEvent::EarlyStopped { epoch, best_epoch, .. } => stopped = Some((epoch, best_epoch)),
+            TrainingEvent::BestEpoch { epoch, loss, validation } => best.push((epoch, loss, validation)),
+            _ => {}
+        })
+        .unwrap();
+        (epochs_run, validation_losses, stopped, best)
+    };
+
+    // The validation loss keeps creeping down, so plain patience runs to the end
+    let (all_epochs, losses, stopped, best) = run(0.0);
+    assert_eq!((all_epochs, stopped), (300, None));
+    assert_eq!(losses.len(), 300);
+    // The best epoch is still reported, at the lowest validation loss
+    let lowest = (0..losses.len()).min_by(|&a, &b| losses[a].total_cmp(&losses[b])).unwrap();
+    assert_eq!(best, vec![(lowest, losses[lowest], true)]);
+
+    // Requiring a visible drop stops once the curve flattens, 10 epochs after the last real gain
+    let (epochs_run, _, stopped, best) = run(0.01);
+    let (stop_epoch, best_epoch) = stopped.expect("training should stop early");
+    assert!(epochs_run < all_epochs);
+    assert_eq!((stop_epoch + 1, stop_epoch - best_epoch), (epochs_run, 10));
+    assert_eq!(best.len(), 1);
+    assert_eq!(best[0].0, best_epoch);
+}
+
+#[test]
+fn evaluation_reports_precision_recall_and_f1() {
+    let (_, _, network) = train(&separable_dataset(300, 1));
+    let test = separable_dataset(200, 41);
+
+    let evaluation = evaluate(&network, &test);
+    assert!(evaluation.precision > 0.8 && evaluation.recall > 0.8, "{:?}", evaluation);
+    let harmonic_mean = 2.0 * evaluation.precision * evaluation.recall / (evaluation.precision + evaluation.recall);
+    assert!((evaluation.f1 - harmonic_mean).abs() < 1e-12);
+
+    // No potable samples and none predicted: undefined ratios are reported as 0
+    let negatives = Dataset {
+        features: Array2::from_elem((5, NUM_FEATURES), -10.0) * Array2::from_shape_fn((1, NUM_FEATURES), |(_, j)| W[j].signum()),
+        labels: Array2::zeros((5, 1)),
+        weights: None,
+    };
+    let evaluation = evaluate(&network, &negatives);
+    assert_eq!((evaluation.accuracy, evaluation.precision, evaluation.recall, evaluation.f1), (100.0, 0.0, 0.0, 0.0));
+}
+
+#[test]
+fn alternative_losses_train_and_report_their_own_loss() {
+    let dataset = separable_dataset(300, 43);
+    let run = |loss| {
+        let params = TrainingParams { loss, ..params() };
+        let mut network = create_network(&params).unwrap();
+        let (mut accuracies, mut losses) = (Vec::new(), Vec::new());
+        train_network(&mut network, &dataset, &params, |_, accuracy, loss| {
+            accuracies.push(accuracy);
+            losses.push(loss);
+        })
+        .unwrap();
+        (accuracies, losses)
+    };
+
+    let (bce_accuracies, bce_losses) = run(LossFn::Bce);
+    let (mse_accuracies, mse_losses) = run(LossFn::Mse);
+    let (_, huber_losses) = run(LossFn::Huber { delta: 1.0 });
+
+    // Same initial weights: accuracy agrees before the losses steer training apart
+    assert_eq!(bce_accuracies[0], mse_accuracies[0]);
+    assert!(bce_losses[0] > mse_losses[0]);
+    // Residuals of probabilities never exceed 1, so Huber with delta 1 is half the squared error
+    assert!((huber_losses[0] - 0.5 * mse_losses[0]).abs() < 1e-12);
+
+    assert!(mse_losses.last().unwrap() < &mse_losses[0]);
+    assert!(*mse_accuracies.last().unwrap() > 90.0, "MSE accuracy {:.2}%", mse_accuracies.last().unwrap());
+}
+
+#[test]
+fn dry_run_reports_the_network_without_training_and_catches_bad_input() {
+    let mut dataset = separable_dataset(50, 47);
+    let params = TrainingParams { batch_size: 16, ..params() };
+
+    let check = dry_run(&dataset, &params).unwrap();
+    assert_eq!((check.n_samples, check.batch_size), (50, 16));
+    assert_eq!(check.summary, create_network(&params).unwrap().summary());
+    assert!(check.estimated_bytes > 50 * NUM_FEATURES * 8);
+
+    assert!(dry_run(&dataset, &TrainingParams { learning_rate: 0.0, ..params.clone() }).is_err());
+    assert!(dry_run(&dataset, &TrainingParams { frozen_layers: vec![5], ..params.clone() }).is_err());
+
+    dataset.features[(37, 2)] = f64::NAN;
+    let error = dry_run(&dataset, &params).unwrap_err().to_string();
+    assert!(error.contains("Sample 37"), "{}", error);
+}
+
+#[test]
+fn custom_callbacks_see_the_epoch_state_and_can_stop_training() {
+    // Stops once the training loss falls below a target
+    struct StopAtLoss {
+        target: f64,
+        states: Vec<EpochState>,
+    }
+    impl TrainingCallback for StopAtLoss {
+        fn on_epoch_end(&mut self, state: &EpochState) {
+            self.states.push(state.clone());
+        }
+        fn should_stop(&mut self, state: &EpochState) -> bool {
+            state.loss < self.target
+        }
+    }
+
+    let dataset = separable_dataset(300, 43);
+    let validation = separable_dataset(100, 47);
+    let params = params();
+    let mut callback = StopAtLoss { target: 0.3, states: Vec::new() };
+    let mut network = create_network(&params).unwrap();
+    let dyn_callback: &mut dyn TrainingCallback = &mut callback;
+    train_network_validated(&mut network, &dataset, &validation, &params, dyn_callback, |_| {}).unwrap();
+
+    let last = callback.states.last().unwrap();
+    assert!(last.loss < 0.3 && last.epoch + 1 < params.epochs);
+    assert_eq!(callback.states.len(), last.epoch + 1);
+    assert!(callback.states[..last.epoch].iter().all(|state| state.loss >= 0.3));
+    assert!(callback.states.iter().all(|state| state.epochs == params.epochs && state.validation_loss.is_some()));
+    assert_eq!(callback.states[0].learning_rate, params.learning_rate);
+}
+
+#[test]
+fn input_dim_sets_the_network_width_and_must_match_the_data() {
+    // The first four features of the separable data
+    let full = separable_dataset(200, 53);
+    let dataset = Dataset { features: full.features.slice(ndarray::s![.., ..4]).to_owned(), labels: full.labels.clone(), weights: None };
+    let params = TrainingParams { input_dim: 4, epochs: 50, ..params() };
+
+    let network = create_network(&params).unwrap();
+    assert_eq!(network.input_dim(), 4);
+    assert!(dry_run(&dataset, &params).is_ok());
+    assert!(cross_validate(&dataset, &params, 2, 1).is_ok());
+
+    // The default still expects the water dataset's columns
+    assert!(matches!(
+        dry_run(&dataset, &TrainingParams { input_dim: NUM_FEATURES, ..params.clone() }),
+        Err(NnError::InputDimension { expected: NUM_FEATURES, found: 4 })
+    ));
+    assert!(matches!(params.check_data(&full), Err(NnError::InputDimension { expected: 4, found: NUM_FEATURES })));
+
+    // A continuous column read as the label is caught before classification trains on it
+    let mut continuous = dataset.clone();
+    continuous.labels[(0, 0)] = 2.96;
+    assert!(matches!(params.check_data(&continuous), Err(NnError::Config(_))));
+    assert!(TrainingParams { task: TaskType::Regression, ..params }.check_data(&continuous).is_ok());
+}
+
+#[test]
+fn training_outcome_reports_how_the_run_ended() {
+    let dataset = separable_dataset(300, 59);
+    let validation = separable_dataset(100, 61);
+    let run = |params: &TrainingParams, stop_at: Option<usize>| {
+        let mut network = create_network(params).unwrap();
+        let mut best = None;
+        let outcome = train_network_validated(&mut network, &dataset, &validation, params, StopAt(stop_at), |event| {
+            if let TrainingEvent::BestEpoch { epoch, .. } = event {
+                best = Some(epoch);
+            }
+        })
+        .unwrap();
+        assert_eq!(outcome.best_epoch, best);
+        assert_eq!(outcome.final_metrics.as_ref().map(|state| state.epoch + 1), Some(outcome.epochs_run));
+        outcome
+    };
+    // Asks to stop after the given epoch
+    struct StopAt(Option<usize>);
+    impl TrainingCallback for StopAt {
+        fn on_epoch_end(&mut self, _: &EpochState) {}
+        fn should_stop(&mut self, state: &EpochState) -> bool {
+            self.0 == Some(state.epoch)
+        }
+    }
+
+    let params = TrainingParams { epochs: 40, ..params() };
+    let completed = run(&params, None);
+    assert_eq!((completed.epochs_run, completed.stopped_reason), (40, StopReason::Completed));
+    assert!(completed.final_metrics.unwrap().validation_loss.is_some());
+
+    let cancelled = run(&params, Some(9));
+    assert_eq!((cancelled.epochs_run, cancelled.stopped_reason), (10, StopReason::Cancelled));
+
+    let early = run(&TrainingParams { epochs: 300, early_stopping_patience: 10, min_delta: 0.01, ..params.clone() }, None);
+    assert_eq!(early.stopped_reason, StopReason::EarlyStopped);
+    assert_eq!(early.epochs_run - early.best_epoch.unwrap(), 11);
+
+    // Every epoch outlasts a zero limit, so only the first one runs
+    let timed_out = run(&TrainingParams { time_limit: Some(Duration::ZERO), ..params.clone() }, None);
+    assert_eq!((timed_out.epochs_run, timed_out.stopped_reason), (1, StopReason::TimedOut));
+
+    // Any score reaches a target of 0, and the target beats the other criteria to it
+    let reached = run(&TrainingParams { target_accuracy: Some(0.0), time_limit: Some(Duration::ZERO), ..params.clone() }, None);
+    assert_eq!((reached.epochs_run, reached.stopped_reason), (1, StopReason::TargetReached));
+    let unreachable = run(&TrainingParams { target_accuracy: Some(101.0), ..params.clone() }, None);
+    assert_eq!((unreachable.epochs_run, unreachable.stopped_reason), (40, StopReason::Completed));
+}
+
+#[test]
+fn oversized_networks_are_refused_before_they_are_built() {
+    let dataset = separable_dataset(200, 67);
+    let wide = TrainingParams { neurons_per_layer: 1 << 20, max_memory: 64 << 20, ..params() };
+    let estimated = wide.estimate_memory(dataset.n_samples());
+    assert!(estimated > wide.max_memory);
+
+    // Checked before create_network would allocate the weights
+    assert!(matches!(
+        wide.check_data(&dataset),
+        Err(NnError::MemoryLimit { estimated: e, limit }) if e == estimated && limit == 64 << 20
+    ));
+    assert!(matches!(dry_run(&dataset, &wide), Err(NnError::MemoryLimit { .. })));
+    assert!(matches!(cross_validate(&dataset, &wide, 2, 1), Err(NnError::MemoryLimit { .. })));
+
+    // Training an already built network checks it against the limit too
+    let tight = TrainingParams { max_memory: 1024, ..params() };
+    let mut network = create_network(&tight).unwrap();
+    assert!(matches!(train_network(&mut network, &dataset, &tight, |_, _, _| {}), Err(NnError::MemoryLimit { .. })));
+
+    // The dry run reports the same estimate the guard uses, and 0 lifts the limit
+    let fits = TrainingParams { max_memory: 0, ..tight };
+    assert_eq!(dry_run(&dataset, &fits).unwrap().estimated_bytes, fits.estimate_memory(dataset.n_samples()));
+    assert!(train_network(&mut network, &dataset, &TrainingParams { epochs: 2, ..fits }, |_, _, _| {}).is_ok());
+}
+
+#[test]
+fn validation_metrics_score_the_same_held_out_rows_every_epoch() {
+    let dataset = separable_dataset(300, 71);
+    let validation = separable_dataset(100, 73);
+    let params = TrainingParams { epochs: 30, batch_size: 32, ..params() };
+
+    let mut reported = Vec::new();
+    let mut network = create_network(&params).unwrap();
+    let outcome = train_network_validated(
+        &mut network, &dataset, &validation, &params,
+        ValidationMetrics(|epoch, accuracy, loss| reported.push((epoch, accuracy, loss))),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(reported.len(), params.epochs);
+
+    // The last point is exactly the trained network scored on the validation set
+    let (_, accuracy, loss) = *reported.last().unwrap();
+    let evaluation = evaluate(&network, &validation);
+    assert_eq!(accuracy, evaluation.accuracy);
+    assert!((loss - evaluation.loss).abs() < 1e-12);
+    let last = outcome.final_metrics.unwrap();
+    assert_eq!((last.validation_accuracy, last.validation_loss), (Some(accuracy), Some(loss)));
+
+    // Without a validation set there is nothing to report
+    let mut calls = 0;
+    let mut network = create_network(&params).unwrap();
+    train_network(&mut network, &dataset, &params, ValidationMetrics(|_, _, _| calls += 1)).unwrap();
+    assert_eq!(calls, 0);
+}
+
+#[test]
+fn baseline_scores_the_untrained_network_before_the_first_update() {
+    #[derive(Default)]
+    struct Recorded {
+        baseline: Option<EpochState>,
+        epochs: usize,
+    }
+    impl TrainingCallback for Recorded {
+        fn wants_baseline(&self) -> bool {
+            true
+        }
+        fn on_train_start(&mut self, baseline: &EpochState) {
+            assert_eq!(self.epochs, 0, "the baseline comes before every epoch");
+            self.baseline = Some(baseline.clone());
+        }
+        fn on_epoch_end(&mut self, _: &EpochState) {
+            self.epochs += 1;
+        }
+    }
+
+    let dataset = separable_dataset(300, 79);
+    let validation = separable_dataset(100, 81);
+    // Mini-batches, so the first epoch's average already includes updates
+    let params = TrainingParams { epochs: 5, batch_size: 32, noise_std: 0.1, ..params() };
+    let mut network = create_network(&params).unwrap();
+    let untrained = network.clone();
+    let mut recorded = Recorded::default();
+    train_network_validated(&mut network, &dataset, &validation, &params, &mut recorded as &mut dyn TrainingCallback, |_| {}).unwrap();
+
+    let baseline = recorded.baseline.unwrap();
+    assert_eq!((baseline.epoch, recorded.epochs), (0, params.epochs));
+    let on_training = evaluate(&untrained, &dataset);
+    assert_eq!(baseline.accuracy, on_training.accuracy);
+    assert!((baseline.loss - on_training.loss).abs() < 1e-9, "{} vs {}", baseline.loss, on_training.loss);
+    let on_validation = evaluate(&untrained, &validation);
+    assert_eq!(baseline.validation_accuracy, Some(on_validation.accuracy));
+    assert!((baseline.validation_loss.unwrap() - on_validation.loss).abs() < 1e-9);
+
+    // A closure sees the baseline first, without an epoch
+    let mut epochs = Vec::new();
+    let mut network = create_network(&params).unwrap();
+    train_network(&mut network, &dataset, &params, WithBaseline(|epoch, _, _| epochs.push(epoch))).unwrap();
+    assert_eq!(epochs, [None, Some(0), Some(1), Some(2), Some(3), Some(4)]);
+
+    // A full batch without noise still gets a baseline, so the curve starts at the untrained network
+    let params = TrainingParams { batch_size: 0, noise_std: 0.0, ..params };
+    let mut points = Vec::new();
+    let mut network = create_network(&params).unwrap();
+    let untrained = network.clone();
+    train_network(&mut network, &dataset, &params, WithBaseline(|epoch: Option<usize>, accuracy, _| {
+        points.push((epoch.map_or(0, |epoch| epoch + 1), accuracy))
+    }))
+    .unwrap();
+    assert_eq!(points.len(), params.epochs + 1);
+    assert_eq!(points[0], (0, evaluate(&untrained, &dataset).accuracy));
+
+    // Wrapped in ValidationMetrics, the baseline is the untrained network on the validation set
+    let mut points = Vec::new();
+    let mut network = create_network(&params).unwrap();
+    train_network_validated(
+        &mut network, &dataset, &validation, &params,
+        ValidationMetrics(WithBaseline(|epoch: Option<usize>, accuracy, _| points.push((epoch, accuracy)))),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(points.len(), params.epochs + 1);
+    assert_eq!(points[0], (None, on_validation.accuracy));
+    assert_eq!(points.last().unwrap().1, evaluate(&network, &validation).accuracy);
+}
+
+#[test]
+fn validation_with_frozen_layers_scores_like_the_full_network() {
+    let dataset = separable_dataset(300, 75);
+    let validation = separable_dataset(100, 77);
+
+    for frozen in [vec![0], vec![0, 1], vec![1]] {
+        let params = TrainingParams { epochs: 20, hidden_layers: 2, frozen_layers: frozen.clone(), ..params() };
+        let mut last = None;
+        let mut network = create_network(&params).unwrap();
+        train_network_validated(
+            &mut network, &dataset, &validation, &params,
+            ValidationMetrics(|_, accuracy, loss| last = Some((accuracy, loss))),
+            |_| {},
+        )
+        .unwrap();
+
+        let (accuracy, loss) = last.unwrap();
+        let evaluation = evaluate(&network, &validation);
+        assert_eq!(accuracy, evaluation.accuracy, "frozen {:?}", frozen);
+        assert!((loss - evaluation.loss).abs() < 1e-12, "frozen {:?}: {} vs {}", frozen, loss, evaluation.loss);
+    }
+}
+
+#[test]
+fn input_noise_only_perturbs_training_batches() {
+    let dataset = separable_dataset(300, 83);
+    let validation = separable_dataset(100, 89);
+    let noisy = TrainingParams { epochs: 30, noise_std: 0.5, ..params() };
+    let train = |params: &TrainingParams| {
+        let mut network = create_network(params).unwrap();
+        let outcome = train_network_validated(&mut network, &dataset, &validation, params, |_, _, _| {}, |_| {}).unwrap();
+        (network, outcome.final_metrics.unwrap())
+    };
+
+    // Validation scores the clean rows, and noise drawn from the seed repeats
+    let (network, last) = train(&noisy);
+    let evaluation = evaluate(&network, &validation);
+    assert_eq!(last.validation_accuracy, Some(evaluation.accuracy));
+    assert!((last.validation_loss.unwrap() - evaluation.loss).abs() < 1e-12);
+    assert_eq!(train(&noisy).1, last);
+
+    // The training metrics come from the noisy inputs
+    let (_, clean) = train(&TrainingParams { noise_std: 0.0, ..noisy.clone() });
+    assert_ne!(clean.loss, last.loss);
+    assert!(TrainingParams { noise_std: -0.1, ..noisy }.validate().is_err());
+    assert!(TrainingParams { val_fraction: 0.6, test_fraction: 0.4, ..params() }.validate().is_err());
+    assert!(TrainingParams { val_fraction: 0.2, test_fraction: 0.2, ..params() }.validate().is_ok());
+}
+
+#[test]
+fn tiny_datasets_train_in_one_batch_or_fail_with_a_clear_error() {
+    let params = TrainingParams { epochs: 20, batch_size: 32, ..params() };
+
+    let one = separable_dataset(1, 97);
+    let mut network = create_network(&params).unwrap();
+    let error = train_network(&mut network, &one, &params, |_, _, _| {}).unwrap_err();
+    assert!(matches!(error, NnError::TooFewSamples { needed: MIN_TRAINING_SAMPLES, found: 1, .. }), "{:?}", error);
+    assert!(error.to_string().contains("too few"), "{}", error);
+    assert!(dry_run(&one, &params).is_err());
+
+    // Five rows: the batch size shrinks to the data, and every metric stays finite
+    let five = separable_dataset(5, 101);
+    let (train, validation) = (separable_dataset(4, 103), separable_dataset(1, 107));
+    let mut network = create_network(&params).unwrap();
+    assert_eq!(dry_run(&five, &params).unwrap().batch_size, 5);
+    let outcome = train_network_validated(&mut network, &train, &validation, &params, |_, _, _| {}, |_| {}).unwrap();
+    let last = outcome.final_metrics.unwrap();
+    assert_eq!(outcome.epochs_run, 20);
+    for metric in [last.accuracy, last.loss, last.validation_accuracy.unwrap(), last.validation_loss.unwrap()] {
+        assert!(metric.is_finite(), "{:?}", last);
+    }
+}
+
+#[test]
+fn final_metrics_and_completion_arrive_when_the_ui_channel_is_full() {
+    let dataset = separable_dataset(200, 79);
+    let params = TrainingParams { epochs: 50, ..params() };
+    let (mut metrics, metrics_receiver) = progress_channel(8);
+    let (finished, finished_receiver) = channel();
+
+    // Nobody reads during training, so the channel fills and later epochs are dropped without blocking
+    let mut network = create_network(&params).unwrap();
+    let outcome = train_network(&mut network, &dataset, &params, |epoch, accuracy, loss| {
+        metrics.send((epoch, accuracy, loss)).unwrap();
+    })
+    .unwrap();
+
+    let backend = thread::spawn(move || {
+        metrics.flush().unwrap();
+        finished.send(outcome.epochs_run).unwrap();
+    });
+    let received: Vec<_> = metrics_receiver.iter().collect();
+    backend.join().unwrap();
+
+    let epochs: Vec<usize> = received.iter().map(|&(epoch, _, _)| epoch).collect();
+    assert_eq!(epochs, [0, 1, 2, 3, 4, 5, 6, 7, 49]);
+    let last = outcome.final_metrics.unwrap();
+    assert_eq!(received.last(), Some(&(49, last.accuracy, last.loss)));
+    assert_eq!(finished_receiver.recv_timeout(Duration::from_secs(5)), Ok(params.epochs));
+}